@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lrumap::testing::{check_slab_operations, slab_operations_from_bytes};
+
+fuzz_target!(|data: &[u8]| {
+    check_slab_operations(slab_operations_from_bytes(data));
+});
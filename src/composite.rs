@@ -0,0 +1,114 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// A two-part key that can be used to query maps keyed by `(A, B)` without
+/// constructing an owned tuple.
+///
+/// Each map type in this crate looks up keys using [`Borrow`]. Because
+/// `(A, B)` implements `Borrow<dyn KeyPair<A, B>>`, a tuple of references can
+/// be used to query a map whose keys are owned tuples:
+///
+/// ```rust
+/// use lrumap::{KeyPair, LruHashMap, LruMap};
+///
+/// let mut lru = LruHashMap::new(2);
+/// lru.push((String::from("a"), 1_u32), "a1");
+///
+/// let name = String::from("a");
+/// assert_eq!(lru.get(&(&name, &1_u32) as &dyn KeyPair<_, _>), Some(&"a1"));
+/// ```
+///
+/// `dyn KeyPair<A, B>` hashes and compares the same as `(A, B)`, so it can be
+/// used with [`LruHashMap`](crate::LruHashMap),
+/// [`LruBTreeMap`](crate::LruBTreeMap), and
+/// [`SmallLruMap`](crate::SmallLruMap).
+pub trait KeyPair<A, B> {
+    /// Returns the first part of the key.
+    fn first(&self) -> &A;
+    /// Returns the second part of the key.
+    fn second(&self) -> &B;
+}
+
+impl<A, B> KeyPair<A, B> for (A, B) {
+    fn first(&self) -> &A {
+        &self.0
+    }
+
+    fn second(&self) -> &B {
+        &self.1
+    }
+}
+
+impl<A, B> KeyPair<A, B> for (&A, &B) {
+    fn first(&self) -> &A {
+        self.0
+    }
+
+    fn second(&self) -> &B {
+        self.1
+    }
+}
+
+impl<'a, A, B> Borrow<dyn KeyPair<A, B> + 'a> for (A, B)
+where
+    A: 'a,
+    B: 'a,
+{
+    fn borrow(&self) -> &(dyn KeyPair<A, B> + 'a) {
+        self
+    }
+}
+
+// The implementations below must match the implementations for tuples, which
+// hash and compare each field in order.
+
+impl<A, B> Hash for dyn KeyPair<A, B> + '_
+where
+    A: Hash,
+    B: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.first().hash(state);
+        self.second().hash(state);
+    }
+}
+
+impl<A, B> PartialEq for dyn KeyPair<A, B> + '_
+where
+    A: PartialEq,
+    B: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.first() == other.first() && self.second() == other.second()
+    }
+}
+
+impl<A, B> Eq for dyn KeyPair<A, B> + '_
+where
+    A: Eq,
+    B: Eq,
+{
+}
+
+impl<A, B> PartialOrd for dyn KeyPair<A, B> + '_
+where
+    A: Ord,
+    B: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<A, B> Ord for dyn KeyPair<A, B> + '_
+where
+    A: Ord,
+    B: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.first()
+            .cmp(other.first())
+            .then_with(|| self.second().cmp(other.second()))
+    }
+}
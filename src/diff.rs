@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The differences between the keys of two Least Recently Used maps, as
+/// returned by [`LruPeek::diff()`](crate::LruPeek::diff).
+///
+/// Applying the differences to the first map makes it contain the same keys
+/// in the same order as the second map: remove the keys in
+/// [`removed`](Self::removed), then push the keys in [`added`](Self::added)
+/// and touch the keys in [`reordered`](Self::reordered), merged in the order
+/// they appear in the second map, from least recently used to most recently
+/// used.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CacheDiff<Key> {
+    /// The keys present in the second map but not in the first map, from
+    /// most recently used to least recently used in the second map.
+    pub added: Vec<Key>,
+    /// The keys present in the first map but not in the second map, from most
+    /// recently used to least recently used in the first map.
+    pub removed: Vec<Key>,
+    /// The keys present in both maps that must be touched for the first
+    /// map's order to match the second map's, from most recently used to
+    /// least recently used in the second map.
+    ///
+    /// Only the second map's least recently used keys that are already in
+    /// the same order in the first map, with no added keys between them, are
+    /// not reordered.
+    pub reordered: Vec<Key>,
+}
+
+impl<Key> CacheDiff<Key> {
+    /// Returns true if both maps contain the same keys in the same order.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.reordered.is_empty()
+    }
+}
+
+/// Returns the differences between two sequences of keys, each ordered from
+/// most recently used to least recently used.
+pub fn diff<'a, Key>(
+    from: impl Iterator<Item = &'a Key>,
+    to: impl Iterator<Item = &'a Key>,
+) -> CacheDiff<Key>
+where
+    Key: Hash + Eq + Clone + 'a,
+{
+    let from = from.collect::<Vec<_>>();
+    let positions = from
+        .iter()
+        .enumerate()
+        .map(|(index, &key)| (key, index))
+        .collect::<HashMap<_, _>>();
+
+    let mut retained = vec![false; from.len()];
+    let to = to
+        .map(|key| {
+            let position = positions.get(key).copied();
+            if let Some(position) = position {
+                retained[position] = true;
+            }
+            (key, position)
+        })
+        .collect::<Vec<_>>();
+
+    // The least recently used keys of `to` that are already in the same
+    // order in `from` can stay in place. Every key in front of them must be
+    // pushed or touched.
+    let mut in_place = to.len();
+    let mut next_position = usize::MAX;
+    while let Some(&(_, Some(position))) = in_place.checked_sub(1).map(|index| &to[index]) {
+        if position >= next_position {
+            break;
+        }
+        next_position = position;
+        in_place -= 1;
+    }
+
+    let mut added = Vec::new();
+    let mut reordered = Vec::new();
+    for &(key, position) in &to[..in_place] {
+        if position.is_some() {
+            reordered.push(key.clone());
+        } else {
+            added.push(key.clone());
+        }
+    }
+
+    CacheDiff {
+        added,
+        removed: from
+            .iter()
+            .zip(retained)
+            .filter(|&(_, retained)| !retained)
+            .map(|(&key, _)| key.clone())
+            .collect(),
+        reordered,
+    }
+}
@@ -1,8 +1,15 @@
 use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
 #[cfg(not(feature = "hashbrown"))]
 use std::collections::{hash_map, hash_map::RandomState as DefaultState, HashMap};
 use std::fmt::Debug;
+#[cfg(any(feature = "hashbrown", feature = "hashed-index"))]
+use std::hash::Hasher;
 use std::hash::{BuildHasher, Hash};
+use std::iter::{Peekable, Rev, Take};
+#[cfg(feature = "hashed-index")]
+use std::marker::PhantomData;
+use std::sync::Arc;
 
 #[cfg(feature = "hashbrown")]
 use hashbrown::{
@@ -10,8 +17,14 @@ use hashbrown::{
     HashMap,
 };
 
-use crate::lru::{EntryCache, EntryRef, IntoIter, LruCache, NodeId, Removed};
-use crate::LruMap;
+use crate::lru::{
+    batch, evict_headroom, evict_headroom_sparing, push_with_token, reserve_slot, retain,
+    retain_incremental, try_reserve_slot, with_eviction_capture, ConfigError, CorruptionError,
+    CursorIter, Deterministic, Dirty, DuplicatePolicy, EntryCache, EntryId, EntryRef, Expiring,
+    IntoIter, Iter, IterTail, KeyHandle, KeysByStaleness, LruCache, NodeId, Occupancy,
+    OccupiedSlots, OwnedCursor, Removed, RetainProgress, SlotToken, Trim, Unordered, VacancyStats,
+};
+use crate::{CacheObserver, LruMap, LruPeek};
 
 /// A Least Recently Used map with fixed capacity that stores keys using a
 /// `HashMap` internally. Inserting and querying has similar performance to
@@ -24,13 +37,69 @@ use crate::LruMap;
 /// To avoid `unsafe`, this crate must store each entry's key twice. This means
 /// that `Key` must implement `Clone`. If you're using expensive-to-clone keys,
 /// consider wrapping the key in an `Rc`/`Arc` or using an alternate LRU crate.
+///
+/// When the `hashed-index` feature is enabled, each key is only stored once.
+/// The index stores the hash of each key instead, and compares queries against
+/// the keys stored in the map. `Key` must still implement `Clone` so that
+/// enabling the feature does not change which keys can be used.
+///
+/// The `Observer` is notified of operations performed on this map. See
+/// [`CacheObserver`] for more information.
 #[derive(Debug)]
 #[must_use]
-pub struct LruHashMap<Key, Value, State = DefaultState> {
-    map: HashMap<Key, NodeId, State>,
+pub struct LruHashMap<Key, Value, State = DefaultState, Observer = ()> {
+    index: KeyIndex<Key, State>,
     cache: LruCache<Key, Value>,
+    observer: Observer,
 }
 
+/// An [`LruHashMap`] that stores each key in an [`Arc`].
+///
+/// Because the key is stored twice, wrapping it in an [`Arc`] reduces the cost
+/// of storing each key to a reference count increment, and removes the
+/// requirement for `Key` to implement `Clone`. Lookups can still be performed
+/// using `&Key`, as `Arc<Key>` implements `Borrow<Key>`.
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use lrumap::{LruArcMap, LruMap};
+///
+/// #[derive(Hash, Eq, PartialEq, Debug)]
+/// struct ExpensiveKey(Vec<u8>);
+///
+/// let mut lru = LruArcMap::new(2);
+/// lru.push(Arc::new(ExpensiveKey(vec![1])), 1);
+/// assert_eq!(lru.get(&ExpensiveKey(vec![1])), Some(&1));
+/// ```
+pub type LruArcMap<Key, Value, State = DefaultState, Observer = ()> =
+    LruHashMap<Arc<Key>, Value, State, Observer>;
+
+/// An [`LruHashMap`] that stores each value in an [`Arc`].
+///
+/// [`LruHashMap::get_arc()`] returns a clone of the stored [`Arc`], which can
+/// be used after the map is no longer borrowed. This is most useful with
+/// [`SharedLruMap::get_arc()`](crate::SharedLruMap::get_arc), which only holds
+/// its lock while incrementing the reference count.
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use lrumap::{LruArcValueMap, LruMap};
+///
+/// let mut lru = LruArcValueMap::new(2);
+/// lru.push(1, Arc::new(String::from("one")));
+///
+/// let one = lru.get_arc(&1).unwrap();
+/// lru.push(2, Arc::new(String::from("two")));
+/// lru.push(3, Arc::new(String::from("three")));
+/// // The entry was evicted, but the returned value is still usable.
+/// assert!(lru.get(&1).is_none());
+/// assert_eq!(*one, "one");
+/// ```
+pub type LruArcValueMap<Key, Value, State = DefaultState, Observer = ()> =
+    LruHashMap<Key, Arc<Value>, State, Observer>;
+
 impl<Key, Value> LruHashMap<Key, Value, DefaultState>
 where
     Key: Hash + Eq + Clone,
@@ -39,13 +108,54 @@ where
     ///
     /// # Panics
     ///
-    /// Panics if `capacity` is <= 1.
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
     pub fn new(capacity: usize) -> Self {
-        assert!(capacity > 1);
-        Self {
-            map: HashMap::with_capacity(capacity),
-            cache: LruCache::new(capacity),
-        }
+        Self::with_observer(capacity, ())
+    }
+}
+
+impl<Key, Value, Observer> LruHashMap<Key, Value, DefaultState, Observer>
+where
+    Key: Hash + Eq + Clone,
+    Observer: CacheObserver<Key, Value>,
+{
+    /// Creates a new map with the maximum `capacity` that notifies `observer`
+    /// of operations performed on the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn with_observer(capacity: usize, observer: Observer) -> Self {
+        Self::with_hasher_and_observer(capacity, DefaultState::default(), observer)
+    }
+}
+
+impl<Key, Value> LruHashMap<Key, Value, RandomState>
+where
+    Key: Hash + Eq + Clone,
+{
+    /// Creates a new map with the maximum `capacity` that hashes keys using
+    /// the standard library's [`RandomState`], which is seeded randomly for
+    /// each map.
+    ///
+    /// Without the `hashbrown` feature, this is equivalent to [`Self::new()`].
+    /// With the `hashbrown` feature, the default hasher is not guaranteed to
+    /// be seeded at runtime. Use this function for maps whose keys are
+    /// controlled by untrusted parties, such as network clients.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::with_random_state(2);
+    /// lru.push(String::from("a"), 1);
+    /// assert_eq!(lru.get("a"), Some(&1));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn with_random_state(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
     }
 }
 
@@ -58,12 +168,72 @@ where
     ///
     /// # Panics
     ///
-    /// Panics if `capacity` is <= 1
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
     pub fn with_hasher(capacity: usize, hasher: State) -> Self {
+        Self::with_hasher_and_observer(capacity, hasher, ())
+    }
+}
+
+impl<Key, Value, State, Observer> LruHashMap<Key, Value, State, Observer>
+where
+    Key: Hash + Eq + Clone,
+    State: BuildHasher,
+    Observer: CacheObserver<Key, Value>,
+{
+    /// Creates a new map with the maximum `capacity` and `hasher` that
+    /// notifies `observer` of operations performed on the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn with_hasher_and_observer(capacity: usize, hasher: State, observer: Observer) -> Self {
         assert!(capacity > 1);
+        assert!(capacity <= usize::try_from(u32::MAX).unwrap());
         Self {
-            map: HashMap::with_capacity_and_hasher(capacity, hasher),
+            index: KeyIndex::new(capacity, hasher),
             cache: LruCache::new(capacity),
+            observer,
+        }
+    }
+
+    /// Returns a reference to this map's observer.
+    pub const fn observer(&self) -> &Observer {
+        &self.observer
+    }
+
+    /// Returns an exclusive reference to this map's observer.
+    pub const fn observer_mut(&mut self) -> &mut Observer {
+        &mut self.observer
+    }
+
+    /// Returns a map containing the result of `map(value)` for each value in
+    /// this map.
+    ///
+    /// The keys keep their order, staleness, and scores, and the new map
+    /// keeps this map's settings and observer. This allows a derived cache to
+    /// be built without losing the recency of the original cache's keys.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut raw = LruHashMap::new(3);
+    /// raw.extend([(1, "10"), (2, "20"), (3, "30")]);
+    /// raw.get(&1);
+    ///
+    /// let parsed = raw.map_values(|value| value.parse::<u32>().unwrap());
+    /// assert_eq!(
+    ///     parsed.iter().collect::<Vec<_>>(),
+    ///     [(&1, &10), (&3, &30), (&2, &20)]
+    /// );
+    /// ```
+    pub fn map_values<NewValue>(
+        self,
+        map: impl FnMut(Value) -> NewValue,
+    ) -> LruHashMap<Key, NewValue, State, Observer> {
+        LruHashMap {
+            index: self.index,
+            cache: self.cache.map_values(map),
+            observer: self.observer,
         }
     }
 
@@ -75,8 +245,14 @@ where
         QueryKey: Hash + Eq + ?Sized,
         Key: Borrow<QueryKey>,
     {
-        let node = self.map.get(key).copied();
-        node.map(|node| self.cache.get(node).value())
+        if let Some(node) = self.index.get(key, &self.cache) {
+            let node = self.cache.get(node);
+            self.observer.on_hit(node.key());
+            Some(node.value())
+        } else {
+            self.observer.on_miss();
+            None
+        }
     }
 
     /// Returns the stored value for `key`, if present.
@@ -87,8 +263,37 @@ where
         QueryKey: Hash + Eq + ?Sized,
         Key: Borrow<QueryKey>,
     {
-        let node = self.map.get(key).copied();
-        node.map(|node| self.cache.get_mut(node).value_mut())
+        if let Some(node) = self.index.get(key, &self.cache) {
+            let node = self.cache.get_mut(node);
+            self.observer.on_hit(node.key());
+            Some(node.value_mut())
+        } else {
+            self.observer.on_miss();
+            None
+        }
+    }
+
+    /// Returns true if `key` is present in this map.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(String::from("a"), 1);
+    ///
+    /// assert!(lru.contains_key("a"));
+    /// assert!(!lru.contains_key("b"));
+    /// ```
+    #[must_use]
+    pub fn contains_key<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.index.contains_key(key, &self.cache)
     }
 
     /// Returns the stored value for `key`, if present.
@@ -100,9 +305,230 @@ where
         QueryKey: Hash + Eq + ?Sized,
         Key: Borrow<QueryKey>,
     {
-        self.map
-            .get(key)
-            .map(|node| self.cache.get_without_touch(*node).value())
+        self.index
+            .get(key, &self.cache)
+            .map(|node| self.cache.get_without_touch(node).value())
+    }
+
+    /// Returns a mutable reference to the stored value for `key`, if present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// *lru.get_mut_without_update(&1).unwrap() += 10;
+    /// assert_eq!(lru.tail().unwrap().peek_value(), &11);
+    /// ```
+    pub fn get_mut_without_update<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.index.get(key, &self.cache)?;
+        Some(self.cache.get_mut_without_touch(node).value_mut())
+    }
+
+    /// Returns true if `a` was touched more recently than `b`, or `None` if
+    /// either key is not present.
+    ///
+    /// The keys' last accessed sequences are compared. Neither key is more
+    /// recent than the other if they were last touched in the same
+    /// [batch](Self::batch). This function does not touch either key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2)]);
+    /// assert_eq!(lru.is_more_recent_than(&2, &1), Some(true));
+    ///
+    /// lru.get(&1);
+    /// assert_eq!(lru.is_more_recent_than(&2, &1), Some(false));
+    /// assert_eq!(lru.is_more_recent_than(&2, &3), None);
+    /// ```
+    #[must_use]
+    pub fn is_more_recent_than<QueryKey>(&self, a: &QueryKey, b: &QueryKey) -> Option<bool>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let a = self
+            .cache
+            .get_without_touch(self.index.get(a, &self.cache)?)
+            .last_accessed();
+        let b = self
+            .cache
+            .get_without_touch(self.index.get(b, &self.cache)?)
+            .last_accessed();
+        Some(a > b)
+    }
+
+    /// Returns an iterator over the keys and values beginning with `key` in
+    /// order from most recently touched to least recently touched, if `key`
+    /// is present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///
+    /// let colder = lru.iter_from(&2).unwrap().map(|(key, _)| *key);
+    /// assert_eq!(colder.collect::<Vec<_>>(), vec![2, 1]);
+    /// assert!(lru.iter_from(&4).is_none());
+    /// ```
+    pub fn iter_from<QueryKey>(&self, key: &QueryKey) -> Option<Iter<'_, Key, Value>>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.index
+            .get(key, &self.cache)
+            .map(|node| self.cache.iter_starting_at(node))
+    }
+
+    /// Returns an iterator over the keys and values in order from most
+    /// recently touched to least recently touched.
+    ///
+    /// The order of this iterator is guaranteed to depend only on the sequence
+    /// of operations performed on this map, never on the hasher or its seed.
+    /// Entries that were last touched by the same change, such as within
+    /// [`Self::batch()`], are ordered by when they were touched or inserted.
+    /// This makes the order suitable for snapshot tests of cache state. The
+    /// returned [`Deterministic`] iterator encodes this guarantee in its type,
+    /// allowing test helpers to require it.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut a = LruHashMap::with_random_state(3);
+    /// let mut b = LruHashMap::with_random_state(3);
+    /// for lru in [&mut a, &mut b] {
+    ///     lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///     lru.get(&1);
+    /// }
+    ///
+    /// assert!(a.iter_deterministic().eq(b.iter_deterministic()));
+    /// ```
+    pub const fn iter_deterministic(&self) -> Deterministic<'_, Key, Value> {
+        self.cache.iter_deterministic()
+    }
+
+    /// Invokes `update` with the value stored for `key`, if present. Returns
+    /// true if `key` was found.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert!(lru.update(&1, |value| *value += 10));
+    /// assert!(!lru.update(&3, |value| *value += 10));
+    /// assert_eq!(lru.head().unwrap().key(), &1);
+    /// assert_eq!(lru.get_without_update(&1), Some(&11));
+    /// ```
+    pub fn update<QueryKey, Update>(&mut self, key: &QueryKey, update: Update) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+        Update: FnOnce(&mut Value),
+    {
+        self.get_mut(key).map(update).is_some()
+    }
+
+    /// Invokes `update` with the value stored for `key`, if present. Returns
+    /// true if `key` was found.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert!(lru.update_without_touch(&1, |value| *value += 10));
+    /// assert_eq!(lru.head().unwrap().key(), &2);
+    /// assert_eq!(lru.get_without_update(&1), Some(&11));
+    /// ```
+    pub fn update_without_touch<QueryKey, Update>(&mut self, key: &QueryKey, update: Update) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+        Update: FnOnce(&mut Value),
+    {
+        if let Some(node) = self.index.get(key, &self.cache) {
+            update(self.cache.get_mut_without_touch(node).value_mut());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Invokes `modify` with the key and value stored for each of `keys` that
+    /// is present, returning the keys that were not found.
+    ///
+    /// This function touches each key that is found, making the last found
+    /// key the most recently used key. All touches are grouped into a single
+    /// change, as with [`Self::batch()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///
+    /// let missing = lru.modify_many(&[1, 4, 2], |_, value| *value += 10);
+    /// assert_eq!(missing, [&4]);
+    /// assert_eq!(
+    ///     lru.iter().collect::<Vec<_>>(),
+    ///     [(&2, &12), (&1, &11), (&3, &3)]
+    /// );
+    /// ```
+    pub fn modify_many<'keys, QueryKey, Keys, Modify>(
+        &mut self,
+        keys: Keys,
+        mut modify: Modify,
+    ) -> Vec<&'keys QueryKey>
+    where
+        QueryKey: Hash + Eq + ?Sized + 'keys,
+        Key: Borrow<QueryKey>,
+        Keys: IntoIterator<Item = &'keys QueryKey>,
+        Modify: FnMut(&Key, &mut Value),
+    {
+        batch(self, |lru| {
+            let mut missing = Vec::new();
+            for key in keys {
+                if let Some(node) = lru.index.get(key, &lru.cache) {
+                    let node = lru.cache.get_mut(node);
+                    lru.observer.on_hit(node.key());
+                    let (key, value) = node.key_and_value_mut();
+                    modify(key, value);
+                } else {
+                    lru.observer.on_miss();
+                    missing.push(key);
+                }
+            }
+            missing
+        })
     }
 
     /// Returns an [`EntryRef`] for `key`, if present.
@@ -137,96 +563,1546 @@ where
         QueryKey: Hash + Eq + ?Sized,
         Key: Borrow<QueryKey>,
     {
-        self.map
-            .get(key)
-            .copied()
+        self.index
+            .get(key, &self.cache)
             .map(|node| EntryRef::new(self, node))
     }
 
-    /// Inserts `value` for `key` into this map. If a value is already stored
-    /// for this key, [`Removed::PreviousValue`] is returned with the previously
-    /// stored value. If no value is currently stored and the map is full, the
-    /// least recently used entry will be returned in [`Removed::Evicted`].
-    /// Otherwise, `None` will be returned.
+    /// Touches `key`, moving it to be the second most recently used key
+    /// rather than the head. Returns true if `key` was found.
     ///
-    /// This function touches the key, making it the most recently used key.
+    /// This is equivalent to calling [`Self::touch_to_depth()`] with a depth
+    /// of 1.
+    pub fn touch_soft<QueryKey>(&mut self, key: &QueryKey) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.touch_to_depth(key, 1)
+    }
+
+    /// Touches `key`, moving it to be `depth` entries behind the most recently
+    /// used key. Returns true if `key` was found. A `depth` of 0 is equivalent
+    /// to touching the key normally.
     ///
-    /// ```rust
-    /// use lrumap::{LruHashMap, LruMap, Removed};
+    /// If the key is already within `depth` entries of the head, its position
+    /// is preserved. This allows accesses such as periodic background scans
+    /// to keep entries from being evicted without hiding which entries were
+    /// most recently used by other accesses. Moving an entry this way takes
+    /// the staleness of the entry in front of it rather than resetting it.
     ///
-    /// let mut lru = LruHashMap::new(3);
-    /// lru.push(1, 1);
-    /// lru.push(2, 2);
-    /// lru.push(3, 3);
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
     ///
-    /// // The cache is now full. The next push will evict an entry.
-    /// let removed = lru.push(4, 4);
-    /// assert_eq!(removed, Some(Removed::Evicted(1, 1)));
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
     ///
-    /// // This leaves the cache with 4 as the most recent key, and 2 as the
-    /// // least recent key.
-    /// assert_eq!(lru.head().unwrap().key(), &4);
-    /// assert_eq!(lru.tail().unwrap().key(), &2);
+    /// assert!(lru.touch_soft(&1));
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [4, 1, 3, 2]);
+    /// assert!(lru.touch_to_depth(&2, 2));
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [4, 1, 2, 3]);
+    /// assert!(!lru.touch_soft(&5));
     /// ```
-    pub fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
-        // Create the new entry for this key/value pair, which also puts it at
-        // the front of the LRU
-        // let existing_entry = self.map.entry(key.clone());
-        let entry = self.map.entry(key.clone());
-
-        if let hash_map::Entry::Occupied(entry) = &entry {
-            let node_ref = *entry.get();
-            // Swap the value out.
-            let value = self.cache.get_mut(node_ref).replace_value(value);
-
-            return Some(Removed::PreviousValue(value));
+    pub fn touch_to_depth<QueryKey>(&mut self, key: &QueryKey, depth: usize) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        if let Some(node) = self.index.get(key, &self.cache) {
+            self.cache.touch_to_depth(node, depth);
+            true
+        } else {
+            false
         }
+    }
 
-        // Key is not currently contained. Create a new node.
-        let (node, result) = self.cache.push(key, value);
-
-        // Insert the node
-        entry.or_insert(node);
-
-        if let Some(Removed::Evicted(key, _)) = &result {
-            self.map.remove(key);
+    /// Marks the entry for `key` as dirty, returning true if `key` was found.
+    /// See [`EntryRef::mark_dirty()`] for more information.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    pub fn mark_dirty<QueryKey>(&mut self, key: &QueryKey) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        if let Some(node) = self.index.get(key, &self.cache) {
+            self.cache.get_mut_without_touch(node).set_dirty(true);
+            true
+        } else {
+            false
         }
-
-        result
     }
 
-    /// Pushes all items from `iterator` into this map. If there are more
-    /// entries in the iterator than capacity remaining, keys will be evicted as
-    /// needed.
+    /// Returns an [`EntryRef`] for the key referenced by `handle`, if it is
+    /// still present.
     ///
-    /// This function is equivalent to a for loop calling [`Self::push()`].
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
     ///
     /// ```rust
     /// use lrumap::{LruHashMap, LruMap};
     ///
-    /// let mut lru = LruHashMap::new(3);
-    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// let handle = lru.entry(&1).unwrap().downgrade();
     ///
-    /// assert_eq!(lru.head().unwrap().key(), &4);
-    /// assert_eq!(lru.tail().unwrap().key(), &2);
+    /// lru.push(2, 2);
+    /// assert_eq!(lru.upgrade(&handle).unwrap().peek_value(), &1);
+    /// lru.push(3, 3);
+    /// assert!(lru.upgrade(&handle).is_none());
     /// ```
-    pub fn extend<IntoIter: IntoIterator<Item = (Key, Value)>>(&mut self, iterator: IntoIter) {
+    pub fn upgrade(&mut self, handle: &KeyHandle<Key>) -> Option<EntryRef<'_, Self, Key, Value>> {
+        let node = self
+            .cache
+            .resolve(handle)
+            .or_else(|| self.index.get(handle.key(), &self.cache))?;
+        Some(EntryRef::new(self, node))
+    }
+
+    /// Returns an [`EntryRef`] for the entry `cursor` was created for, if it
+    /// is still present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///
+    /// let cursor = lru.entry(&2).unwrap().cursor();
+    ///
+    /// // The map can be modified while the cursor is held.
+    /// lru.push(4, 4);
+    /// assert_eq!(lru.resume(cursor).unwrap().key(), &2);
+    /// lru.push(5, 5);
+    /// assert!(lru.resume(cursor).is_none());
+    /// ```
+    pub fn resume(&mut self, cursor: OwnedCursor) -> Option<EntryRef<'_, Self, Key, Value>> {
+        let node = self.cache.resume(cursor)?;
+        Some(EntryRef::new(self, node))
+    }
+
+    /// Invokes `operations` with this map, grouping all touches it performs
+    /// into a single change. Returns the result of `operations`.
+    ///
+    /// Each touch normally counts as a change to the map, which increases the
+    /// [staleness](EntryRef::staleness) of every other entry. Grouping the
+    /// touches performed by a single logical operation keeps staleness
+    /// proportional to the number of logical operations. Keys touched within
+    /// the batch are still moved to the front of the lru cache in the order
+    /// they are touched. Calls to this function can be nested.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// lru.batch(|lru| {
+    ///     lru.get(&1);
+    ///     lru.get(&2);
+    ///     lru.get(&3);
+    /// });
+    ///
+    /// assert_eq!(lru.entry(&4).unwrap().staleness(), 1);
+    /// assert_eq!(lru.entry(&1).unwrap().staleness(), 0);
+    /// assert_eq!(lru.head().unwrap().key(), &3);
+    /// ```
+    pub fn batch<Operations, Output>(&mut self, operations: Operations) -> Output
+    where
+        Operations: FnOnce(&mut Self) -> Output,
+    {
+        batch(self, operations)
+    }
+
+    /// Invokes `operations` with this map, returning its result along with a
+    /// copy of every entry evicted while it ran, from first to last evicted.
+    ///
+    /// Entries are captured regardless of which function evicted them,
+    /// including entries evicted to restore
+    /// [headroom](Self::set_headroom) that are not returned to the caller.
+    /// Entries that are removed explicitly, such as through
+    /// [`EntryRef::take()`], are not evictions and are not captured. Calls to
+    /// this function can be nested, and the entries captured by an inner call
+    /// are also returned from the outer call.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// let ((), evicted) = lru.with_eviction_capture(|lru| {
+    ///     lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///     lru.push(4, 4);
+    /// });
+    ///
+    /// assert_eq!(evicted, vec![(1, 1), (2, 2)]);
+    /// ```
+    pub fn with_eviction_capture<Operations, Output>(
+        &mut self,
+        operations: Operations,
+    ) -> (Output, Vec<(Key, Value)>)
+    where
+        Operations: FnOnce(&mut Self) -> Output,
+        Value: Clone,
+    {
+        with_eviction_capture(self, operations)
+    }
+
+    /// Removes `key` from this map, returning the stored key and value if
+    /// present.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(String::from("a"), 1);
+    ///
+    /// assert_eq!(lru.take("a"), Some((String::from("a"), 1)));
+    /// assert_eq!(lru.take("a"), None);
+    /// assert!(lru.is_empty());
+    /// ```
+    pub fn take<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.index.remove(key, &self.cache)?;
+        let (removed, ..) = self.cache.remove(node);
+        Some(removed)
+    }
+
+    /// Removes `key` from this map, returning the stored value if present.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(String::from("a"), 1);
+    ///
+    /// assert_eq!(lru.remove("a"), Some(1));
+    /// assert_eq!(lru.remove("a"), None);
+    /// assert!(lru.is_empty());
+    /// ```
+    pub fn remove<QueryKey>(&mut self, key: &QueryKey) -> Option<Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.take(key).map(|(_, value)| value)
+    }
+
+    /// Removes `key` from this map, returning the stored key and value if
+    /// present. This is equivalent to [`Self::take()`], and is useful when
+    /// the stored key owns data that the query key does not.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// let key: Arc<str> = Arc::from("a");
+    /// lru.push(key.clone(), 1);
+    ///
+    /// let (removed, value) = lru.remove_entry("a").unwrap();
+    /// assert!(Arc::ptr_eq(&removed, &key));
+    /// assert_eq!(value, 1);
+    /// assert_eq!(lru.remove_entry("a"), None);
+    /// ```
+    pub fn remove_entry<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.take(key)
+    }
+
+    /// Removes the least recently used entry from this map, returning its key
+    /// and value if the map is not empty.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// assert_eq!(lru.pop_least_recent(), Some((2, 2)));
+    /// assert_eq!(lru.pop_least_recent(), Some((3, 3)));
+    /// assert_eq!(lru.pop_least_recent(), Some((1, 1)));
+    /// assert_eq!(lru.pop_least_recent(), None);
+    /// ```
+    pub fn pop_least_recent(&mut self) -> Option<(Key, Value)> {
+        let tail = self.cache.tail()?;
+        let (removed, ..) = self.remove_node(tail);
+        Some(removed)
+    }
+
+    /// Removes the most recently used entry from this map, returning its key
+    /// and value if the map is not empty.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// assert_eq!(lru.pop_most_recent(), Some((1, 1)));
+    /// assert_eq!(lru.pop_most_recent(), Some((3, 3)));
+    /// assert_eq!(lru.pop_most_recent(), Some((2, 2)));
+    /// assert_eq!(lru.pop_most_recent(), None);
+    /// ```
+    pub fn pop_most_recent(&mut self) -> Option<(Key, Value)> {
+        let head = self.cache.head()?;
+        let (removed, ..) = self.remove_node(head);
+        Some(removed)
+    }
+
+    /// Removes every entry from this map. The storage allocated for the
+    /// entries is kept, allowing the map to be refilled without allocating.
+    ///
+    /// The observer is not notified of the removed entries.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.clear();
+    /// assert!(lru.is_empty());
+    /// assert!(lru.get(&1).is_none());
+    ///
+    /// lru.push(4, 4);
+    /// assert_eq!(lru.vacancy_stats().vacant_slots, 2);
+    /// ```
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.cache.clear();
+    }
+
+    /// Inserts `value` for `key` into this map. If a value is already stored
+    /// for this key, [`Removed::PreviousValue`] is returned with the previously
+    /// stored value. If no value is currently stored and the map is full, the
+    /// least recently used entry will be returned in [`Removed::Evicted`].
+    /// Otherwise, `None` will be returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, Removed};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    /// lru.push(3, 3);
+    ///
+    /// // The cache is now full. The next push will evict an entry.
+    /// let removed = lru.push(4, 4);
+    /// assert_eq!(removed, Some(Removed::Evicted(1, 1)));
+    ///
+    /// // This leaves the cache with 4 as the most recent key, and 2 as the
+    /// // least recent key.
+    /// assert_eq!(lru.head().unwrap().key(), &4);
+    /// assert_eq!(lru.tail().unwrap().key(), &2);
+    /// ```
+    pub fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
+        // Create the new entry for this key/value pair, which also puts it at
+        // the front of the LRU
+        let slot = self.index.slot(&key, &self.cache);
+
+        if let Some(node_ref) = slot.node() {
+            // Swap the value out.
+            let node = self.cache.get_mut(node_ref);
+            let value = node.replace_value(value);
+            self.observer.on_replace(node.key());
+
+            return Some(Removed::PreviousValue(value));
+        }
+
+        // Key is not currently contained. Create a new node.
+        let dirty = self.cache.evictee_is_dirty();
+        let (node, result) = self.cache.push(key, value);
+
+        // Insert the node
+        let evicted_hash = slot.insert(node);
+
+        if let Some(Removed::Evicted(key, value)) = &result {
+            self.index.remove_hashed(evicted_hash, key);
+            self.notify_evicted(key, value, dirty);
+            evict_headroom(self);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        result
+    }
+
+    /// Inserts `value` for `key` into this map if `key` is not already
+    /// present. If the map is full, the least recently used entry will be
+    /// returned in [`Removed::Evicted`]. If `key` is already present, the map
+    /// is not modified and `key` and `value` are returned as an error.
+    ///
+    /// Unlike [`Self::push()`], this function never replaces an existing
+    /// value. This function touches the key, making it the most recently used
+    /// key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, Removed};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// assert_eq!(lru.insert_new(1, 1), Ok(None));
+    /// assert_eq!(lru.insert_new(1, 11), Err((1, 11)));
+    /// assert_eq!(lru.insert_new(2, 2), Ok(None));
+    /// assert_eq!(lru.insert_new(3, 3), Ok(Some(Removed::Evicted(1, 1))));
+    /// ```
+    pub fn insert_new(
+        &mut self,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Removed<Key, Value>>, (Key, Value)> {
+        let slot = self.index.slot(&key, &self.cache);
+        if slot.node().is_some() {
+            return Err((key, value));
+        }
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, result) = self.cache.push(key, value);
+        let evicted_hash = slot.insert(node);
+
+        if let Some(Removed::Evicted(key, value)) = &result {
+            self.index.remove_hashed(evicted_hash, key);
+            self.notify_evicted(key, value, dirty);
+            evict_headroom(self);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        Ok(result)
+    }
+
+    /// Inserts `value` for `key` into this map, subject to the rate set with
+    /// [`Self::set_insertion_sampling()`]. If `key` is already present, its
+    /// value is replaced as with [`Self::push()`]. Otherwise, if `key` is not
+    /// admitted, the map is not modified and `key` and `value` are returned
+    /// as an error.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, Removed};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.set_insertion_sampling(2);
+    /// assert_eq!(lru.push_sampled(1, 1), Ok(None));
+    /// assert_eq!(lru.push_sampled(2, 2), Err((2, 2)));
+    /// assert_eq!(lru.push_sampled(1, 11), Ok(Some(Removed::PreviousValue(1))));
+    /// assert_eq!(lru.push_sampled(2, 2), Ok(None));
+    /// ```
+    pub fn push_sampled(
+        &mut self,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Removed<Key, Value>>, (Key, Value)> {
+        if self.index.get(&key, &self.cache).is_none() && !self.cache.admit() {
+            return Err((key, value));
+        }
+        Ok(self.push(key, value))
+    }
+
+    /// Replaces the value stored for `key` with `new_value`, returning the
+    /// previous value. If `key` is not present, the map is not modified,
+    /// `new_value` is dropped, and `None` is returned.
+    ///
+    /// Unlike [`Self::push()`], this function never inserts a new entry.
+    /// This function touches the key, making it the most recently used key.
+    /// Use [`Self::swap_value()`] to replace the value without touching the
+    /// key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert_eq!(lru.replace_existing(&1, 11), Some(1));
+    /// assert_eq!(lru.replace_existing(&3, 33), None);
+    /// assert_eq!(lru.head().unwrap().peek_value(), &11);
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    pub fn replace_existing<QueryKey>(&mut self, key: &QueryKey, new_value: Value) -> Option<Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.index.get(key, &self.cache)?;
+        let node = self.cache.get_mut(node);
+        let value = node.replace_value(new_value);
+        self.observer.on_replace(node.key());
+        Some(value)
+    }
+
+    /// Updates the value stored for `key` using `update` if present, otherwise
+    /// inserts the value returned by `insert`. If inserting a new entry
+    /// requires evicting the least recently used entry, the evicted entry is
+    /// returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// assert_eq!(lru.upsert(1, |count| *count += 1, || 1), None);
+    /// assert_eq!(lru.upsert(1, |count| *count += 1, || 1), None);
+    /// assert_eq!(lru.get_without_update(&1), Some(&2));
+    ///
+    /// lru.push(2, 1);
+    /// assert_eq!(lru.upsert(3, |count| *count += 1, || 1), Some((1, 2)));
+    /// ```
+    pub fn upsert<Update, Insert>(
+        &mut self,
+        key: Key,
+        update: Update,
+        insert: Insert,
+    ) -> Option<(Key, Value)>
+    where
+        Update: FnOnce(&mut Value),
+        Insert: FnOnce() -> Value,
+    {
+        let slot = self.index.slot(&key, &self.cache);
+
+        if let Some(node) = slot.node() {
+            let node = self.cache.get_mut(node);
+            self.observer.on_hit(node.key());
+            update(node.value_mut());
+
+            return None;
+        }
+
+        self.observer.on_miss();
+        let dirty = self.cache.evictee_is_dirty();
+        let (node, result) = self.cache.push(key, insert());
+        let evicted_hash = slot.insert(node);
+
+        let evicted = if let Some(Removed::Evicted(key, value)) = result {
+            self.index.remove_hashed(evicted_hash, &key);
+            self.notify_evicted(&key, &value, dirty);
+            evict_headroom(self);
+            Some((key, value))
+        } else {
+            None
+        };
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        evicted
+    }
+
+    /// Inserts `value` for `key` into this map. If a value is already stored
+    /// for this key, `merge` is called to combine `value` into the stored
+    /// value. If no value is currently stored and the map is full, the least
+    /// recently used entry is evicted and returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// let append = |stored: &mut Vec<u32>, new: Vec<u32>| stored.extend(new);
+    /// assert_eq!(lru.push_or_merge(1, vec![1], append), None);
+    /// assert_eq!(lru.push_or_merge(1, vec![2], append), None);
+    /// assert_eq!(lru.get_without_update(&1), Some(&vec![1, 2]));
+    ///
+    /// lru.push(2, vec![]);
+    /// assert_eq!(lru.push_or_merge(3, vec![3], append), Some((1, vec![1, 2])));
+    /// ```
+    pub fn push_or_merge<Merge>(
+        &mut self,
+        key: Key,
+        value: Value,
+        merge: Merge,
+    ) -> Option<(Key, Value)>
+    where
+        Merge: FnOnce(&mut Value, Value),
+    {
+        if let Some(node) = self.index.get(&key, &self.cache) {
+            let node = self.cache.get_mut(node);
+            merge(node.value_mut(), value);
+            self.observer.on_replace(node.key());
+
+            return None;
+        }
+
+        match self.push(key, value) {
+            Some(Removed::Evicted(key, value)) => Some((key, value)),
+            _ => None,
+        }
+    }
+
+    /// Reserves a slot for a new entry, returning a token that allows pushing
+    /// a new key without evicting an entry. If pushing a new key would evict
+    /// an entry, the least recently used entry is evicted now and returned.
+    ///
+    /// This allows flushing an evicted entry before constructing an expensive
+    /// value. Until the token is passed to [`Self::push_with_token()`] or
+    /// [`Self::release_slot()`], or dropped, the reserved slot is treated as
+    /// occupied.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.extend([(1, 1), (2, 2)]);
+    ///
+    /// let (token, evicted) = lru.reserve_slot();
+    /// assert_eq!(evicted, Some((1, 1)));
+    /// assert_eq!(lru.push_with_token(token, 3, 3), Ok(None));
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if reserving a slot would leave no unreserved slots.
+    pub fn reserve_slot(&mut self) -> (SlotToken, Option<(Key, Value)>) {
+        reserve_slot(self)
+    }
+
+    /// Reserves a slot for a later insert, returning an error instead of
+    /// panicking if reserving a slot would leave no unreserved slots. See
+    /// [`Self::reserve_slot()`] for more information.
+    pub fn try_reserve_slot(&mut self) -> Result<(SlotToken, Option<(Key, Value)>), ConfigError> {
+        try_reserve_slot(self)
+    }
+
+    /// Inserts `value` for `key` into the slot reserved by `token`, releasing
+    /// the reservation. If a value is already stored for this key, the value
+    /// is replaced and the previous value is returned.
+    ///
+    /// This function never evicts an entry, and it touches the key, making it
+    /// the most recently used key.
+    ///
+    /// # Errors
+    ///
+    /// If `token` was returned from `reserve_slot()` on another map, this map
+    /// is not modified and `key` and `value` are returned. The token's slot is
+    /// released in the map that reserved it.
+    pub fn push_with_token(
+        &mut self,
+        token: SlotToken,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Value>, (Key, Value)> {
+        push_with_token(self, token, key, value, Self::push)
+    }
+
+    /// Releases the slot reserved by `token` without inserting an entry. This
+    /// is equivalent to dropping `token`.
+    pub fn release_slot(&mut self, token: SlotToken) {
+        debug_assert!(
+            self.cache.owns(&token),
+            "token was not reserved by this map"
+        );
+        drop(token);
+    }
+
+    /// Replaces the value stored for `key` with `new_value` if `predicate`
+    /// returns true for the currently stored value. Returns `Ok(Some(..))`
+    /// with the previous value if it was replaced, or `Err(new_value)` if
+    /// `predicate` returned false.
+    ///
+    /// If `key` is not present, `new_value` is inserted and `Ok(None)` is
+    /// returned. If the map is full, inserting will evict the least recently
+    /// used entry. The evicted entry is reported to this map's observer and
+    /// dropped. [`Self::would_evict()`] can be used to check whether an
+    /// eviction would happen beforehand.
+    ///
+    /// If the value is replaced or inserted, this function touches the key,
+    /// making it the most recently used key. Otherwise, the key's position is
+    /// preserved.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// let newer = |current: &(u32, &str)| current.0 < 2;
+    /// assert_eq!(lru.replace_if(1, (1, "one"), newer), Ok(None));
+    /// assert_eq!(lru.replace_if(1, (2, "two"), newer), Ok(Some((1, "one"))));
+    /// assert_eq!(lru.replace_if(1, (3, "three"), newer), Err((3, "three")));
+    /// assert_eq!(lru.get_without_update(&1), Some(&(2, "two")));
+    /// ```
+    pub fn replace_if<Predicate>(
+        &mut self,
+        key: Key,
+        new_value: Value,
+        predicate: Predicate,
+    ) -> Result<Option<Value>, Value>
+    where
+        Predicate: FnOnce(&Value) -> bool,
+    {
+        let slot = self.index.slot(&key, &self.cache);
+
+        if let Some(node_ref) = slot.node() {
+            if !predicate(self.cache.get_without_touch(node_ref).value()) {
+                return Err(new_value);
+            }
+
+            let node = self.cache.get_mut(node_ref);
+            let value = node.replace_value(new_value);
+            self.observer.on_replace(node.key());
+
+            return Ok(Some(value));
+        }
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, result) = self.cache.push(key, new_value);
+        let evicted_hash = slot.insert(node);
+
+        if let Some(Removed::Evicted(key, value)) = &result {
+            self.index.remove_hashed(evicted_hash, key);
+            self.notify_evicted(key, value, dirty);
+            evict_headroom(self);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        Ok(None)
+    }
+
+    /// Replaces the value stored for `key` with `new_value`, returning the
+    /// previous value. If `key` is not present, `new_value` is dropped and
+    /// `None` is returned.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache. This makes it suitable for refreshing values in the
+    /// background without affecting which keys are considered recently used.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert_eq!(lru.swap_value(&1, 11), Some(1));
+    /// assert_eq!(lru.swap_value(&3, 33), None);
+    /// assert_eq!(lru.tail().unwrap().peek_value(), &11);
+    /// ```
+    pub fn swap_value<QueryKey>(&mut self, key: &QueryKey, new_value: Value) -> Option<Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.index.get(key, &self.cache)?;
+        let node = self.cache.get_mut_without_touch(node);
+        let value = node.replace_value(new_value);
+        self.observer.on_replace(node.key());
+        Some(value)
+    }
+
+    /// Replaces the value stored for `key` with `new_value` and restarts its
+    /// [age since insertion](EntryRef::age_since_insert), returning the
+    /// previous value. If `key` is not present, `new_value` is dropped and
+    /// `None` is returned.
+    ///
+    /// Like [`Self::swap_value()`], this function does not touch the key. A
+    /// refresh-ahead loop can refresh the entries returned from
+    /// [`LruPeek::iter_expiring_within()`] without making them more recently
+    /// used than the entries that were requested.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert_eq!(lru.refresh(&1, 11), Some(1));
+    /// assert_eq!(lru.refresh(&3, 33), None);
+    /// let refreshed = lru.tail().unwrap();
+    /// assert_eq!(refreshed.peek_value(), &11);
+    /// assert_eq!(refreshed.age_since_insert(), 0);
+    /// assert_eq!(refreshed.staleness(), 1);
+    /// ```
+    pub fn refresh<QueryKey>(&mut self, key: &QueryKey, new_value: Value) -> Option<Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.index.get(key, &self.cache)?;
+        let sequence = self.cache.sequence();
+        let node = self.cache.get_mut_without_touch(node);
+        let value = node.refresh_value(new_value, sequence);
+        self.observer.on_replace(node.key());
+        Some(value)
+    }
+
+    /// Pushes all items from `iterator` into this map. If there are more
+    /// entries in the iterator than capacity remaining, keys will be evicted as
+    /// needed.
+    ///
+    /// This function is equivalent to a for loop calling [`Self::push()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    ///
+    /// assert_eq!(lru.head().unwrap().key(), &4);
+    /// assert_eq!(lru.tail().unwrap().key(), &2);
+    /// ```
+    pub fn extend<IntoIter: IntoIterator<Item = (Key, Value)>>(&mut self, iterator: IntoIter) {
+        for (key, value) in iterator {
+            self.push(key, value);
+        }
+    }
+
+    /// Pushes all items from `iterator` into this map, handling keys that are
+    /// already present according to `on_duplicate`. New keys are pushed, and
+    /// keys will be evicted as needed.
+    ///
+    /// This allows loading a snapshot into a live map without overwriting
+    /// values that have been updated since the snapshot was taken.
+    ///
+    /// ```rust
+    /// use lrumap::{DuplicatePolicy, LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.push(1, 10);
+    ///
+    /// lru.extend_with([(1, 1), (2, 2)], DuplicatePolicy::Ignore);
+    /// assert_eq!(lru.get_without_update(&1), Some(&10));
+    ///
+    /// lru.extend_with([(1, 5), (3, 3)], DuplicatePolicy::Merge(|stored, new| *stored += new));
+    /// assert_eq!(lru.get_without_update(&1), Some(&15));
+    /// assert_eq!(lru.len(), 3);
+    /// ```
+    pub fn extend_with<IntoIter: IntoIterator<Item = (Key, Value)>>(
+        &mut self,
+        iterator: IntoIter,
+        on_duplicate: DuplicatePolicy<Value>,
+    ) {
         for (key, value) in iterator {
+            match on_duplicate {
+                DuplicatePolicy::Merge(merge) => {
+                    self.push_or_merge(key, value, merge);
+                }
+                DuplicatePolicy::Ignore if self.index.contains_key(&key, &self.cache) => {}
+                DuplicatePolicy::Replace | DuplicatePolicy::Ignore => {
+                    self.push(key, value);
+                }
+            }
+        }
+    }
+
+    /// Pushes items from `iterator` into this map until pushing the next item
+    /// would require evicting an entry. Returns the remaining items, including
+    /// the item that was not pushed.
+    ///
+    /// Keys that are already present are always pushed, because replacing a
+    /// value never requires an eviction. When evictions are deferred, new keys
+    /// are only pushed while the map is below its capacity.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.push(1, 1);
+    /// let mut remaining = lru.extend_until_full([(2, 2), (1, 11), (3, 3), (4, 4)]);
+    ///
+    /// assert_eq!(remaining.next(), Some((4, 4)));
+    /// assert_eq!(lru.len(), 3);
+    /// assert_eq!(lru.get_without_update(&1), Some(&11));
+    /// ```
+    pub fn extend_until_full<IntoIter: IntoIterator<Item = (Key, Value)>>(
+        &mut self,
+        iterator: IntoIter,
+    ) -> Peekable<IntoIter::IntoIter> {
+        let mut iterator = iterator.into_iter().peekable();
+        while let Some((key, value)) = iterator
+            .next_if(|(key, _)| !self.cache.is_full() || self.index.contains_key(key, &self.cache))
+        {
             self.push(key, value);
         }
+        iterator
+    }
+
+    /// Inserts a previously evicted entry back into this map as the least
+    /// recently used entry. This is useful when an eviction turns out to be
+    /// premature, such as when persisting an evicted entry fails.
+    ///
+    /// If `removed` is [`Removed::Evicted`] and its key is not currently
+    /// present, the entry is inserted at the least recently used end of this
+    /// map. If the map is full, the current least recently used entry is
+    /// evicted to make room and returned in `Ok(Some(..))`. The configured
+    /// [headroom](Self::set_headroom) is then evicted, as when pushing, from
+    /// the entries more recently used than the reinserted entry.
+    ///
+    /// [`Removed::PreviousValue`] does not contain a key and cannot be
+    /// reinserted. It is returned in `Err` along with evicted entries whose
+    /// key has been pushed again since they were evicted.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// let removed = lru.push(4, 4).unwrap();
+    ///
+    /// // Reinserting evicts the current tail.
+    /// assert_eq!(lru.reinsert(removed), Ok(Some((2, 2))));
+    /// assert_eq!(lru.tail().unwrap().key(), &1);
+    /// ```
+    pub fn reinsert(
+        &mut self,
+        removed: Removed<Key, Value>,
+    ) -> Result<Option<(Key, Value)>, Removed<Key, Value>> {
+        let (key, value) = match removed {
+            Removed::Evicted(key, value) => (key, value),
+            previous @ Removed::PreviousValue(_) => return Err(previous),
+        };
+
+        let slot = self.index.slot(&key, &self.cache);
+        if slot.node().is_some() {
+            return Err(Removed::Evicted(key, value));
+        }
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, evicted) = self.cache.push_back(key, value);
+        let evicted_hash = slot.insert(node);
+        if let Some((evicted_key, evicted_value)) = &evicted {
+            self.index.remove_hashed(evicted_hash, evicted_key);
+            self.notify_evicted(evicted_key, evicted_value, dirty);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+        if evicted.is_some() {
+            evict_headroom_sparing(self, node);
+        }
+
+        Ok(evicted)
+    }
+
+    /// Returns the most recently used key, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    #[must_use]
+    pub fn head_key(&self) -> Option<&Key> {
+        self.head_key_value().map(|(key, _)| key)
+    }
+
+    /// Returns the least recently used key, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// assert_eq!(lru.head_key(), Some(&3));
+    /// assert_eq!(lru.tail_key(), Some(&1));
+    /// assert_eq!(lru.tail_key_value(), Some((&1, &1)));
+    /// ```
+    #[must_use]
+    pub fn tail_key(&self) -> Option<&Key> {
+        self.tail_key_value().map(|(key, _)| key)
+    }
+
+    /// Returns the most recently used key and value, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    #[must_use]
+    pub fn head_key_value(&self) -> Option<(&Key, &Value)> {
+        self.cache.head().map(|node| {
+            let node = self.cache.get_without_touch(node);
+            (node.key(), node.value())
+        })
+    }
+
+    /// Returns the least recently used key and value, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    #[must_use]
+    pub fn tail_key_value(&self) -> Option<(&Key, &Value)> {
+        self.cache.tail().map(|node| {
+            let node = self.cache.get_without_touch(node);
+            (node.key(), node.value())
+        })
+    }
+
+    /// Returns the entry that will be evicted when the next new key is pushed,
+    /// or `None` if there is room for another key.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// assert_eq!(lru.peek_evictee(), None);
+    /// lru.push(2, 2);
+    /// assert_eq!(lru.peek_evictee(), Some((&1, &1)));
+    /// ```
+    #[must_use]
+    pub fn peek_evictee(&self) -> Option<(&Key, &Value)> {
+        self.cache
+            .peek_evictee()
+            .map(|node| (node.key(), node.value()))
+    }
+
+    /// Returns true if pushing `key` into this map would cause an entry to be
+    /// evicted. Pushing a key that is already present never evicts an entry.
+    /// The entry that would be evicted can be inspected using
+    /// [`Self::peek_evictee()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// assert!(!lru.would_evict(&2));
+    /// lru.push(2, 2);
+    /// assert!(!lru.would_evict(&1));
+    /// assert!(lru.would_evict(&3));
+    /// ```
+    #[must_use]
+    pub fn would_evict<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.cache.push_evicts() && !self.index.contains_key(key, &self.cache)
+    }
+
+    /// Enables or disables deferred eviction.
+    ///
+    /// While evictions are deferred, pushing a new key into a full map will
+    /// grow the map beyond its capacity instead of evicting the least recently
+    /// used entry. The entries beyond capacity remain accessible until
+    /// [`Self::reclaim()`] is called, which evicts the least recently used
+    /// entries until the map is back within its capacity.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.set_deferred_eviction(true);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// assert_eq!(lru.len(), 4);
+    ///
+    /// let evicted = lru.reclaim().collect::<Vec<_>>();
+    /// assert_eq!(evicted, vec![(1, 1), (2, 2)]);
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    pub const fn set_deferred_eviction(&mut self, deferred: bool) {
+        self.cache.set_deferred_eviction(deferred);
+    }
+
+    /// Returns true if evictions are currently being deferred. See
+    /// [`Self::set_deferred_eviction()`] for more information.
+    #[must_use]
+    pub const fn deferred_eviction(&self) -> bool {
+        self.cache.deferred_eviction()
+    }
+
+    /// Sets the rate at which [`Self::push_sampled()`] admits new keys. One
+    /// in every `one_in` new keys is inserted, and the others are counted in
+    /// [`Self::rejected_insertions()`] and returned to the caller. Keys that
+    /// are already present are always updated.
+    ///
+    /// Sampling protects the map from insert storms, such as a scan over many
+    /// keys that will never be requested again, which would otherwise evict
+    /// every entry. Keys that are pushed repeatedly are eventually admitted.
+    /// The rate can be changed at any time, and a rate of 1, the default,
+    /// admits every new key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.set_insertion_sampling(3);
+    /// for key in 0..9 {
+    ///     let _ = lru.push_sampled(key, key);
+    /// }
+    /// assert_eq!(lru.len(), 3);
+    /// assert_eq!(lru.rejected_insertions(), 6);
+    ///
+    /// lru.set_insertion_sampling(1);
+    /// assert_eq!(lru.push_sampled(9, 9), Ok(None));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `one_in` is 0.
+    pub const fn set_insertion_sampling(&mut self, one_in: usize) {
+        self.cache.set_insertion_sampling(one_in);
+    }
+
+    /// Sets the rate at which [`Self::push_sampled()`] admits new keys,
+    /// returning an error instead of panicking if `one_in` is 0. See
+    /// [`Self::set_insertion_sampling()`] for more information.
+    ///
+    /// ```rust
+    /// use lrumap::{ConfigError, LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::<u32, u32>::new(4);
+    /// assert_eq!(lru.try_set_insertion_sampling(2), Ok(()));
+    /// assert_eq!(
+    ///     lru.try_set_insertion_sampling(0),
+    ///     Err(ConfigError::InvalidSamplingRate)
+    /// );
+    /// assert_eq!(lru.insertion_sampling(), 2);
+    /// ```
+    pub const fn try_set_insertion_sampling(&mut self, one_in: usize) -> Result<(), ConfigError> {
+        self.cache.try_set_insertion_sampling(one_in)
+    }
+
+    /// Returns the rate at which [`Self::push_sampled()`] admits new keys. See
+    /// [`Self::set_insertion_sampling()`] for more information.
+    #[must_use]
+    pub const fn insertion_sampling(&self) -> usize {
+        self.cache.insertion_sampling()
+    }
+
+    /// Returns the number of new keys that [`Self::push_sampled()`] has not
+    /// inserted.
+    #[must_use]
+    pub const fn rejected_insertions(&self) -> u64 {
+        self.cache.rejected_insertions()
+    }
+
+    /// Sets the number of entries to keep available when evicting.
+    ///
+    /// When pushing a new key into a full map, the least recently used entry
+    /// is evicted and returned as usual. Additional entries are then evicted
+    /// until the map contains no more than `capacity - headroom` entries.
+    /// The evicted entries are reported to the observer and dropped. This
+    /// allows evictions to happen in batches rather than on every push into
+    /// a full map.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek, Removed};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.set_headroom(2);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// assert_eq!(lru.len(), 4);
+    ///
+    /// assert_eq!(lru.push(5, 5), Some(Removed::Evicted(1, 1)));
+    /// assert_eq!(lru.len(), 2);
+    /// assert_eq!(lru.push(6, 6), None);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `headroom` is not less than this map's capacity.
+    pub const fn set_headroom(&mut self, headroom: usize) {
+        self.cache.set_headroom(headroom);
+    }
+
+    /// Sets the number of entries to evict in addition to the least recently
+    /// used entry, returning an error instead of panicking if `headroom` is
+    /// not less than this map's capacity. See [`Self::set_headroom()`] for
+    /// more information.
+    ///
+    /// ```rust
+    /// use lrumap::{ConfigError, LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::<u32, u32>::new(4);
+    /// assert_eq!(lru.try_set_headroom(3), Ok(()));
+    /// assert_eq!(
+    ///     lru.try_set_headroom(4),
+    ///     Err(ConfigError::InvalidHeadroom {
+    ///         headroom: 4,
+    ///         capacity: 4
+    ///     })
+    /// );
+    /// assert_eq!(lru.headroom(), 3);
+    /// ```
+    pub const fn try_set_headroom(&mut self, headroom: usize) -> Result<(), ConfigError> {
+        self.cache.try_set_headroom(headroom)
+    }
+
+    /// Returns the number of entries kept available when evicting. See
+    /// [`Self::set_headroom()`] for more information.
+    #[must_use]
+    pub const fn headroom(&self) -> usize {
+        self.cache.headroom()
+    }
+
+    /// Returns an iterator that evicts the least recently used entries until
+    /// this map is within its capacity. This is only needed when evictions
+    /// have been deferred using [`Self::set_deferred_eviction()`].
+    ///
+    /// Entries are evicted as the iterator is advanced. If the iterator is
+    /// dropped before being exhausted, the remaining entries that exceed the
+    /// capacity will be evicted and dropped.
+    pub const fn reclaim(&mut self) -> Trim<'_, Self, Key, Value> {
+        let capacity = self.cache.capacity();
+        Trim::evicting(self, capacity)
+    }
+
+    /// Returns an iterator that removes the least recently used entries until
+    /// this map contains no more than `len` entries, keeping only the `len`
+    /// most recently used entries.
+    ///
+    /// Entries are removed as the iterator is advanced. If the iterator is
+    /// dropped before being exhausted, the remaining entries beyond `len` will
+    /// be removed and dropped. Removed entries are not reported to the
+    /// observer as evictions.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// lru.get(&1);
+    ///
+    /// let removed = lru.truncate(2).collect::<Vec<_>>();
+    /// assert_eq!(removed, vec![(2, 2), (3, 3)]);
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    pub const fn truncate(&mut self, len: usize) -> Trim<'_, Self, Key, Value> {
+        Trim::removing(self, len)
+    }
+
+    /// Returns a cursor over the entries of this map, from most recently used
+    /// to least recently used. The current entry can be removed or touched
+    /// without ending the iteration.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    ///
+    /// let mut cursor = lru.cursor_iter();
+    /// while let Some((key, _)) = cursor.advance() {
+    ///     if key % 2 == 0 {
+    ///         cursor.remove_current();
+    ///     } else if *key == 1 {
+    ///         cursor.touch_current();
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(lru.iter().collect::<Vec<_>>(), [(&1, &1), (&3, &3)]);
+    /// ```
+    pub fn cursor_iter(&mut self) -> CursorIter<'_, Self, Key, Value> {
+        CursorIter::new(self)
+    }
+
+    /// Removes each entry that `keep` returns false for, visiting every entry
+    /// once from most recently used to least recently used. `keep` can modify
+    /// the values of the entries it keeps. The order of the remaining entries
+    /// is unchanged.
+    ///
+    /// Removed entries are not reported to the observer as evictions. To
+    /// spread the work across multiple calls, use
+    /// [`Self::retain_incremental()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(8);
+    /// lru.extend((0..8).map(|key| (key, key)));
+    ///
+    /// lru.retain(|key, _| key % 2 == 0);
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [6, 4, 2, 0]);
+    /// assert!(lru.get(&3).is_none());
+    /// ```
+    pub fn retain<Keep>(&mut self, keep: Keep)
+    where
+        Keep: FnMut(&Key, &mut Value) -> bool,
+    {
+        retain(self, keep);
+    }
+
+    /// Visits up to `budget` entries, removing each entry that `keep` returns
+    /// false for. Returns the progress to pass to the next call, allowing a
+    /// sweep over a large map to be spread across multiple calls.
+    ///
+    /// Entries are visited in the order they are stored rather than by how
+    /// recently they were used. The map can be modified between calls.
+    /// Touching or removing entries does not change where the sweep resumes,
+    /// so each entry is visited at most once and the sweep always finishes.
+    /// Entries inserted between calls may not be visited by the current
+    /// sweep.
+    ///
+    /// Removed entries are not reported to the observer as evictions.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek, RetainProgress};
+    ///
+    /// let mut lru = LruHashMap::new(8);
+    /// lru.extend((0..8).map(|key| (key, key)));
+    ///
+    /// let mut progress = RetainProgress::default();
+    /// while !progress.is_finished() {
+    ///     progress = lru.retain_incremental(progress, 3, |key, _| key % 2 == 0);
+    /// }
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [6, 4, 2, 0]);
+    /// ```
+    pub fn retain_incremental<Keep>(
+        &mut self,
+        progress: RetainProgress,
+        budget: usize,
+        keep: Keep,
+    ) -> RetainProgress
+    where
+        Keep: FnMut(&Key, &mut Value) -> bool,
+    {
+        retain_incremental(self, progress, budget, keep)
+    }
+
+    /// Converts this map into an iterator of its entries in order from least
+    /// recently touched to most recently touched.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// let entries = lru.into_lru_iter().collect::<Vec<_>>();
+    /// assert_eq!(entries, vec![(2, 2), (3, 3), (1, 1)]);
+    /// ```
+    pub fn into_lru_iter(self) -> Rev<IntoIter<Key, Value>> {
+        IntoIter::from(self.cache).rev()
+    }
+}
+
+#[cfg(feature = "interning")]
+impl<Value, State, Observer> LruHashMap<Arc<str>, Value, State, Observer>
+where
+    State: BuildHasher,
+    Observer: CacheObserver<Arc<str>, Value>,
+{
+    /// Inserts `value` for `key` into this map. See [`Self::push()`] for more
+    /// information.
+    ///
+    /// A new [`Arc<str>`] is only allocated if `key` is not already present.
+    /// Otherwise, the stored key is reused. Both copies of the key that this
+    /// map stores share the same allocation.
+    ///
+    /// This function is only available when feature `interning` is enabled.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use lrumap::{LruArcMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruArcMap::<str, u32>::new(2);
+    /// lru.push_str("a", 1);
+    /// let stored = lru.head_key().unwrap().clone();
+    ///
+    /// lru.push_str("a", 2);
+    /// assert!(Arc::ptr_eq(&stored, lru.head_key().unwrap()));
+    /// assert_eq!(lru.get("a"), Some(&2));
+    /// ```
+    pub fn push_str(&mut self, key: &str, value: Value) -> Option<Removed<Arc<str>, Value>> {
+        let key = self.index.get(key, &self.cache).map_or_else(
+            || Arc::from(key),
+            |node| self.cache.get_without_touch(node).key().clone(),
+        );
+        self.push(key, value)
+    }
+}
+
+impl<Key, Value, State, Observer> LruHashMap<Key, Arc<Value>, State, Observer>
+where
+    Key: Hash + Eq + Clone,
+    State: BuildHasher,
+    Observer: CacheObserver<Key, Arc<Value>>,
+{
+    /// Returns a clone of the [`Arc`] stored for `key`, if present.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    /// Unlike [`Self::get()`], the returned value does not borrow this map.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use lrumap::{LruArcValueMap, LruMap};
+    ///
+    /// let mut lru = LruArcValueMap::new(2);
+    /// lru.push(1, Arc::new(1));
+    ///
+    /// let one = lru.get_arc(&1).unwrap();
+    /// assert!(Arc::ptr_eq(&one, lru.get(&1).unwrap()));
+    /// assert!(lru.get_arc(&2).is_none());
+    /// ```
+    pub fn get_arc<QueryKey>(&mut self, key: &QueryKey) -> Option<Arc<Value>>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.get(key).map(Arc::clone)
+    }
+}
+
+impl<Key, Value, State, Observer> LruPeek<Key, Value> for LruHashMap<Key, Value, State, Observer>
+where
+    Key: Hash + Eq + Clone,
+    State: BuildHasher,
+    Observer: CacheObserver<Key, Value>,
+{
+    fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    fn is_full(&self) -> bool {
+        self.cache.is_full()
+    }
+
+    fn remaining_capacity(&self) -> usize {
+        self.cache.remaining_capacity()
+    }
+
+    fn tail_staleness(&self) -> Option<usize> {
+        self.cache.tail_staleness()
+    }
+
+    fn peek_head(&self) -> Option<(&Key, &Value)> {
+        self.head_key_value()
+    }
+
+    fn head_key(&self) -> Option<&Key> {
+        self.head_key()
+    }
+
+    fn head_key_value(&self) -> Option<(&Key, &Value)> {
+        self.head_key_value()
+    }
+
+    fn tail_key(&self) -> Option<&Key> {
+        self.tail_key()
+    }
+
+    fn tail_key_value(&self) -> Option<(&Key, &Value)> {
+        self.tail_key_value()
+    }
+
+    fn iter(&self) -> Iter<'_, Key, Value> {
+        self.cache.iter()
+    }
+
+    fn get_without_update<QueryKey>(&self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.get_without_update(key)
+    }
+
+    fn contains_key<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.contains_key(key)
+    }
+
+    fn is_more_recent_than<QueryKey>(&self, a: &QueryKey, b: &QueryKey) -> Option<bool>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.is_more_recent_than(a, b)
+    }
+
+    fn iter_from<QueryKey>(&self, key: &QueryKey) -> Option<Iter<'_, Key, Value>>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.iter_from(key)
+    }
+
+    fn staleness_histogram(&self, bucket_bounds: &[usize]) -> Vec<usize> {
+        self.cache.staleness_histogram(bucket_bounds)
+    }
+
+    fn partition_by_staleness(
+        &self,
+        threshold: usize,
+    ) -> (Take<Iter<'_, Key, Value>>, Iter<'_, Key, Value>) {
+        self.cache.partition_by_staleness(threshold)
+    }
+
+    fn iter_expiring_within(&self, staleness: usize) -> Expiring<'_, Key, Value> {
+        self.cache.iter_expiring_within(staleness)
+    }
+
+    fn iter_tail(&self, n: usize) -> IterTail<'_, Key, Value> {
+        self.cache.iter_tail(n)
+    }
+
+    fn keys_by_staleness(&self) -> KeysByStaleness<'_, Key, Value> {
+        self.cache.keys_by_staleness()
+    }
+
+    fn iter_dirty(&self) -> Dirty<'_, Key, Value> {
+        self.cache.iter_dirty()
+    }
+
+    fn vacancy_stats(&self) -> VacancyStats {
+        self.cache.vacancy_stats()
+    }
+
+    fn occupied_slots(&self) -> OccupiedSlots<'_, Key, Value> {
+        self.cache.occupied_slots()
+    }
+
+    fn iter_unordered(&self) -> Unordered<'_, Key, Value> {
+        self.cache.iter_unordered()
+    }
+
+    #[cfg(feature = "access-scores")]
+    fn top_k_by_access(&self, k: usize) -> Vec<(&Key, f32)> {
+        self.cache.top_k_by_access(k)
+    }
+
+    fn occupancy(&self) -> Occupancy {
+        self.cache.occupancy()
+    }
+
+    fn contains_entry(&self, id: EntryId) -> bool {
+        self.cache.contains_entry(id)
+    }
+
+    fn validate(&self) -> Result<(), CorruptionError> {
+        self.cache.validate()
     }
 }
 
-impl<Key, Value> LruMap<Key, Value> for LruHashMap<Key, Value, DefaultState>
+impl<Key, Value, State, Observer> LruMap<Key, Value> for LruHashMap<Key, Value, State, Observer>
 where
     Key: Hash + Eq + Clone,
+    State: BuildHasher + Default,
+    Observer: CacheObserver<Key, Value> + Default,
 {
-    fn new(capacity: usize) -> Self {
-        Self::new(capacity)
-    }
+    type Config = (State, Observer);
 
-    fn len(&self) -> usize {
-        self.cache.len()
+    fn with_config(capacity: usize, (hasher, observer): Self::Config) -> Self {
+        Self::with_hasher_and_observer(capacity, hasher, observer)
     }
 
     fn head(&mut self) -> Option<EntryRef<'_, Self, Key, Value>> {
@@ -245,12 +2121,20 @@ where
         self.get(key)
     }
 
-    fn get_without_update<QueryKey>(&self, key: &QueryKey) -> Option<&Value>
+    fn get_mut<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
     where
         QueryKey: Ord + Hash + Eq + ?Sized,
         Key: Borrow<QueryKey> + Ord + Hash + Eq,
     {
-        self.get_without_update(key)
+        self.get_mut(key)
+    }
+
+    fn get_mut_without_update<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.get_mut_without_update(key)
     }
 
     fn entry<QueryKey>(&mut self, key: &QueryKey) -> Option<EntryRef<'_, Self, Key, Value>>
@@ -261,12 +2145,44 @@ where
         self.entry(key)
     }
 
-    fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
-        self.push(key, value)
+    fn take<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.take(key)
     }
 
-    fn iter(&self) -> crate::lru::Iter<'_, Key, Value> {
-        self.cache.iter()
+    fn remove<QueryKey>(&mut self, key: &QueryKey) -> Option<Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.remove(key)
+    }
+
+    fn remove_entry<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.remove_entry(key)
+    }
+
+    fn pop_least_recent(&mut self) -> Option<(Key, Value)> {
+        self.pop_least_recent()
+    }
+
+    fn pop_most_recent(&mut self) -> Option<(Key, Value)> {
+        self.pop_most_recent()
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+
+    fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
+        self.push(key, value)
     }
 
     fn extend<IntoIter: IntoIterator<Item = (Key, Value)>>(&mut self, iterator: IntoIter) {
@@ -274,10 +2190,11 @@ where
     }
 }
 
-impl<Key, Value, State> EntryCache<Key, Value> for LruHashMap<Key, Value, State>
+impl<Key, Value, State, Observer> EntryCache<Key, Value> for LruHashMap<Key, Value, State, Observer>
 where
     Key: Hash + Eq + Clone,
     State: BuildHasher,
+    Observer: CacheObserver<Key, Value>,
 {
     fn cache(&self) -> &LruCache<Key, Value> {
         &self.cache
@@ -287,14 +2204,23 @@ where
         &mut self.cache
     }
 
-    fn remove(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>) {
+    fn remove_node(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>) {
         let ((key, value), next, previous) = self.cache.remove(node);
-        self.map.remove(&key);
+        self.index.remove_node(node, &key);
         ((key, value), next, previous)
     }
+
+    fn notify_evicted(&mut self, key: &Key, value: &Value, dirty: bool) {
+        self.cache.capture_evicted(key, value);
+        if dirty {
+            self.observer.on_evict_dirty(key, value);
+        } else {
+            self.observer.on_evict(key, value);
+        }
+    }
 }
 
-impl<Key, Value, State> IntoIterator for LruHashMap<Key, Value, State>
+impl<Key, Value, State, Observer> IntoIterator for LruHashMap<Key, Value, State, Observer>
 where
     Key: Hash + Eq + Clone,
     State: BuildHasher,
@@ -306,3 +2232,429 @@ where
         IntoIter::from(self.cache)
     }
 }
+
+/// The index of keys to the nodes that contain them.
+///
+/// When the `hashbrown` feature is enabled, the hash of each key is stored for
+/// each node. This allows pushing and removing entries to hash each key at
+/// most once, as evicted and removed keys can be found using their stored
+/// hash.
+#[cfg(not(feature = "hashed-index"))]
+#[derive(Debug)]
+struct KeyIndex<Key, State> {
+    map: HashMap<Key, NodeId, State>,
+    #[cfg(feature = "hashbrown")]
+    hashes: Vec<u64>,
+}
+
+/// The hash that was stored for a node before it was reused by
+/// [`Slot::insert()`].
+#[cfg(not(feature = "hashed-index"))]
+#[derive(Clone, Copy)]
+struct StoredHash(#[cfg(feature = "hashbrown")] u64);
+
+#[cfg(not(feature = "hashed-index"))]
+impl<Key, State> KeyIndex<Key, State>
+where
+    Key: Hash + Eq + Clone,
+    State: BuildHasher,
+{
+    fn new(capacity: usize, hasher: State) -> Self {
+        Self {
+            map: HashMap::with_capacity_and_hasher(capacity, hasher),
+            #[cfg(feature = "hashbrown")]
+            hashes: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn get<QueryKey, Value>(&self, key: &QueryKey, _cache: &LruCache<Key, Value>) -> Option<NodeId>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.map.get(key).copied()
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    fn contains_key<QueryKey, Value>(&self, key: &QueryKey, _cache: &LruCache<Key, Value>) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.map.contains_key(key)
+    }
+
+    fn remove<QueryKey, Value>(
+        &mut self,
+        key: &QueryKey,
+        _cache: &LruCache<Key, Value>,
+    ) -> Option<NodeId>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.map.remove(key)
+    }
+
+    #[cfg(not(feature = "hashbrown"))]
+    fn slot<Value>(&mut self, key: &Key, _cache: &LruCache<Key, Value>) -> Slot<'_, Key> {
+        Slot {
+            entry: self.map.entry(key.clone()),
+        }
+    }
+
+    #[cfg(feature = "hashbrown")]
+    fn slot<Value>(&mut self, key: &Key, _cache: &LruCache<Key, Value>) -> Slot<'_, Key, State> {
+        let mut hasher = self.map.hasher().build_hasher();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        Slot {
+            entry: self.map.raw_entry_mut().from_key_hashed_nocheck(hash, key),
+            hashes: &mut self.hashes,
+            hash,
+            key: key.clone(),
+        }
+    }
+
+    /// Removes `key`, which was stored in `node`, from the index.
+    #[cfg(not(feature = "hashbrown"))]
+    fn remove_node(&mut self, _node: NodeId, key: &Key) {
+        self.map.remove(key);
+    }
+
+    /// Removes `key`, which was stored in `node`, from the index.
+    #[cfg(feature = "hashbrown")]
+    fn remove_node(&mut self, node: NodeId, key: &Key) {
+        self.remove_hashed(StoredHash(self.hashes[node.as_usize()]), key);
+    }
+
+    /// Removes `key` from the index using the hash that was stored for it.
+    #[cfg(not(feature = "hashbrown"))]
+    fn remove_hashed(&mut self, _hash: StoredHash, key: &Key) {
+        self.map.remove(key);
+    }
+
+    /// Removes `key` from the index using the hash that was stored for it.
+    #[cfg(feature = "hashbrown")]
+    fn remove_hashed(&mut self, hash: StoredHash, key: &Key) {
+        if let hash_map::RawEntryMut::Occupied(entry) = self
+            .map
+            .raw_entry_mut()
+            .from_hash(hash.0, |candidate| candidate == key)
+        {
+            entry.remove();
+        }
+    }
+}
+
+/// The location in a [`KeyIndex`] for a key that is being pushed.
+#[cfg(not(any(feature = "hashbrown", feature = "hashed-index")))]
+struct Slot<'a, Key> {
+    entry: hash_map::Entry<'a, Key, NodeId>,
+}
+
+#[cfg(not(any(feature = "hashbrown", feature = "hashed-index")))]
+impl<Key> Slot<'_, Key> {
+    /// Returns the node currently containing this slot's key, if present.
+    fn node(&self) -> Option<NodeId> {
+        match &self.entry {
+            hash_map::Entry::Occupied(entry) => Some(*entry.get()),
+            hash_map::Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Stores `node` for this slot's key, returning the hash that was
+    /// previously stored for `node`.
+    fn insert(self, node: NodeId) -> StoredHash {
+        self.entry.or_insert(node);
+        StoredHash()
+    }
+}
+
+/// The location in a [`KeyIndex`] for a key that is being pushed.
+#[cfg(all(feature = "hashbrown", not(feature = "hashed-index")))]
+struct Slot<'a, Key, State> {
+    entry: hash_map::RawEntryMut<'a, Key, NodeId, State>,
+    hashes: &'a mut Vec<u64>,
+    hash: u64,
+    key: Key,
+}
+
+#[cfg(all(feature = "hashbrown", not(feature = "hashed-index")))]
+impl<Key, State> Slot<'_, Key, State>
+where
+    Key: Hash,
+    State: BuildHasher,
+{
+    /// Returns the node currently containing this slot's key, if present.
+    fn node(&self) -> Option<NodeId> {
+        match &self.entry {
+            hash_map::RawEntryMut::Occupied(entry) => Some(*entry.get()),
+            hash_map::RawEntryMut::Vacant(_) => None,
+        }
+    }
+
+    /// Stores `node` for this slot's key, returning the hash that was
+    /// previously stored for `node`.
+    fn insert(self, node: NodeId) -> StoredHash {
+        let previous = if let Some(stored) = self.hashes.get_mut(node.as_usize()) {
+            std::mem::replace(stored, self.hash)
+        } else {
+            debug_assert_eq!(self.hashes.len(), node.as_usize());
+            self.hashes.push(self.hash);
+            0
+        };
+        if let hash_map::RawEntryMut::Vacant(entry) = self.entry {
+            entry.insert_hashed_nocheck(self.hash, self.key, node);
+        }
+        StoredHash(previous)
+    }
+}
+
+/// The index of key hashes to the nodes that contain them.
+///
+/// When the `hashed-index` feature is enabled, the index does not store a
+/// copy of each key. Instead, each key's hash is mapped to the nodes whose keys
+/// have that hash, and the query is compared against the keys stored in the
+/// cache. Distinct keys whose hashes collide share a [`Bucket`].
+///
+/// The hash of each node's key is also stored, which allows removing evicted
+/// and removed keys without hashing them again.
+#[cfg(feature = "hashed-index")]
+#[derive(Debug)]
+struct KeyIndex<Key, State> {
+    state: State,
+    buckets: HashMap<u64, Bucket, BuildPassThrough>,
+    hashes: Vec<u64>,
+    _key: PhantomData<Key>,
+}
+
+/// The hash that was stored for a node before it was reused by
+/// [`Slot::insert()`].
+#[cfg(feature = "hashed-index")]
+#[derive(Clone, Copy)]
+struct StoredHash {
+    hash: u64,
+    node: NodeId,
+}
+
+#[cfg(feature = "hashed-index")]
+impl<Key, State> KeyIndex<Key, State>
+where
+    Key: Hash + Eq,
+    State: BuildHasher,
+{
+    fn new(capacity: usize, hasher: State) -> Self {
+        Self {
+            state: hasher,
+            buckets: HashMap::with_capacity_and_hasher(capacity, BuildPassThrough),
+            hashes: Vec::with_capacity(capacity),
+            _key: PhantomData,
+        }
+    }
+
+    fn find<QueryKey, Value>(
+        &self,
+        hash: u64,
+        key: &QueryKey,
+        cache: &LruCache<Key, Value>,
+    ) -> Option<NodeId>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.buckets
+            .get(&hash)?
+            .nodes()
+            .iter()
+            .copied()
+            .find(|node| cache.get_without_touch(*node).key().borrow() == key)
+    }
+
+    fn get<QueryKey, Value>(&self, key: &QueryKey, cache: &LruCache<Key, Value>) -> Option<NodeId>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.find(self.state.hash_one(key), key, cache)
+    }
+
+    fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    fn contains_key<QueryKey, Value>(&self, key: &QueryKey, cache: &LruCache<Key, Value>) -> bool
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.get(key, cache).is_some()
+    }
+
+    fn remove<QueryKey, Value>(
+        &mut self,
+        key: &QueryKey,
+        cache: &LruCache<Key, Value>,
+    ) -> Option<NodeId>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let hash = self.state.hash_one(key);
+        let node = self.find(hash, key, cache)?;
+        self.unlink(hash, node);
+        Some(node)
+    }
+
+    fn slot<Value>(&mut self, key: &Key, cache: &LruCache<Key, Value>) -> Slot<'_> {
+        let hash = self.state.hash_one(key);
+        Slot {
+            node: self.find(hash, key, cache),
+            buckets: &mut self.buckets,
+            hashes: &mut self.hashes,
+            hash,
+        }
+    }
+
+    /// Removes `node` from the bucket for `hash`.
+    fn unlink(&mut self, hash: u64, node: NodeId) {
+        if let hash_map::Entry::Occupied(mut bucket) = self.buckets.entry(hash) {
+            if bucket.get_mut().remove(node) {
+                bucket.remove();
+            }
+        }
+    }
+
+    /// Removes `node`, which stored `key`, from the index.
+    fn remove_node(&mut self, node: NodeId, _key: &Key) {
+        self.unlink(self.hashes[node.as_usize()], node);
+    }
+
+    /// Removes the node that was evicted to make room for a key that was
+    /// inserted.
+    fn remove_hashed(&mut self, hash: StoredHash, _key: &Key) {
+        self.unlink(hash.hash, hash.node);
+    }
+}
+
+/// The nodes whose keys have the same hash.
+///
+/// Nearly every bucket contains a single node. When the hashes of distinct
+/// keys collide, the bucket holds every colliding node, and lookups compare
+/// each node's key until a match is found.
+#[cfg(feature = "hashed-index")]
+#[derive(Debug)]
+enum Bucket {
+    One(NodeId),
+    Colliding(Vec<NodeId>),
+}
+
+#[cfg(feature = "hashed-index")]
+impl Bucket {
+    fn nodes(&self) -> &[NodeId] {
+        match self {
+            Self::One(node) => std::slice::from_ref(node),
+            Self::Colliding(nodes) => nodes,
+        }
+    }
+
+    fn push(&mut self, node: NodeId) {
+        match self {
+            Self::One(existing) => *self = Self::Colliding(vec![*existing, node]),
+            Self::Colliding(nodes) => nodes.push(node),
+        }
+    }
+
+    /// Removes `node`, returning true if the bucket is now empty.
+    fn remove(&mut self, node: NodeId) -> bool {
+        match self {
+            Self::One(existing) => *existing == node,
+            Self::Colliding(nodes) => {
+                if let Some(index) = nodes.iter().position(|candidate| *candidate == node) {
+                    nodes.swap_remove(index);
+                }
+                if let [remaining] = nodes.as_slice() {
+                    *self = Self::One(*remaining);
+                }
+                false
+            }
+        }
+    }
+}
+
+/// The location in a [`KeyIndex`] for a key that is being pushed.
+#[cfg(feature = "hashed-index")]
+struct Slot<'a> {
+    node: Option<NodeId>,
+    buckets: &'a mut HashMap<u64, Bucket, BuildPassThrough>,
+    hashes: &'a mut Vec<u64>,
+    hash: u64,
+}
+
+#[cfg(feature = "hashed-index")]
+impl Slot<'_> {
+    /// Returns the node currently containing this slot's key, if present.
+    const fn node(&self) -> Option<NodeId> {
+        self.node
+    }
+
+    /// Stores `node` for this slot's key, returning the hash that was
+    /// previously stored for `node`.
+    fn insert(self, node: NodeId) -> StoredHash {
+        let previous = if let Some(stored) = self.hashes.get_mut(node.as_usize()) {
+            std::mem::replace(stored, self.hash)
+        } else {
+            debug_assert_eq!(self.hashes.len(), node.as_usize());
+            self.hashes.push(self.hash);
+            0
+        };
+        match self.buckets.entry(self.hash) {
+            hash_map::Entry::Occupied(mut bucket) => bucket.get_mut().push(node),
+            hash_map::Entry::Vacant(bucket) => {
+                bucket.insert(Bucket::One(node));
+            }
+        }
+        StoredHash {
+            hash: previous,
+            node,
+        }
+    }
+}
+
+/// Builds hashers that use the already computed hash of a key as-is.
+#[cfg(feature = "hashed-index")]
+#[derive(Debug, Clone, Copy)]
+struct BuildPassThrough;
+
+#[cfg(feature = "hashed-index")]
+impl BuildHasher for BuildPassThrough {
+    type Hasher = PassThrough;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        PassThrough(0)
+    }
+}
+
+#[cfg(feature = "hashed-index")]
+struct PassThrough(u64);
+
+#[cfg(feature = "hashed-index")]
+impl Hasher for PassThrough {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        // Only `u64` hashes are written to this hasher.
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*byte);
+        }
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
@@ -17,36 +17,537 @@
     clippy::cast_possible_truncation
 )]
 
+mod composite;
+mod diff;
 mod hashed;
+pub mod linked_slab;
 mod lru;
+mod memoized;
+mod observer;
 mod ordered;
+pub mod replay;
+mod session;
+mod shared;
+mod small;
+mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 use std::borrow::Borrow;
 use std::hash::Hash;
+use std::iter::Take;
 
+pub use crate::composite::*;
+pub use crate::diff::CacheDiff;
 pub use crate::hashed::*;
-use crate::lru::{EntryCache, IntoIter};
-pub use crate::lru::{EntryRef, Iter, Removed};
+use crate::lru::{validate_capacity, EntryCache, IntoIter};
+pub use crate::lru::{
+    ConfigError, CorruptionError, CursorIter, Deterministic, Dirty, DuplicatePolicy, EntryId,
+    EntryRef, Expiring, Iter, IterTail, KeyHandle, KeysByStaleness, Occupancy, OccupiedSlots,
+    OwnedCursor, Projected, Removed, RetainProgress, SlotToken, Trim, Unordered, VacancyStats,
+};
+pub use crate::memoized::*;
+pub use crate::observer::*;
 pub use crate::ordered::*;
+pub use crate::session::*;
+pub use crate::shared::*;
+pub use crate::small::*;
+
+/// A read-only Least Recently Used map interface that supports all map
+/// implementations exposed by this crate.
+///
+/// All functions in this trait only require a shared reference and never
+/// touch any keys. This allows accepting read-only views of a cache
+/// generically.
+pub trait LruPeek<Key, Value> {
+    /// Returns the number of keys present in this map.
+    ///
+    /// The number of keys is maintained as entries are added and removed, so
+    /// this function runs in constant time.
+    fn len(&self) -> usize;
+
+    /// Returns true if this map contains no keys.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of keys this map holds before it begins
+    /// evicting.
+    fn capacity(&self) -> usize;
+
+    /// Returns true if this map contains at least [`Self::capacity()`] keys,
+    /// counting slots reserved with `reserve_slot()`.
+    ///
+    /// Unless evictions have been deferred, pushing a new key into a full map
+    /// evicts the least recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2)]);
+    /// assert_eq!(lru.capacity(), 3);
+    /// assert_eq!(lru.remaining_capacity(), 1);
+    /// assert!(!lru.is_full());
+    ///
+    /// lru.push(3, 3);
+    /// assert_eq!(lru.remaining_capacity(), 0);
+    /// assert!(lru.is_full());
+    /// ```
+    fn is_full(&self) -> bool;
+
+    /// Returns the number of new keys that can be pushed before this map is
+    /// full. See [`Self::is_full()`] for more information.
+    fn remaining_capacity(&self) -> usize;
+
+    /// Returns the [staleness](EntryRef::staleness) of the least recently used
+    /// key, or `None` if this map is empty.
+    ///
+    /// Because the least recently used key is the stalest key, this measures
+    /// how long entries survive in this map without being touched. Like
+    /// [`Self::len()`], [`Self::capacity()`], [`Self::is_full()`], and
+    /// [`Self::remaining_capacity()`], this function runs in constant time,
+    /// which makes it suitable for polling on every request.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// assert_eq!(lru.tail_staleness(), None);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// assert_eq!(lru.tail_staleness(), Some(2));
+    /// lru.get(&1);
+    /// assert_eq!(lru.tail_staleness(), Some(2));
+    /// ```
+    fn tail_staleness(&self) -> Option<usize>;
+
+    /// Returns the most recently used key and value, if any.
+    fn peek_head(&self) -> Option<(&Key, &Value)>;
+
+    /// Returns the most recently used key, if any.
+    fn head_key(&self) -> Option<&Key>;
+
+    /// Returns the most recently used key and value, if any. This is the same
+    /// as [`Self::peek_head()`].
+    fn head_key_value(&self) -> Option<(&Key, &Value)>;
+
+    /// Returns the least recently used key, if any.
+    fn tail_key(&self) -> Option<&Key>;
+
+    /// Returns the least recently used key and value, if any.
+    fn tail_key_value(&self) -> Option<(&Key, &Value)>;
+
+    /// Returns an iterator over the keys and values in order from most recently
+    /// touched to least recently touched.
+    fn iter(&self) -> Iter<'_, Key, Value>;
+
+    /// Returns an iterator over the `n` most recently touched keys and values,
+    /// in order from most recently touched to least recently touched.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    ///
+    /// let hottest = lru.iter_head(2).map(|(key, _)| *key);
+    /// assert_eq!(hottest.collect::<Vec<_>>(), vec![4, 3]);
+    /// let coldest = lru.iter_tail(2).map(|(key, _)| *key);
+    /// assert_eq!(coldest.collect::<Vec<_>>(), vec![1, 2]);
+    /// ```
+    fn iter_head(&self, n: usize) -> Take<Iter<'_, Key, Value>> {
+        self.iter().take(n)
+    }
+
+    /// Returns an iterator over the keys and `project(value)` for each value,
+    /// in order from most recently touched to least recently touched.
+    ///
+    /// This allows iterating over a field of each value, or a borrowed view
+    /// of it, while preserving the order of the keys.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut responses = LruHashMap::new(2);
+    /// responses.push("/a", (200, String::from("a body")));
+    /// responses.push("/b", (404, String::from("not found")));
+    ///
+    /// let bodies = responses.values_projected(|(_, body)| body.as_str());
+    /// assert_eq!(
+    ///     bodies.collect::<Vec<_>>(),
+    ///     [(&"/b", "not found"), (&"/a", "a body")]
+    /// );
+    /// ```
+    fn values_projected<Project, Projection>(
+        &self,
+        project: Project,
+    ) -> Projected<'_, Key, Value, Project>
+    where
+        Project: FnMut(&Value) -> &Projection,
+        Projection: ?Sized,
+    {
+        Projected::new(self.iter(), project)
+    }
+
+    /// Returns an iterator over the `n` least recently touched keys and
+    /// values, in order from least recently touched to most recently touched.
+    /// See [`Self::iter_head()`] for an example.
+    fn iter_tail(&self, n: usize) -> IterTail<'_, Key, Value>;
+
+    /// Returns the stored value for `key`, if present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    fn get_without_update<QueryKey>(&self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Returns true if `key` is present in this map. This function does not
+    /// touch the key.
+    fn contains_key<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Returns true if `a` was touched more recently than `b`, or `None` if
+    /// either key is not present. This function does not touch either key.
+    fn is_more_recent_than<QueryKey>(&self, a: &QueryKey, b: &QueryKey) -> Option<bool>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Returns an iterator over the keys and values beginning with `key` in
+    /// order from most recently touched to least recently touched, if `key`
+    /// is present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    fn iter_from<QueryKey>(&self, key: &QueryKey) -> Option<Iter<'_, Key, Value>>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Returns the number of entries in each staleness bucket.
+    ///
+    /// `bucket_bounds` contains the inclusive upper bound of each bucket's
+    /// [staleness](EntryRef::staleness), and must be sorted in ascending
+    /// order. The returned `Vec` contains one more element than
+    /// `bucket_bounds`: the number of entries that are staler than the last
+    /// bound.
+    ///
+    /// This function visits every entry. When only the stalest entry is
+    /// needed, [`Self::tail_staleness()`] runs in constant time.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// // Staleness is 3, 2, 1, and 0 from least to most recently touched.
+    /// assert_eq!(lru.staleness_histogram(&[0, 2]), vec![1, 2, 1]);
+    /// ```
+    fn staleness_histogram(&self, bucket_bounds: &[usize]) -> Vec<usize>;
+
+    /// Returns two iterators that split this map's entries at the first entry
+    /// whose [staleness](EntryRef::staleness) exceeds `threshold`.
+    ///
+    /// The first iterator returns the entries whose staleness is less than or
+    /// equal to `threshold`, and the second returns the remaining entries.
+    /// Both iterators return entries in order from most recently touched to
+    /// least recently touched. Because entries are ordered by recency, the
+    /// split is found in a single pass over the fresh entries.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// let (fresh, stale) = lru.partition_by_staleness(1);
+    /// assert_eq!(fresh.map(|(key, _)| *key).collect::<Vec<_>>(), vec![4, 3]);
+    /// assert_eq!(stale.map(|(key, _)| *key).collect::<Vec<_>>(), vec![2, 1]);
+    /// ```
+    fn partition_by_staleness(
+        &self,
+        threshold: usize,
+    ) -> (Take<Iter<'_, Key, Value>>, Iter<'_, Key, Value>);
+
+    /// Returns an iterator over the entries that were last touched no more
+    /// than `staleness` changes after the least recently used entry, in order
+    /// from least recently touched to most recently touched.
+    ///
+    /// These are the entries that are next in line to be evicted. Background
+    /// tasks can use this to refresh values before they are evicted using
+    /// `refresh()`, which replaces a value and restarts its age since
+    /// insertion without touching its key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// let expiring = lru
+    ///     .iter_expiring_within(1)
+    ///     .map(|(key, _)| *key)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(expiring, vec![1, 2]);
+    /// ```
+    fn iter_expiring_within(&self, staleness: usize) -> Expiring<'_, Key, Value>;
+
+    /// Returns an iterator over the keys and their
+    /// [staleness](EntryRef::staleness) in order from least recently touched
+    /// to most recently touched.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    /// let ranked = lru.keys_by_staleness().collect::<Vec<_>>();
+    /// assert_eq!(ranked, vec![(2, &2), (1, &3), (0, &1)]);
+    /// ```
+    fn keys_by_staleness(&self) -> KeysByStaleness<'_, Key, Value>;
+
+    /// Returns an iterator over the entries that have been
+    /// [marked dirty](EntryRef::mark_dirty), in order from least recently
+    /// touched to most recently touched.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// assert!(lru.mark_dirty(&3));
+    /// assert!(lru.mark_dirty(&1));
+    ///
+    /// let dirty = lru.iter_dirty().map(|(key, _)| *key).collect::<Vec<_>>();
+    /// assert_eq!(dirty, vec![1, 3]);
+    /// ```
+    fn iter_dirty(&self) -> Dirty<'_, Key, Value>;
+
+    /// Returns statistics about the slots used to store this map's entries.
+    ///
+    /// This function follows the chain of vacant slots to verify its length,
+    /// so it runs in time proportional to the number of vacant slots.
+    /// [`Self::occupancy()`] reports the same counts in constant time.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.take(&2);
+    ///
+    /// let stats = lru.vacancy_stats();
+    /// assert_eq!(stats.occupied_slots, 2);
+    /// assert_eq!(stats.vacant_slots, 1);
+    /// assert_eq!(stats.vacant_chain, 1);
+    /// assert_eq!(lru.occupied_slots().collect::<Vec<_>>(), vec![0, 2]);
+    /// ```
+    fn vacancy_stats(&self) -> VacancyStats;
+
+    /// Returns an iterator over the indices of the slots containing entries.
+    /// See [`Self::vacancy_stats()`] for more information.
+    fn occupied_slots(&self) -> OccupiedSlots<'_, Key, Value>;
+
+    /// Returns an iterator over the keys and values in the order they are
+    /// stored, which is unrelated to the order they were touched.
+    ///
+    /// Rather than following the links between entries, this iterator visits
+    /// the entries' storage directly, skipping vacant slots. This is faster
+    /// than [`Self::iter()`] when the order of the entries is irrelevant, such
+    /// as when exporting every entry.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// let mut entries = lru.iter_unordered().collect::<Vec<_>>();
+    /// entries.sort_unstable();
+    /// assert_eq!(entries, [(&1, &1), (&2, &2), (&3, &3)]);
+    /// ```
+    fn iter_unordered(&self) -> Unordered<'_, Key, Value>;
+
+    /// Returns up to `k` keys with the highest access scores, along with their
+    /// scores, from highest to lowest.
+    ///
+    /// Each score is the exponentially decayed access count described by
+    /// [`EntryRef::score()`]. The scores are computed in a single pass over
+    /// the entries' storage without touching any entries, which makes this
+    /// suitable for periodically checking whether a few keys dominate the
+    /// traffic to this map.
+    ///
+    /// This function is only available when feature `access-scores` is
+    /// enabled.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// for _ in 0..3 {
+    ///     lru.get(&2);
+    ///     lru.get(&3);
+    /// }
+    /// lru.get(&2);
+    ///
+    /// let hot = lru.top_k_by_access(2);
+    /// assert_eq!(hot.iter().map(|&(key, _)| *key).collect::<Vec<_>>(), [2, 3]);
+    /// assert!(hot[0].1 > hot[1].1);
+    /// ```
+    #[cfg(feature = "access-scores")]
+    fn top_k_by_access(&self, k: usize) -> Vec<(&Key, f32)>;
+
+    /// Returns the differences between the keys of this map and `other`.
+    ///
+    /// The result lists the keys only present in `other`, the keys only
+    /// present in this map, and the shared keys that are in a different order
+    /// in `other`. See [`CacheDiff`] for more information. Neither map is
+    /// touched.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut primary = LruHashMap::new(4);
+    /// primary.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// let mut standby = LruHashMap::new(4);
+    /// standby.extend([(1, 1), (2, 2), (3, 3), (5, 5)]);
+    /// standby.get(&2);
+    ///
+    /// let diff = standby.diff(&primary);
+    /// assert_eq!(diff.added, [4]);
+    /// assert_eq!(diff.removed, [5]);
+    /// // 3 must be touched to move it in front of 2.
+    /// assert_eq!(diff.reordered, [3]);
+    ///
+    /// standby.take(&5);
+    /// standby.get(&3);
+    /// standby.push(4, 4);
+    /// assert!(standby.diff(&primary).is_empty());
+    /// ```
+    fn diff(&self, other: &Self) -> CacheDiff<Key>
+    where
+        Key: Hash + Eq + Clone,
+    {
+        diff::diff(
+            self.iter().map(|(key, _)| key),
+            other.iter().map(|(key, _)| key),
+        )
+    }
+
+    /// Returns the number of entries, capacity, and vacant slots of this map.
+    /// This function runs in constant time.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.take(&2);
+    ///
+    /// let occupancy = lru.occupancy();
+    /// assert_eq!(occupancy.entries, 2);
+    /// assert_eq!(occupancy.capacity, 4);
+    /// assert_eq!(occupancy.vacant_slots, 1);
+    /// ```
+    fn occupancy(&self) -> Occupancy;
+
+    /// Returns true if the entry identified by `id` is still stored in this
+    /// map. Returns false if the entry has been evicted or removed, even if
+    /// its storage has been reused by another entry.
+    ///
+    /// `id` must have been returned from [`EntryRef::id()`] for an entry in
+    /// this map.
+    fn contains_entry(&self, id: EntryId) -> bool;
+
+    /// Checks the links between this map's entries, returning an error if
+    /// any corruption is detected.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// assert_eq!(lru.validate(), Ok(()));
+    /// ```
+    fn validate(&self) -> Result<(), CorruptionError>;
+}
 
 /// A Least Recently Used map interface that supports all map implementations
 /// exposed by this crate.
 pub trait LruMap<Key, Value>:
-    IntoIterator<Item = (Key, Value), IntoIter = IntoIter<Key, Value>> + EntryCache<Key, Value> + Sized
+    LruPeek<Key, Value>
+    + IntoIterator<Item = (Key, Value), IntoIter = IntoIter<Key, Value>>
+    + EntryCache<Key, Value>
+    + Sized
 {
+    /// The backend-specific configuration used to construct this map.
+    ///
+    /// For [`LruHashMap`], this is the hasher and observer. For
+    /// [`LruBTreeMap`] and [`SmallLruMap`], this is the observer.
+    ///
+    /// ```rust
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// use lrumap::{LruBTreeMap, LruHashMap, LruMap};
+    ///
+    /// fn warm<Map: LruMap<u32, u32>>(config: Map::Config) -> Map {
+    ///     let mut map = Map::with_config(2, config);
+    ///     map.push(1, 1);
+    ///     map
+    /// }
+    ///
+    /// let hashed: LruHashMap<u32, u32, RandomState> = warm((RandomState::new(), ()));
+    /// let ordered: LruBTreeMap<u32, u32> = warm(());
+    /// ```
+    type Config: Default;
+
     /// Creates a new map with the maximum `capacity`.
     ///
     /// # Panics
     ///
     /// Panics if `capacity` is <= 1 or > `u32::MAX`.
-    fn new(capacity: usize) -> Self;
+    #[must_use]
+    fn new(capacity: usize) -> Self {
+        Self::with_config(capacity, Self::Config::default())
+    }
 
-    /// Returns the number of keys present in this map.
-    fn len(&self) -> usize;
+    /// Creates a new map with the maximum `capacity` using `config`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    fn with_config(capacity: usize, config: Self::Config) -> Self;
 
-    /// Retruns true if this map contains no keys.
-    fn is_empty(&self) -> bool {
-        self.len() == 0
+    /// Creates a new map with the maximum `capacity`, returning an error
+    /// instead of panicking if `capacity` is <= 1 or > `u32::MAX`.
+    ///
+    /// ```rust
+    /// use lrumap::{ConfigError, LruHashMap, LruMap};
+    ///
+    /// assert!(LruHashMap::<u32, u32>::try_new(2).is_ok());
+    /// assert_eq!(
+    ///     LruHashMap::<u32, u32>::try_new(1).unwrap_err(),
+    ///     ConfigError::InvalidCapacity(1)
+    /// );
+    /// ```
+    fn try_new(capacity: usize) -> Result<Self, ConfigError> {
+        Self::try_with_config(capacity, Self::Config::default())
+    }
+
+    /// Creates a new map with the maximum `capacity` using `config`,
+    /// returning an error instead of panicking if `capacity` is <= 1 or >
+    /// `u32::MAX`.
+    ///
+    /// Implementors with different capacity requirements than the maps in
+    /// this crate should override this function.
+    fn try_with_config(capacity: usize, config: Self::Config) -> Result<Self, ConfigError> {
+        validate_capacity(capacity)?;
+        Ok(Self::with_config(capacity, config))
     }
 
     /// Returns a reference to the most recently used key.
@@ -54,10 +555,6 @@ pub trait LruMap<Key, Value>:
     /// Returns a reference to the least recently used key.
     fn tail(&mut self) -> Option<EntryRef<'_, Self, Key, Value>>;
 
-    /// Returns an iterator over the keys and values in order from most recently
-    /// touched to least recently touched.
-    fn iter(&self) -> Iter<'_, Key, Value>;
-
     /// Returns the stored value for `key`, if present.
     ///
     /// This function touches the key, making it the most recently used key.
@@ -66,11 +563,19 @@ pub trait LruMap<Key, Value>:
         QueryKey: Ord + Hash + Eq + ?Sized,
         Key: Borrow<QueryKey> + Ord + Hash + Eq;
 
-    /// Returns the stored value for `key`, if present.
+    /// Returns a mutable reference to the stored value for `key`, if present.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    fn get_mut<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Returns a mutable reference to the stored value for `key`, if present.
     ///
     /// This function does not touch the key, preserving its current position in
     /// the lru cache.
-    fn get_without_update<QueryKey>(&self, key: &QueryKey) -> Option<&Value>
+    fn get_mut_without_update<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
     where
         QueryKey: Ord + Hash + Eq + ?Sized,
         Key: Borrow<QueryKey> + Ord + Hash + Eq;
@@ -85,6 +590,45 @@ pub trait LruMap<Key, Value>:
         QueryKey: Ord + Hash + Eq + ?Sized,
         Key: Borrow<QueryKey> + Ord + Hash + Eq;
 
+    /// Removes and returns the entry for `key`, if present.
+    ///
+    /// Like [`Self::get()`] and [`Self::entry()`], `key` can be any borrowed
+    /// form of `Key`, including unsized types such as `str` for `String` keys
+    /// and `[u8]` for `Vec<u8>` keys.
+    fn take<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Removes `key` from this map, returning the stored value if present.
+    ///
+    /// This is equivalent to [`Self::take()`], discarding the stored key.
+    fn remove<QueryKey>(&mut self, key: &QueryKey) -> Option<Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Removes `key` from this map, returning the stored key and value if
+    /// present.
+    ///
+    /// This is equivalent to [`Self::take()`].
+    fn remove_entry<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq;
+
+    /// Removes the least recently used entry from this map, returning its key
+    /// and value if the map is not empty.
+    fn pop_least_recent(&mut self) -> Option<(Key, Value)>;
+
+    /// Removes the most recently used entry from this map, returning its key
+    /// and value if the map is not empty.
+    fn pop_most_recent(&mut self) -> Option<(Key, Value)>;
+
+    /// Removes every entry from this map, keeping the storage allocated for
+    /// the entries.
+    fn clear(&mut self);
+
     /// Inserts `value` for `key` into this map. If a value is already stored
     /// for this key, [`Removed::PreviousValue`] is returned with the previously
     /// stored value. If no value is currently stored and the map is full, the
@@ -102,5 +646,36 @@ pub trait LruMap<Key, Value>:
     fn extend<IntoIter: IntoIterator<Item = (Key, Value)>>(&mut self, iterator: IntoIter);
 }
 
+/// Collects an iterator of keys and values into a Least Recently Used map.
+pub trait IntoLru<Key, Value>: IntoIterator<Item = (Key, Value)> + Sized {
+    /// Returns a new map with the maximum `capacity` containing the items of
+    /// this iterator. If the iterator contains more unique keys than
+    /// `capacity`, the least recently pushed keys will be evicted.
+    ///
+    /// ```rust
+    /// use lrumap::{IntoLru, LruBTreeMap, LruPeek};
+    ///
+    /// let lru: LruBTreeMap<_, _> = (0..10).map(|key| (key, key)).collect_lru(3);
+    /// assert_eq!(
+    ///     lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+    ///     vec![9, 8, 7]
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    fn collect_lru<Map>(self, capacity: usize) -> Map
+    where
+        Map: LruMap<Key, Value>,
+    {
+        let mut map = Map::new(capacity);
+        map.extend(self);
+        map
+    }
+}
+
+impl<Key, Value, Iter> IntoLru<Key, Value> for Iter where Iter: IntoIterator<Item = (Key, Value)> {}
+
 #[cfg(test)]
 mod tests;
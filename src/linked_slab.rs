@@ -0,0 +1,518 @@
+//! The doubly linked list that orders the entries of every map in this
+//! crate.
+//!
+//! [`LinkedSlab`] stores its nodes in a `Vec` and links them by index, which
+//! avoids both `unsafe` code and an allocation per node. It only maintains
+//! the order of its values, which allows other eviction policies to reuse it
+//! without reimplementing the link updates.
+//!
+//! ```rust
+//! use lrumap::linked_slab::LinkedSlab;
+//!
+//! let mut slab = LinkedSlab::with_capacity(3);
+//! let a = slab.push_front("a");
+//! let b = slab.push_front("b");
+//! slab.push_back("c");
+//!
+//! assert!(slab.move_to_front(a));
+//! assert_eq!(slab.remove(b), ("b", slab.tail(), Some(a)));
+//! assert_eq!(slab[slab.head().unwrap()], "a");
+//! assert_eq!(slab.len(), 2);
+//! ```
+
+use std::ops::{Index, IndexMut};
+
+/// A doubly linked list whose nodes are stored in a `Vec`.
+///
+/// Removed nodes are kept in a chain of vacant slots, which are reused before
+/// the `Vec` is grown. Because slots are never moved, a [`NodeId`] remains
+/// valid until the node it refers to is removed.
+///
+/// This type only maintains the order of its values. Policies such as when to
+/// evict a value are implemented by the types built on top of it.
+#[derive(Debug)]
+pub struct LinkedSlab<T> {
+    slots: Vec<Slot<T>>,
+    head: Option<NodeId>,
+    tail: Option<NodeId>,
+    vacant: Option<NodeId>,
+    length: usize,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    value: Option<T>,
+    previous: Option<NodeId>,
+    next: Option<NodeId>,
+    /// Incremented each time the slot is vacated, allowing references to a
+    /// previous occupant to be detected.
+    generation: u32,
+}
+
+/// The location of a node in a [`LinkedSlab`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[repr(transparent)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// Returns the index of this node's slot.
+    #[must_use]
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl<T> LinkedSlab<T> {
+    /// Returns an empty list with space for `capacity` nodes before it
+    /// reallocates.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            head: None,
+            tail: None,
+            vacant: None,
+            length: 0,
+        }
+    }
+
+    /// Returns the number of linked nodes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns true if no nodes are linked.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the number of slots allocated, including vacant slots.
+    #[must_use]
+    pub const fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the first node of the list.
+    #[must_use]
+    pub const fn head(&self) -> Option<NodeId> {
+        self.head
+    }
+
+    /// Returns the last node of the list.
+    #[must_use]
+    pub const fn tail(&self) -> Option<NodeId> {
+        self.tail
+    }
+
+    /// Returns the most recently vacated slot. The remaining vacant slots can
+    /// be found by following [`Self::next()`].
+    #[must_use]
+    pub const fn vacant(&self) -> Option<NodeId> {
+        self.vacant
+    }
+
+    /// Returns the node after `node`. If `node` is vacant, this returns the
+    /// next vacant slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a slot of this list.
+    #[must_use]
+    pub fn next(&self, node: NodeId) -> Option<NodeId> {
+        self.slots[node.as_usize()].next
+    }
+
+    /// Returns the node before `node`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a slot of this list.
+    #[must_use]
+    pub fn previous(&self, node: NodeId) -> Option<NodeId> {
+        self.slots[node.as_usize()].previous
+    }
+
+    /// Returns the value stored in `node`, or `None` if the slot is vacant or
+    /// does not exist.
+    #[must_use]
+    pub fn get(&self, node: NodeId) -> Option<&T> {
+        self.slots.get(node.as_usize())?.value.as_ref()
+    }
+
+    /// Returns the generation of `node`'s slot, which changes each time the
+    /// slot is vacated. The generation wraps after `u32::MAX` reuses of the
+    /// same slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a slot of this list.
+    #[must_use]
+    pub fn generation(&self, node: NodeId) -> u32 {
+        self.slots[node.as_usize()].generation
+    }
+
+    /// Returns a slab with each value replaced by `map(value)`. Every node
+    /// keeps its position in the list and its [`NodeId`].
+    pub fn map<U>(self, mut map: impl FnMut(T) -> U) -> LinkedSlab<U> {
+        LinkedSlab {
+            slots: self
+                .slots
+                .into_iter()
+                .map(|slot| Slot {
+                    value: slot.value.map(&mut map),
+                    previous: slot.previous,
+                    next: slot.next,
+                    generation: slot.generation,
+                })
+                .collect(),
+            head: self.head,
+            tail: self.tail,
+            vacant: self.vacant,
+            length: self.length,
+        }
+    }
+
+    /// Returns an iterator over every slot and its value, if occupied.
+    #[must_use]
+    pub fn slots(&self) -> Slots<'_, T> {
+        Slots {
+            slots: self.slots.iter().enumerate(),
+        }
+    }
+
+    /// Inserts `value` before the current head.
+    pub fn push_front(&mut self, value: T) -> NodeId {
+        let node = self.allocate(value);
+        if let Some(head) = self.head {
+            self.slots[node.as_usize()].next = Some(head);
+            debug_assert!(self.slots[head.as_usize()].previous.is_none());
+            self.slots[head.as_usize()].previous = Some(node);
+        } else {
+            self.tail = Some(node);
+        }
+        self.head = Some(node);
+        node
+    }
+
+    /// Inserts `value` after the current tail.
+    pub fn push_back(&mut self, value: T) -> NodeId {
+        let node = self.allocate(value);
+        if let Some(tail) = self.tail {
+            self.slots[node.as_usize()].previous = Some(tail);
+            debug_assert!(self.slots[tail.as_usize()].next.is_none());
+            self.slots[tail.as_usize()].next = Some(node);
+        } else {
+            self.head = Some(node);
+        }
+        self.tail = Some(node);
+        node
+    }
+
+    fn allocate(&mut self, value: T) -> NodeId {
+        self.length += 1;
+        if let Some(vacant) = self.vacant {
+            // Pull a slot off the vacant list.
+            let slot = &mut self.slots[vacant.as_usize()];
+            self.vacant = slot.next.take();
+            slot.value = Some(value);
+            vacant
+        } else {
+            let node = NodeId(self.slots.len() as u32);
+            self.slots.push(Slot {
+                value: Some(value),
+                previous: None,
+                next: None,
+                generation: 0,
+            });
+            node
+        }
+    }
+
+    /// Moves `node` to the front of the list. Returns false if `node` was
+    /// already the head.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is vacant or is not a slot of this list.
+    pub fn move_to_front(&mut self, node: NodeId) -> bool {
+        if self.head == Some(node) {
+            return false;
+        }
+
+        // Update the next pointer to the current head.
+        let next = std::mem::replace(&mut self.slots[node.as_usize()].next, self.head);
+        // Get and clear the previous node, as this node is going to be the new
+        // head.
+        let previous = self.slots[node.as_usize()].previous.take().unwrap();
+        // Update the previous pointer's next to the previous next value.
+        self.slots[previous.as_usize()].next = next;
+        if self.tail == Some(node) {
+            // If this is the tail, update the tail to the previous node.
+            self.tail = Some(previous);
+        } else {
+            // Otherwise, we need to update the next node's previous to point to
+            // this node's former previous.
+            self.slots[next.unwrap().as_usize()].previous = Some(previous);
+        }
+
+        // Move this node to the front
+        self.slots[self.head.unwrap().as_usize()].previous = Some(node);
+        self.head = Some(node);
+        true
+    }
+
+    /// Moves `node` to directly after `anchor`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` and `anchor` are the same node, or if either is not a
+    /// slot of this list. Both must be linked nodes for the list to remain
+    /// consistent.
+    pub fn move_after(&mut self, node: NodeId, anchor: NodeId) {
+        assert_ne!(node, anchor, "a node cannot be moved after itself");
+        // Unlink the node from its current position.
+        let slot = &mut self.slots[node.as_usize()];
+        let next = slot.next.take();
+        let previous = slot.previous.take();
+        match previous {
+            Some(previous) => self.slots[previous.as_usize()].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.slots[next.as_usize()].previous = previous,
+            None => self.tail = previous,
+        }
+
+        // Link it after the anchor.
+        let next = self.slots[anchor.as_usize()].next.replace(node);
+        let slot = &mut self.slots[node.as_usize()];
+        slot.previous = Some(anchor);
+        slot.next = next;
+        match next {
+            Some(next) => self.slots[next.as_usize()].previous = Some(node),
+            None => self.tail = Some(node),
+        }
+    }
+
+    /// Unlinks `node`, returning its value and the nodes that were after and
+    /// before it. The slot is added to the vacant chain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is vacant or is not a slot of this list.
+    pub fn remove(&mut self, node: NodeId) -> (T, Option<NodeId>, Option<NodeId>) {
+        let slot = &mut self.slots[node.as_usize()];
+        let value = slot.value.take().expect("remove called on a vacant slot");
+        let next = std::mem::replace(&mut slot.next, self.vacant);
+        let previous = slot.previous.take();
+        slot.generation = slot.generation.wrapping_add(1);
+        self.length -= 1;
+
+        if let Some(previous) = previous {
+            self.slots[previous.as_usize()].next = next;
+        }
+        if let Some(next) = next {
+            self.slots[next.as_usize()].previous = previous;
+        }
+
+        if self.tail == Some(node) {
+            self.tail = previous;
+        }
+
+        if self.head == Some(node) {
+            self.head = next;
+        }
+
+        self.vacant = Some(node);
+
+        (value, next, previous)
+    }
+
+    /// Removes every node, adding every slot to the vacant chain. The slots
+    /// remain allocated for reuse.
+    pub fn clear(&mut self) {
+        let mut vacant = None;
+        for (index, slot) in self.slots.iter_mut().enumerate().rev() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+            slot.previous = None;
+            slot.next = vacant;
+            vacant = Some(NodeId(index as u32));
+        }
+        self.head = None;
+        self.tail = None;
+        self.vacant = vacant;
+        self.length = 0;
+    }
+
+    /// Overwrites the link after `node`. This is used to test how corrupt
+    /// lists are handled.
+    #[cfg(test)]
+    pub fn set_next(&mut self, node: NodeId, next: Option<NodeId>) {
+        self.slots[node.as_usize()].next = next;
+    }
+
+    /// Overwrites the length of the list. This is used to test how corrupt
+    /// lists are handled.
+    #[cfg(test)]
+    pub const fn set_len(&mut self, length: usize) {
+        self.length = length;
+    }
+}
+
+impl<T> Index<NodeId> for LinkedSlab<T> {
+    type Output = T;
+
+    fn index(&self, node: NodeId) -> &Self::Output {
+        self.slots[node.as_usize()]
+            .value
+            .as_ref()
+            .expect("vacant slot")
+    }
+}
+
+impl<T> IndexMut<NodeId> for LinkedSlab<T> {
+    fn index_mut(&mut self, node: NodeId) -> &mut Self::Output {
+        self.slots[node.as_usize()]
+            .value
+            .as_mut()
+            .expect("vacant slot")
+    }
+}
+
+/// An iterator over the slots of a [`LinkedSlab`] in storage order.
+pub struct Slots<'a, T> {
+    slots: std::iter::Enumerate<std::slice::Iter<'a, Slot<T>>>,
+}
+
+impl<'a, T> Iterator for Slots<'a, T> {
+    type Item = (NodeId, Option<&'a T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots
+            .next()
+            .map(|(index, slot)| (NodeId(index as u32), slot.value.as_ref()))
+    }
+}
+
+#[cfg(test)]
+fn linked(slab: &LinkedSlab<u32>) -> Vec<u32> {
+    let mut values = Vec::new();
+    let mut previous = None;
+    let mut current = slab.head();
+    while let Some(node) = current {
+        assert_eq!(slab.previous(node), previous);
+        values.push(slab[node]);
+        previous = current;
+        current = slab.next(node);
+    }
+    assert_eq!(slab.tail(), previous);
+    assert_eq!(values.len(), slab.len());
+    values
+}
+
+#[test]
+fn pushing_moving_and_removing() {
+    let mut slab = LinkedSlab::with_capacity(4);
+    let two = slab.push_front(2);
+    let one = slab.push_front(1);
+    let three = slab.push_back(3);
+    assert_eq!(linked(&slab), [1, 2, 3]);
+
+    assert!(!slab.move_to_front(one));
+    assert!(slab.move_to_front(three));
+    assert_eq!(linked(&slab), [3, 1, 2]);
+    assert!(slab.move_to_front(one));
+    assert_eq!(linked(&slab), [1, 3, 2]);
+    slab.move_after(one, two);
+    assert_eq!(linked(&slab), [3, 2, 1]);
+    slab.move_after(two, one);
+    assert_eq!(linked(&slab), [3, 1, 2]);
+    slab.move_after(one, three);
+    assert_eq!(linked(&slab), [3, 1, 2]);
+    assert!(slab.move_to_front(one));
+
+    assert_eq!(slab.remove(three), (3, Some(two), Some(one)));
+    assert_eq!(linked(&slab), [1, 2]);
+    assert_eq!(slab.get(three), None);
+    assert_eq!(slab.vacant(), Some(three));
+    assert_eq!(slab.generation(three), 1);
+    assert_eq!(slab.generation(two), 0);
+
+    // Vacant slots are reused before allocating new slots.
+    assert_eq!(slab.push_back(4), three);
+    assert_eq!(slab.vacant(), None);
+    assert_eq!(slab.slot_count(), 3);
+    assert_eq!(linked(&slab), [1, 2, 4]);
+
+    assert_eq!(slab.remove(one), (1, Some(two), None));
+    assert_eq!(slab.remove(three), (4, None, Some(two)));
+    assert_eq!(slab.remove(two), (2, None, None));
+    assert_eq!(linked(&slab), []);
+    assert_eq!(slab.slots().filter(|(_, value)| value.is_some()).count(), 0);
+}
+
+#[test]
+#[should_panic = "a node cannot be moved after itself"]
+fn moving_after_itself_panics() {
+    let mut slab = LinkedSlab::with_capacity(2);
+    slab.push_back(1);
+    let two = slab.push_back(2);
+    slab.move_after(two, two);
+}
+
+#[test]
+fn random_operations_match_model() {
+    // A small xorshift generator keeps this test deterministic without
+    // depending on a random number crate.
+    let mut state = 0x2545_f491_u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+
+    let mut slab = LinkedSlab::with_capacity(16);
+    let mut model = std::collections::VecDeque::<(NodeId, u32)>::new();
+    for value in 0..10_000 {
+        let position = |model: &std::collections::VecDeque<(NodeId, u32)>, index: u32| {
+            index as usize % model.len()
+        };
+        match random() % 5 {
+            0 if model.len() < 16 => model.push_front((slab.push_front(value), value)),
+            1 if model.len() < 16 => model.push_back((slab.push_back(value), value)),
+            2 if !model.is_empty() => {
+                let index = position(&model, random());
+                let entry = model.remove(index).unwrap();
+                assert_eq!(slab.move_to_front(entry.0), index != 0);
+                model.push_front(entry);
+            }
+            3 if model.len() > 1 => {
+                let index = position(&model, random());
+                let entry = model.remove(index).unwrap();
+                let anchor = position(&model, random());
+                slab.move_after(entry.0, model[anchor].0);
+                model.insert(anchor + 1, entry);
+            }
+            _ if !model.is_empty() => {
+                let index = position(&model, random());
+                let (node, value) = model.remove(index).unwrap();
+                let (removed, next, previous) = slab.remove(node);
+                assert_eq!(removed, value);
+                assert_eq!(next, model.get(index).map(|(node, _)| *node));
+                assert_eq!(previous, index.checked_sub(1).map(|index| model[index].0));
+            }
+            _ => {}
+        }
+
+        assert_eq!(
+            linked(&slab),
+            model.iter().map(|(_, value)| *value).collect::<Vec<_>>()
+        );
+        assert!(slab.slot_count() <= 16);
+    }
+}
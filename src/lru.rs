@@ -1,194 +1,714 @@
-use std::collections::HashSet;
-use std::fmt::Debug;
+use std::borrow::Borrow;
+use std::fmt::{Debug, Display};
+use std::iter::{FusedIterator, Take};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub use crate::linked_slab::NodeId;
+use crate::linked_slab::{LinkedSlab, Slots};
 
 pub struct LruCache<Key, Value> {
-    nodes: Vec<Node<Key, Value>>,
-    head: Option<NodeId>,
-    tail: Option<NodeId>,
-    vacant: Option<NodeId>,
-    sequence: usize,
-    length: usize,
+    nodes: LinkedSlab<Node<Key, Value>>,
+    sequence: u64,
+    capacity: usize,
+    headroom: usize,
+    /// The number of outstanding [`SlotToken`]s, shared with the tokens so
+    /// that dropping a token releases its slot. This is only allocated once a
+    /// slot is reserved.
+    reservations: Option<Arc<AtomicUsize>>,
+    deferred_eviction: bool,
+    batch: Batch,
+    capture: Option<EvictionCapture<Key, Value>>,
+    sampling: Sampling,
+}
+
+/// The state of insertion sampling, which admits one in every `one_in` new
+/// keys.
+#[derive(Debug, Clone, Copy)]
+struct Sampling {
+    one_in: usize,
+    /// The number of new keys seen since the last admitted key.
+    since_admitted: usize,
+    rejected: u64,
+}
+
+/// Copies of the entries evicted while a capture is active.
+struct EvictionCapture<Key, Value> {
+    entries: Vec<(Key, Value)>,
+    /// Copies an evicted entry. Storing the function allows entries to be
+    /// captured from code that does not require `Key` and `Value` to
+    /// implement `Clone`.
+    copy: fn(&Key, &Value) -> (Key, Value),
+}
+
+/// Tracks whether touches are being grouped into a single change.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Batch {
+    /// Each touch is a separate change.
+    None,
+    /// Touches are being grouped, but none have happened yet.
+    Pending,
+    /// Touches are being grouped, and the sequence has been advanced.
+    Advanced,
 }
 
 impl<Key, Value> LruCache<Key, Value> {
     pub fn new(capacity: usize) -> Self {
         Self {
-            nodes: Vec::with_capacity(capacity),
-            head: None,
-            tail: None,
-            vacant: None,
+            nodes: LinkedSlab::with_capacity(capacity),
             sequence: 0,
-            length: 0,
+            capacity,
+            headroom: 0,
+            reservations: None,
+            deferred_eviction: false,
+            batch: Batch::None,
+            capture: None,
+            sampling: Sampling {
+                one_in: 1,
+                since_admitted: 0,
+                rejected: 0,
+            },
+        }
+    }
+
+    /// Returns a cache with each value replaced by `map(value)`, keeping the
+    /// order, location, and access history of every entry.
+    pub fn map_values<NewValue>(
+        self,
+        mut map: impl FnMut(Value) -> NewValue,
+    ) -> LruCache<Key, NewValue> {
+        LruCache {
+            nodes: self.nodes.map(|node| node.map_value(&mut map)),
+            sequence: self.sequence,
+            capacity: self.capacity,
+            headroom: self.headroom,
+            // Outstanding tokens continue to reserve slots in the new cache.
+            reservations: self.reservations,
+            deferred_eviction: self.deferred_eviction,
+            batch: self.batch,
+            // Captures only exist while borrowed by `with_eviction_capture()`.
+            capture: None,
+            sampling: self.sampling,
         }
     }
 
     pub const fn len(&self) -> usize {
-        self.length
+        self.nodes.len()
+    }
+
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub const fn headroom(&self) -> usize {
+        self.headroom
+    }
+
+    pub const fn set_headroom(&mut self, headroom: usize) {
+        assert!(
+            headroom < self.capacity,
+            "headroom must be less than capacity"
+        );
+        self.headroom = headroom;
+    }
+
+    pub const fn try_set_headroom(&mut self, headroom: usize) -> Result<(), ConfigError> {
+        if headroom < self.capacity {
+            self.headroom = headroom;
+            Ok(())
+        } else {
+            Err(ConfigError::InvalidHeadroom {
+                headroom,
+                capacity: self.capacity,
+            })
+        }
+    }
+
+    pub const fn deferred_eviction(&self) -> bool {
+        self.deferred_eviction
+    }
+
+    pub const fn set_deferred_eviction(&mut self, deferred: bool) {
+        self.deferred_eviction = deferred;
+    }
+
+    pub const fn insertion_sampling(&self) -> usize {
+        self.sampling.one_in
+    }
+
+    pub const fn set_insertion_sampling(&mut self, one_in: usize) {
+        assert!(one_in > 0, "insertion sampling rate must be at least 1");
+        self.sampling.one_in = one_in;
+        self.sampling.since_admitted = 0;
+    }
+
+    pub const fn try_set_insertion_sampling(&mut self, one_in: usize) -> Result<(), ConfigError> {
+        if one_in > 0 {
+            self.sampling.one_in = one_in;
+            self.sampling.since_admitted = 0;
+            Ok(())
+        } else {
+            Err(ConfigError::InvalidSamplingRate)
+        }
+    }
+
+    pub const fn rejected_insertions(&self) -> u64 {
+        self.sampling.rejected
+    }
+
+    /// Returns true if a new key should be inserted under the current
+    /// insertion sampling rate, counting it as rejected otherwise.
+    pub const fn admit(&mut self) -> bool {
+        let admitted = self.sampling.since_admitted == 0;
+        self.sampling.since_admitted = (self.sampling.since_admitted + 1) % self.sampling.one_in;
+        if !admitted {
+            self.sampling.rejected = self.sampling.rejected.saturating_add(1);
+        }
+        admitted
+    }
+
+    /// Returns the number of slots reserved by outstanding [`SlotToken`]s.
+    pub fn reserved(&self) -> usize {
+        self.reservations
+            .as_ref()
+            .map_or(0, |reservations| reservations.load(Ordering::Acquire))
+    }
+
+    /// Reserves a slot, returning a token that releases it when dropped.
+    pub fn reserve(&mut self) -> SlotToken {
+        let reservations = self
+            .reservations
+            .get_or_insert_with(|| Arc::new(AtomicUsize::new(0)));
+        reservations.fetch_add(1, Ordering::AcqRel);
+        SlotToken {
+            reservations: reservations.clone(),
+        }
+    }
+
+    /// Returns true if `token` reserves a slot in this cache.
+    pub fn owns(&self, token: &SlotToken) -> bool {
+        self.reservations
+            .as_ref()
+            .is_some_and(|reservations| Arc::ptr_eq(reservations, &token.reservations))
+    }
+
+    /// Returns true if the cache contains at least `capacity` entries,
+    /// including reserved slots.
+    pub fn is_full(&self) -> bool {
+        self.nodes.len() + self.reserved() >= self.capacity
+    }
+
+    /// Removes every entry, keeping the allocated slots for reuse.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Returns the number of new keys that can be pushed before the cache is
+    /// full, excluding reserved slots.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity
+            .saturating_sub(self.nodes.len())
+            .saturating_sub(self.reserved())
+    }
+
+    /// Returns true if pushing a new key will evict the tail.
+    pub fn push_evicts(&self) -> bool {
+        self.is_full() && !self.deferred_eviction
+    }
+
+    /// Returns true if pushing a new key will evict an entry that has been
+    /// marked dirty.
+    pub fn evictee_is_dirty(&self) -> bool {
+        self.peek_evictee().is_some_and(Node::is_dirty)
+    }
+
+    pub fn peek_evictee(&self) -> Option<&Node<Key, Value>> {
+        if self.push_evicts() {
+            self.nodes.tail().map(|tail| self.get_without_touch(tail))
+        } else {
+            None
+        }
+    }
+
+    /// Begins grouping touches into a single change, returning the previous
+    /// state to pass to [`Self::end_batch()`].
+    pub const fn begin_batch(&mut self) -> Batch {
+        let previous = self.batch;
+        if matches!(previous, Batch::None) {
+            self.batch = Batch::Pending;
+        }
+        previous
+    }
+
+    pub const fn end_batch(&mut self, previous: Batch) {
+        // Nested batches are part of the outermost batch.
+        if matches!(previous, Batch::None) {
+            self.batch = Batch::None;
+        }
+    }
+
+    /// Begins copying evicted entries, returning the state to pass to
+    /// [`Self::end_capture()`].
+    pub fn begin_capture(&mut self) -> Option<usize>
+    where
+        Key: Clone,
+        Value: Clone,
+    {
+        if let Some(capture) = &self.capture {
+            Some(capture.entries.len())
+        } else {
+            self.capture = Some(EvictionCapture {
+                entries: Vec::new(),
+                copy: |key, value| (key.clone(), value.clone()),
+            });
+            None
+        }
+    }
+
+    /// Returns the entries evicted since the matching call to
+    /// [`Self::begin_capture()`].
+    pub fn end_capture(&mut self, start: Option<usize>) -> Vec<(Key, Value)>
+    where
+        Key: Clone,
+        Value: Clone,
+    {
+        // Nested captures are part of the outermost capture, which receives
+        // the entries once it ends.
+        match start {
+            Some(start) => self
+                .capture
+                .as_ref()
+                .map_or_else(Vec::new, |capture| capture.entries[start..].to_vec()),
+            None => self
+                .capture
+                .take()
+                .map_or_else(Vec::new, |capture| capture.entries),
+        }
+    }
+
+    /// Records a copy of the evicted entry if a capture is active.
+    pub fn capture_evicted(&mut self, key: &Key, value: &Value) {
+        if let Some(capture) = &mut self.capture {
+            let entry = (capture.copy)(key, value);
+            capture.entries.push(entry);
+        }
     }
 
-    pub const fn sequence(&self) -> usize {
+    /// Returns the current change sequence. The sequence is a `u64` on all
+    /// platforms and saturates rather than wrapping.
+    pub const fn sequence(&self) -> u64 {
         self.sequence
     }
 
+    /// Returns the number of changes since `sequence`, saturating at
+    /// `usize::MAX`.
+    pub fn changes_since(&self, sequence: u64) -> usize {
+        usize::try_from(self.sequence.saturating_sub(sequence)).unwrap_or(usize::MAX)
+    }
+
     pub const fn head(&self) -> Option<NodeId> {
-        self.head
+        self.nodes.head()
     }
 
     pub const fn tail(&self) -> Option<NodeId> {
-        self.tail
+        self.nodes.tail()
+    }
+
+    /// Returns the next less recently touched node.
+    pub fn next(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes.next(node)
+    }
+
+    /// Returns the next more recently touched node.
+    pub fn previous(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes.previous(node)
     }
 
     pub const fn iter(&self) -> Iter<'_, Key, Value> {
         Iter {
             cache: self,
             node: IterState::BeforeHead,
+            ahead: self.nodes.len(),
+            behind: 0,
+            exact: true,
+            #[cfg(feature = "corruption-reports")]
+            corruption: None,
         }
     }
 
-    pub fn get(&mut self, node: NodeId) -> &Node<Key, Value> {
-        self.touch(node);
-        &self.nodes[node.as_usize()]
+    pub const fn iter_deterministic(&self) -> Deterministic<'_, Key, Value> {
+        Deterministic(self.iter())
     }
 
-    pub fn get_without_touch(&self, node: NodeId) -> &Node<Key, Value> {
-        &self.nodes[node.as_usize()]
+    pub const fn iter_starting_at(&self, node: NodeId) -> Iter<'_, Key, Value> {
+        // The position of `node` is unknown, so each direction is only bounded
+        // by the length.
+        Iter {
+            cache: self,
+            node: IterState::StartingAt(node),
+            ahead: self.nodes.len(),
+            behind: self.nodes.len(),
+            exact: false,
+            #[cfg(feature = "corruption-reports")]
+            corruption: None,
+        }
     }
 
-    pub fn get_mut(&mut self, node: NodeId) -> &mut Node<Key, Value> {
-        self.touch(node);
-        &mut self.nodes[node.as_usize()]
-    }
+    pub fn partition_by_staleness(
+        &self,
+        threshold: usize,
+    ) -> (Take<Iter<'_, Key, Value>>, Iter<'_, Key, Value>) {
+        let mut fresh = 0;
+        let mut current = self.nodes.head();
+        while let Some(node_id) = current {
+            let node = &self.nodes[node_id];
+            if fresh == self.nodes.len() || self.changes_since(node.last_accessed) > threshold {
+                break;
+            }
+            fresh += 1;
+            current = self.nodes.next(node_id);
+        }
 
-    pub fn push(&mut self, key: Key, value: Value) -> (NodeId, Option<Removed<Key, Value>>) {
-        let (node, result) = if self.head.is_some() {
-            self.push_front(key, value)
-        } else {
-            // First node of the list.
-            self.allocate_node(key, value)
+        // The stale entries are the remainder of the list, so their count is
+        // known without walking to the tail.
+        let stale = Iter {
+            cache: self,
+            node: match current {
+                Some(node) if fresh < self.nodes.len() => IterState::StartingAt(node),
+                _ => IterState::AfterTail,
+            },
+            ahead: self.nodes.len() - fresh,
+            behind: fresh,
+            exact: true,
+            #[cfg(feature = "corruption-reports")]
+            corruption: None,
         };
-        (
-            node,
-            result.map(|(key, value)| Removed::Evicted(key, value)),
-        )
+        (self.iter().take(fresh), stale)
     }
 
-    pub fn touch(&mut self, node_index: NodeId) {
-        if self.head == Some(node_index) {
-            // No-op.
-            return;
+    pub fn tail_staleness(&self) -> Option<usize> {
+        self.nodes
+            .tail()
+            .map(|tail| self.changes_since(self.nodes[tail].last_accessed))
+    }
+
+    pub fn staleness_histogram(&self, bucket_bounds: &[usize]) -> Vec<usize> {
+        let mut buckets = vec![0; bucket_bounds.len() + 1];
+        let mut current = self.nodes.head();
+        while let Some(node_id) = current {
+            let staleness = self.changes_since(self.nodes[node_id].last_accessed);
+            buckets[bucket_bounds.partition_point(|bound| *bound < staleness)] += 1;
+            current = self.nodes.next(node_id);
         }
+        buckets
+    }
 
-        self.sequence += 1;
+    pub fn vacancy_stats(&self) -> VacancyStats {
+        let mut vacant_chain = 0;
+        let mut current = self.nodes.vacant();
+        while let Some(node_id) = current {
+            vacant_chain += 1;
+            current = self.nodes.next(node_id);
+        }
+        VacancyStats {
+            occupied_slots: self.nodes.len(),
+            vacant_slots: self.nodes.slot_count() - self.nodes.len(),
+            vacant_chain,
+        }
+    }
 
-        // An entry already exists. Reuse the node.
-        self.nodes[node_index.as_usize()].last_accessed = self.sequence;
+    pub const fn occupancy(&self) -> Occupancy {
+        Occupancy {
+            entries: self.nodes.len(),
+            capacity: self.capacity,
+            vacant_slots: self.nodes.slot_count() - self.nodes.len(),
+        }
+    }
 
-        // Update the next pointer to the current head.
-        let mut next = self.head;
-        std::mem::swap(&mut next, &mut self.nodes[node_index.as_usize()].next);
-        // Get and clear the previous node, as this node is going to be the new
-        // head.
-        let previous = self.nodes[node_index.as_usize()].previous.take().unwrap();
-        // Update the previous pointer's next to the previous next value.
-        self.nodes[previous.as_usize()].next = next;
-        if self.tail == Some(node_index) {
-            // If this is the tail, update the tail to the previous node.
-            self.tail = Some(previous);
-        } else {
-            // Otherwise, we need to update the next node's previous to point to
-            // this node's former previous.
-            self.nodes[next.unwrap().as_usize()].previous = Some(previous);
+    pub fn occupied_slots(&self) -> OccupiedSlots<'_, Key, Value> {
+        OccupiedSlots {
+            slots: self.nodes.slots(),
+        }
+    }
+
+    pub fn iter_unordered(&self) -> Unordered<'_, Key, Value> {
+        Unordered {
+            slots: self.nodes.slots(),
+            remaining: self.nodes.len(),
+        }
+    }
+
+    /// Returns the keys of the `k` entries with the highest access scores and
+    /// their scores, from highest to lowest. Entries with equal scores are
+    /// returned in the order they are stored.
+    #[cfg(feature = "access-scores")]
+    pub fn top_k_by_access(&self, k: usize) -> Vec<(&Key, f32)> {
+        let mut top = Vec::<(&Key, f32)>::with_capacity(k.min(self.nodes.len()) + 1);
+        if k == 0 {
+            return top;
+        }
+        for (node_id, node) in self.nodes.slots() {
+            let Some(node) = node else { continue };
+            let score = self.score(node_id);
+            if top.len() == k && top.last().is_some_and(|&(_, lowest)| score <= lowest) {
+                continue;
+            }
+            let index = top.partition_point(|&(_, existing)| existing >= score);
+            top.insert(index, (node.key(), score));
+            top.truncate(k);
         }
+        top
+    }
 
-        // Move this node to the front
-        self.nodes[self.head.unwrap().as_usize()].previous = Some(node_index);
+    pub const fn iter_expiring_within(&self, staleness: usize) -> Expiring<'_, Key, Value> {
+        Expiring {
+            cache: self,
+            node: self.nodes.tail(),
+            staleness,
+            remaining: self.nodes.len(),
+        }
+    }
 
-        self.head = Some(node_index);
+    pub fn iter_tail(&self, n: usize) -> IterTail<'_, Key, Value> {
+        IterTail {
+            cache: self,
+            node: self.nodes.tail(),
+            remaining: n.min(self.nodes.len()),
+        }
     }
 
-    fn push_front(&mut self, key: Key, value: Value) -> (NodeId, Option<(Key, Value)>) {
-        let (node, removed) = self.allocate_node(key, value);
-        self.sequence += 1;
-        let mut entry = &mut self.nodes[node.as_usize()];
-        entry.last_accessed = self.sequence;
-        entry.next = Some(self.head.unwrap());
+    pub const fn keys_by_staleness(&self) -> KeysByStaleness<'_, Key, Value> {
+        KeysByStaleness {
+            cache: self,
+            node: self.nodes.tail(),
+            remaining: self.nodes.len(),
+        }
+    }
 
-        let mut previous_head = &mut self.nodes[self.head.unwrap().as_usize()];
-        debug_assert!(previous_head.previous.is_none());
-        previous_head.previous = Some(node);
-        self.head = Some(node);
-        (node, removed)
+    pub const fn iter_dirty(&self) -> Dirty<'_, Key, Value> {
+        Dirty {
+            cache: self,
+            node: self.nodes.tail(),
+            remaining: self.nodes.len(),
+        }
     }
 
-    fn allocate_node(&mut self, key: Key, value: Value) -> (NodeId, Option<(Key, Value)>) {
-        if let Some(vacant) = self.vacant {
-            // Pull a node off the vacant list.
-            self.vacant = self.nodes[vacant.as_usize()].next;
-            self.nodes[vacant.as_usize()].next = None;
-            self.nodes[vacant.as_usize()].entry = Entry::Occupied { key, value };
-            self.length += 1;
-            if self.head.is_none() {
-                self.head = Some(vacant);
-                self.tail = Some(vacant);
+    pub fn validate(&self) -> Result<(), CorruptionError> {
+        let mut previous = None;
+        let mut current = self.nodes.head();
+        let mut linked = 0;
+        while let Some(node_id) = current {
+            if linked == self.nodes.len() {
+                return Err(CorruptionError::Cycle);
             }
-            (vacant, None)
-        } else if self.nodes.len() == self.nodes.capacity() {
-            // Expire the least recently used key (tail).
-            let index = self.tail.unwrap();
-            self.tail = self.nodes[index.as_usize()].previous;
-            if let Some(previous) = self.tail {
-                self.nodes[previous.as_usize()].next = None;
+            if self.nodes.get(node_id).is_none() || self.nodes.previous(node_id) != previous {
+                return Err(CorruptionError::BrokenLink {
+                    slot: node_id.as_usize(),
+                });
             }
-            self.nodes[index.as_usize()].previous = None;
-
-            let mut entry = Entry::Occupied { key, value };
-            std::mem::swap(&mut entry, &mut self.nodes[index.as_usize()].entry);
+            linked += 1;
+            previous = current;
+            current = self.nodes.next(node_id);
+        }
 
-            (index, entry.into())
+        if previous != self.nodes.tail() {
+            Err(CorruptionError::InvalidTail)
+        } else if linked != self.nodes.len() {
+            Err(CorruptionError::LengthMismatch {
+                length: self.nodes.len(),
+                linked,
+            })
         } else {
-            // We have capacity to fill.
-            let index = NodeId(self.nodes.len() as u32);
-            self.length += 1;
-            self.nodes.push(Node {
-                last_accessed: self.sequence,
-                previous: None,
-                next: None,
-                entry: Entry::Occupied { key, value },
-            });
-            if self.head.is_none() {
-                self.head = Some(index);
-                self.tail = Some(index);
-            }
-            (index, None)
+            Ok(())
         }
     }
 
-    pub fn remove(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>) {
-        self.length -= 1;
-        let removed = self.nodes[node.as_usize()].entry.evict();
-        let mut next = self.vacant;
-        std::mem::swap(&mut next, &mut self.nodes[node.as_usize()].next);
-        let previous = self.nodes[node.as_usize()].previous.take();
+    /// Returns the node referenced by `handle` if it still contains the
+    /// entry the handle was created for.
+    pub fn resolve(&self, handle: &KeyHandle<Key>) -> Option<NodeId> {
+        self.contains_entry(handle.entry)
+            .then_some(handle.entry.node)
+    }
+
+    /// Returns the identity of the entry stored in `node`.
+    pub fn entry_id(&self, node: NodeId) -> EntryId {
+        EntryId {
+            node,
+            generation: self.nodes.generation(node),
+        }
+    }
+
+    /// Returns true if the entry identified by `id` is still stored.
+    pub fn contains_entry(&self, id: EntryId) -> bool {
+        self.nodes.get(id.node).is_some() && self.nodes.generation(id.node) == id.generation
+    }
+
+    /// Returns the node referenced by `cursor` if it still contains the entry
+    /// the cursor was created for.
+    pub fn resume(&self, cursor: OwnedCursor) -> Option<NodeId> {
+        self.contains_entry(cursor.entry)
+            .then_some(cursor.entry.node)
+    }
+
+    /// Returns the node containing `key` by scanning every node in the arena.
+    pub fn find<QueryKey>(&self, key: &QueryKey) -> Option<NodeId>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.nodes
+            .slots()
+            .find(|(_, node)| node.is_some_and(|node| node.key.borrow() == key))
+            .map(|(node, _)| node)
+    }
+
+    pub fn get(&mut self, node: NodeId) -> &Node<Key, Value> {
+        self.touch(node);
+        &self.nodes[node]
+    }
+
+    pub fn get_without_touch(&self, node: NodeId) -> &Node<Key, Value> {
+        &self.nodes[node]
+    }
+
+    pub fn get_mut(&mut self, node: NodeId) -> &mut Node<Key, Value> {
+        self.touch(node);
+        &mut self.nodes[node]
+    }
+
+    pub fn get_mut_without_touch(&mut self, node: NodeId) -> &mut Node<Key, Value> {
+        &mut self.nodes[node]
+    }
+
+    pub fn push(&mut self, key: Key, value: Value) -> (NodeId, Option<Removed<Key, Value>>) {
+        let evicted = self.evict_for_insert();
+        if self.nodes.head().is_some() {
+            self.sequence = self.sequence.saturating_add(1);
+        }
+        let node = self.nodes.push_front(Node::new(key, value, self.sequence));
+        (
+            node,
+            evicted.map(|(key, value)| Removed::Evicted(key, value)),
+        )
+    }
+
+    pub fn push_back(&mut self, key: Key, value: Value) -> (NodeId, Option<(Key, Value)>) {
+        // The new node should appear no more recently touched than the current
+        // tail, keeping staleness ordered from head to tail.
+        let last_accessed = self.nodes.tail().map(|tail| self.nodes[tail].last_accessed);
+        let evicted = self.evict_for_insert();
+        self.sequence = self.sequence.saturating_add(1);
+        let mut entry = Node::new(key, value, self.sequence);
+        if let Some(last_accessed) = last_accessed {
+            entry.last_accessed = last_accessed;
+        }
+        (self.nodes.push_back(entry), evicted)
+    }
+
+    pub fn touch(&mut self, node_index: NodeId) {
+        let moved = self.nodes.move_to_front(node_index);
+        if moved {
+            match self.batch {
+                Batch::None => self.sequence = self.sequence.saturating_add(1),
+                Batch::Pending => {
+                    self.sequence = self.sequence.saturating_add(1);
+                    self.batch = Batch::Advanced;
+                }
+                Batch::Advanced => {}
+            }
+        }
+
+        // The head keeps its last accessed sequence if it was not moved.
+        let last_accessed = if moved {
+            self.sequence
+        } else {
+            self.nodes[node_index].last_accessed
+        };
+        self.record_access(node_index, last_accessed);
+    }
 
-        if let Some(previous) = previous {
-            self.nodes[previous.as_usize()].next = next;
+    /// Moves `node` to be `depth` entries behind the head, unless it is already
+    /// at least that recent.
+    ///
+    /// Unlike [`Self::touch()`], this does not advance the sequence. The node
+    /// takes the last accessed sequence of the node in front of it, keeping
+    /// staleness ordered from head to tail.
+    pub fn touch_to_depth(&mut self, node: NodeId, depth: usize) {
+        if depth == 0 {
+            self.touch(node);
+            return;
         }
-        if let Some(next) = next {
-            self.nodes[next.as_usize()].previous = previous;
+
+        // Find the node `depth - 1` entries behind the head, which will be in
+        // front of `node`. If `node` is found first, it is already recent
+        // enough.
+        let mut anchor = self.nodes.head();
+        for _ in 1..depth {
+            if anchor == Some(node) {
+                break;
+            }
+            anchor = anchor.and_then(|current| self.nodes.next(current));
         }
 
-        if self.tail == Some(node) {
-            self.tail = previous;
+        match anchor {
+            Some(anchor) if anchor != node => {
+                if self.nodes.next(anchor) != Some(node) {
+                    self.nodes.move_after(node, anchor);
+                }
+                self.record_access(node, self.nodes[anchor].last_accessed);
+            }
+            _ => self.record_access(node, self.nodes[node].last_accessed),
         }
+    }
 
-        if self.head == Some(node) {
-            self.head = next;
+    /// Records an access of `node`, which is now considered to have been last
+    /// touched at `last_accessed`.
+    fn record_access(&mut self, node: NodeId, last_accessed: u64) {
+        #[cfg(feature = "access-scores")]
+        let (sequence, half_life) = (self.sequence, self.capacity);
+        let node = &mut self.nodes[node];
+        // The score is stored as of the current sequence rather than
+        // `last_accessed`, which can be far behind it when a node is touched
+        // without moving. Scaling the access to an older sequence would
+        // overflow.
+        #[cfg(feature = "access-scores")]
+        {
+            node.score = decay(
+                node.score,
+                sequence.saturating_sub(node.scored_at),
+                half_life,
+            ) + 1.0;
+            node.scored_at = sequence;
         }
+        node.last_accessed = last_accessed;
+    }
 
-        self.vacant = Some(node);
+    /// Returns the access score of `node` as of the current sequence.
+    #[cfg(feature = "access-scores")]
+    pub fn score(&self, node: NodeId) -> f32 {
+        let node = &self.nodes[node];
+        decay(
+            node.score,
+            self.sequence.saturating_sub(node.scored_at),
+            self.capacity,
+        )
+    }
+
+    /// Removes the least recently used entry if inserting a new key would
+    /// exceed the capacity.
+    fn evict_for_insert(&mut self) -> Option<(Key, Value)> {
+        if self.push_evicts() {
+            let (removed, ..) = self.remove(self.nodes.tail()?);
+            Some(removed)
+        } else {
+            None
+        }
+    }
 
-        (removed, next, previous)
+    pub fn remove(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>) {
+        let (removed, next, previous) = self.nodes.remove(node);
+        ((removed.key, removed.value), next, previous)
     }
 }
 
@@ -199,59 +719,43 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut list = f.debug_list();
-        if let Some(head) = self.head {
-            let mut seen_nodes = HashSet::new();
-            let mut current_node = head;
-            let mut end_found = false;
-            while seen_nodes.insert(current_node) {
-                let node = &self.nodes[current_node.as_usize()];
-                list.entry(node);
-                current_node = if let Some(next) = node.next {
-                    next
-                } else {
-                    end_found = true;
-                    break;
-                };
+        // The traversal is bounded by the length and stops at the first
+        // missing or repeated node to ensure formatting never loops or panics,
+        // even if the links between nodes are corrupt. Use `validate()` to
+        // detect corruption.
+        let mut visited = vec![false; self.nodes.slot_count()];
+        let mut current = self.nodes.head();
+        for _ in 0..self.nodes.len() {
+            let Some(node_id) = current else {
+                break;
+            };
+            let Some(node) = self.nodes.get(node_id) else {
+                list.entry(&format_args!("<missing {node_id:?}>"));
+                break;
+            };
+            if std::mem::replace(&mut visited[node_id.as_usize()], true) {
+                list.entry(&format_args!("<repeated {node_id:?}>"));
+                break;
             }
-
-            assert!(end_found, "cycle detected");
+            list.entry(node);
+            current = self.nodes.next(node_id);
         }
 
         list.finish()
     }
 }
 
-#[derive(Debug)]
-enum Entry<Key, Value> {
-    Occupied { key: Key, value: Value },
-    Vacant,
-}
-
-impl<Key, Value> Entry<Key, Value> {
-    fn evict(&mut self) -> (Key, Value) {
-        let mut entry = Self::Vacant;
-        std::mem::swap(&mut entry, self);
-        match entry {
-            Self::Occupied { key, value } => (key, value),
-            Self::Vacant => unreachable!("evict called on a vacant entry"),
-        }
-    }
-}
-
-impl<Key, Value> From<Entry<Key, Value>> for Option<(Key, Value)> {
-    fn from(entry: Entry<Key, Value>) -> Self {
-        match entry {
-            Entry::Occupied { key, value } => Some((key, value)),
-            Entry::Vacant => None,
-        }
-    }
-}
-
 pub struct Node<Key, Value> {
-    entry: Entry<Key, Value>,
-    previous: Option<NodeId>,
-    next: Option<NodeId>,
-    last_accessed: usize,
+    key: Key,
+    value: Value,
+    last_accessed: u64,
+    inserted: u64,
+    /// The access score as of `scored_at`.
+    #[cfg(feature = "access-scores")]
+    score: f32,
+    #[cfg(feature = "access-scores")]
+    scored_at: u64,
+    dirty: bool,
 }
 
 impl<Key, Value> Debug for Node<Key, Value>
@@ -262,88 +766,270 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut debug = f.debug_struct("Node");
 
-        if let Entry::Occupied { key, value } = &self.entry {
-            debug.field("key", key);
-            debug.field("value", value);
-        }
+        debug.field("key", &self.key);
+        debug.field("value", &self.value);
         debug.field("last_accessed", &self.last_accessed);
+        debug.field("inserted", &self.inserted);
+        #[cfg(feature = "access-scores")]
+        debug.field("score", &self.score);
+        #[cfg(feature = "access-scores")]
+        debug.field("scored_at", &self.scored_at);
+        debug.field("dirty", &self.dirty);
 
         debug.finish()
     }
 }
 
 impl<Key, Value> Node<Key, Value> {
-    pub const fn last_accessed(&self) -> usize {
-        self.last_accessed
+    const fn new(key: Key, value: Value, sequence: u64) -> Self {
+        Self {
+            key,
+            value,
+            last_accessed: sequence,
+            inserted: sequence,
+            #[cfg(feature = "access-scores")]
+            score: 1.0,
+            #[cfg(feature = "access-scores")]
+            scored_at: sequence,
+            dirty: false,
+        }
     }
 
-    pub fn key(&self) -> &Key {
-        match &self.entry {
-            Entry::Occupied { key, .. } => key,
-            Entry::Vacant => unreachable!("EntryRef can't be made against Vacant"),
+    fn map_value<NewValue>(self, map: impl FnOnce(Value) -> NewValue) -> Node<Key, NewValue> {
+        Node {
+            key: self.key,
+            value: map(self.value),
+            last_accessed: self.last_accessed,
+            inserted: self.inserted,
+            #[cfg(feature = "access-scores")]
+            score: self.score,
+            #[cfg(feature = "access-scores")]
+            scored_at: self.scored_at,
+            dirty: self.dirty,
         }
     }
 
-    pub fn value(&self) -> &Value {
-        match &self.entry {
-            Entry::Occupied { value, .. } => value,
-            Entry::Vacant => unreachable!("EntryRef can't be made against Vacant"),
-        }
+    pub const fn last_accessed(&self) -> u64 {
+        self.last_accessed
     }
 
-    pub fn value_mut(&mut self) -> &mut Value {
-        match &mut self.entry {
-            Entry::Occupied { value, .. } => value,
-            Entry::Vacant => unreachable!("EntryRef can't be made against Vacant"),
-        }
+    pub const fn inserted(&self) -> u64 {
+        self.inserted
     }
 
-    pub fn replace_value(&mut self, mut new_value: Value) -> Value {
-        match &mut self.entry {
-            Entry::Occupied { value, .. } => {
-                std::mem::swap(value, &mut new_value);
-                new_value
-            }
-            Entry::Vacant => unreachable!("EntryRef can't be made against Vacant"),
-        }
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub const fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    pub const fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub const fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub const fn value_mut(&mut self) -> &mut Value {
+        &mut self.value
+    }
+
+    pub const fn key_and_value_mut(&mut self) -> (&Key, &mut Value) {
+        (&self.key, &mut self.value)
+    }
+
+    pub const fn replace_value(&mut self, new_value: Value) -> Value {
+        std::mem::replace(&mut self.value, new_value)
+    }
+
+    /// Replaces the value and restarts the age since insertion at `sequence`
+    /// without changing when this node was last accessed.
+    pub const fn refresh_value(&mut self, new_value: Value, sequence: u64) -> Value {
+        self.inserted = sequence;
+        self.replace_value(new_value)
     }
 }
 
-/// A reference to an entry in a Least Recently Used map.
-#[derive(Debug)]
-pub struct EntryRef<'a, Cache, Key, Value>
-where
-    Cache: EntryCache<Key, Value>,
-{
-    cache: &'a mut Cache,
-    node: NodeId,
-    accessed: bool,
-    _phantom: PhantomData<(Key, Value)>,
+/// Returns `score` after `elapsed` changes, halving every `half_life` changes.
+#[cfg(feature = "access-scores")]
+#[allow(clippy::cast_precision_loss)] // Scores are approximate.
+fn decay(score: f32, elapsed: u64, half_life: usize) -> f32 {
+    score * 0.5_f32.powf(elapsed as f32 / half_life as f32)
 }
 
-pub trait EntryCache<Key, Value> {
-    fn cache(&self) -> &LruCache<Key, Value>;
-    fn cache_mut(&mut self) -> &mut LruCache<Key, Value>;
-    fn remove(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>);
+/// A handle to an entry in a Least Recently Used map that does not borrow the
+/// map. Returned from [`EntryRef::downgrade()`].
+///
+/// Upgrading a handle first checks whether the entry's last known location
+/// still contains the same entry, only looking up the key again if it does
+/// not.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct KeyHandle<Key> {
+    key: Key,
+    entry: EntryId,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-#[repr(transparent)]
-pub struct NodeId(u32);
+impl<Key> KeyHandle<Key> {
+    /// Returns the key this handle refers to.
+    pub const fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+/// A position in a Least Recently Used map that does not borrow the map.
+/// Returned from [`EntryRef::cursor()`].
+///
+/// Unlike [`KeyHandle`], a cursor does not store the entry's key, which makes
+/// it `Copy`, `Send`, and `Sync` regardless of the key type. Resuming a cursor
+/// succeeds as long as the entry it was created for has not been removed,
+/// allowing multi-step maintenance passes to release the map between steps.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[must_use]
+pub struct OwnedCursor {
+    entry: EntryId,
+}
 
-impl NodeId {
-    const fn as_usize(self) -> usize {
-        self.0 as usize
+/// The progress of an incremental retain, which can be resumed after the map
+/// has been modified. Returned from `retain_incremental()`.
+///
+/// A new sweep is started using [`RetainProgress::default()`].
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[must_use]
+pub struct RetainProgress(Sweep);
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+enum Sweep {
+    #[default]
+    Starting,
+    /// The index of the next slot to visit.
+    At(usize),
+    Finished,
+}
+
+impl RetainProgress {
+    /// Returns true if every entry has been visited.
+    #[must_use]
+    pub const fn is_finished(&self) -> bool {
+        matches!(self.0, Sweep::Finished)
     }
 }
 
-impl<'a, Cache, Key, Value> EntryRef<'a, Cache, Key, Value>
+/// Visits every entry of `cache`, removing each entry that `keep` returns
+/// false for.
+pub fn retain<Cache, Key, Value, Keep>(cache: &mut Cache, mut keep: Keep)
 where
     Cache: EntryCache<Key, Value>,
+    Keep: FnMut(&Key, &mut Value) -> bool,
 {
-    pub(crate) fn new(cache: &'a mut Cache, node: NodeId) -> Self {
-        Self {
-            node,
+    let mut current = cache.cache().head();
+    while let Some(node) = current {
+        current = cache.cache().next(node);
+        let (key, value) = cache
+            .cache_mut()
+            .get_mut_without_touch(node)
+            .key_and_value_mut();
+        if !keep(key, value) {
+            cache.remove_node(node);
+        }
+    }
+}
+
+/// Visits up to `budget` entries of `cache` starting where `progress` left
+/// off, removing each entry that `keep` returns false for.
+///
+/// Entries are visited in the order of the slots they are stored in. An entry
+/// keeps its slot when it is touched, and a removed entry's slot does not
+/// move any other entry, so the sweep visits each entry at most once and
+/// finishes regardless of how the map is modified between calls.
+pub fn retain_incremental<Cache, Key, Value, Keep>(
+    cache: &mut Cache,
+    progress: RetainProgress,
+    budget: usize,
+    mut keep: Keep,
+) -> RetainProgress
+where
+    Cache: EntryCache<Key, Value>,
+    Keep: FnMut(&Key, &mut Value) -> bool,
+{
+    let mut slot = match progress.0 {
+        Sweep::Starting => 0,
+        Sweep::At(slot) => slot,
+        Sweep::Finished => return progress,
+    };
+
+    for _ in 0..budget {
+        let Some((node, _)) = cache
+            .cache()
+            .nodes
+            .slots()
+            .skip(slot)
+            .find(|(_, value)| value.is_some())
+        else {
+            return RetainProgress(Sweep::Finished);
+        };
+        slot = node.as_usize() + 1;
+        let (key, value) = cache
+            .cache_mut()
+            .get_mut_without_touch(node)
+            .key_and_value_mut();
+        if !keep(key, value) {
+            cache.remove_node(node);
+        }
+    }
+
+    RetainProgress(if slot < cache.cache().nodes.slot_count() {
+        Sweep::At(slot)
+    } else {
+        Sweep::Finished
+    })
+}
+
+/// An opaque identifier of an entry in a Least Recently Used map. Returned
+/// from [`EntryRef::id()`].
+///
+/// Entries are stored in reusable slots. An identifier refers to the entry
+/// stored when it was created, not to the slot, so it can be used to detect
+/// that an entry has been evicted or removed even if its slot now holds a
+/// different entry. Two identifiers from the same map are equal only if they
+/// refer to the same entry.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct EntryId {
+    node: NodeId,
+    generation: u32,
+}
+
+/// A reference to an entry in a Least Recently Used map.
+#[derive(Debug)]
+pub struct EntryRef<'a, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    cache: &'a mut Cache,
+    node: NodeId,
+    accessed: bool,
+    _phantom: PhantomData<(Key, Value)>,
+}
+
+pub trait EntryCache<Key, Value> {
+    fn cache(&self) -> &LruCache<Key, Value>;
+    fn cache_mut(&mut self) -> &mut LruCache<Key, Value>;
+    fn remove_node(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>);
+    /// Notifies the map's observer that `key` was evicted. `dirty` is true if
+    /// the entry was marked dirty.
+    fn notify_evicted(&mut self, key: &Key, value: &Value, dirty: bool);
+}
+
+impl<'a, Cache, Key, Value> EntryRef<'a, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    pub(crate) fn new(cache: &'a mut Cache, node: NodeId) -> Self {
+        Self {
+            node,
             cache,
             accessed: false,
             _phantom: PhantomData,
@@ -386,24 +1072,147 @@ where
     }
 
     /// Returns the number of changes to the cache since this key was last
-    /// touched.
+    /// touched, saturating at `usize::MAX`.
     #[must_use]
     pub fn staleness(&self) -> usize {
-        self.cache.cache().sequence().wrapping_sub(
-            self.cache
-                .cache()
-                .get_without_touch(self.node)
-                .last_accessed,
-        )
+        let cache = self.cache.cache();
+        cache.changes_since(cache.get_without_touch(self.node).last_accessed)
+    }
+
+    /// Returns the sequence number of the cache when this key was last
+    /// touched.
+    ///
+    /// The cache's sequence number increases with each change to the cache,
+    /// which causes [`staleness()`](Self::staleness) to change even when this
+    /// key is not touched. This value only changes when this key is touched,
+    /// which allows storing it and comparing it against later observations of
+    /// the same key.
+    ///
+    /// The sequence number is a `u64` on all platforms. Even at a billion
+    /// changes per second, it would take centuries to overflow.
+    #[must_use]
+    pub fn last_accessed_sequence(&self) -> u64 {
+        self.cache
+            .cache()
+            .get_without_touch(self.node)
+            .last_accessed()
+    }
+
+    /// Returns an exponentially decayed count of how often this key has been
+    /// accessed.
+    ///
+    /// Inserting and each touch of this key add 1 to the score. The score
+    /// halves each time the map changes as many times as its capacity, so keys
+    /// that are touched frequently have higher scores than keys that were
+    /// touched as recently but less often. Combined with
+    /// [`staleness()`](Self::staleness), this allows policies that consider
+    /// both recency and frequency.
+    ///
+    /// This function is only available when feature `access-scores` is
+    /// enabled. Without it, touches do not update any scores.
+    #[cfg(feature = "access-scores")]
+    #[must_use]
+    pub fn score(&self) -> f32 {
+        self.cache.cache().score(self.node)
+    }
+
+    /// Returns the number of changes to the cache since this key was inserted.
+    ///
+    /// Unlike [`staleness()`](Self::staleness), touching the key does not
+    /// reset this value. Replacing the value of an existing key is not
+    /// considered an insertion. This value saturates at `usize::MAX`.
+    #[must_use]
+    pub fn age_since_insert(&self) -> usize {
+        let cache = self.cache.cache();
+        cache.changes_since(cache.get_without_touch(self.node).inserted())
+    }
+
+    /// Returns true if this entry has been marked dirty. See
+    /// [`Self::mark_dirty()`] for more information.
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.cache.cache().get_without_touch(self.node).is_dirty()
+    }
+
+    /// Marks this entry as dirty.
+    ///
+    /// The dirty flag is not interpreted by the map. It allows write-back
+    /// layers to track which entries have changes that have not been written
+    /// to their backing store. New entries are not dirty, and the flag is
+    /// removed along with the entry when it is evicted or removed. Replacing
+    /// the value of an entry does not change the flag.
+    ///
+    /// When a dirty entry is evicted, the map's observer is notified through
+    /// [`CacheObserver::on_evict_dirty()`](crate::CacheObserver::on_evict_dirty)
+    /// rather than `on_evict()`.
+    ///
+    /// This function does not touch the key.
+    pub fn mark_dirty(&mut self) {
+        self.cache
+            .cache_mut()
+            .get_mut_without_touch(self.node)
+            .set_dirty(true);
+    }
+
+    /// Clears this entry's dirty flag. See [`Self::mark_dirty()`] for more
+    /// information.
+    ///
+    /// This function does not touch the key.
+    pub fn mark_clean(&mut self) {
+        self.cache
+            .cache_mut()
+            .get_mut_without_touch(self.node)
+            .set_dirty(false);
+    }
+
+    /// Returns a handle to this entry that does not borrow the map. The handle
+    /// can be converted back into an [`EntryRef`] using the map's `upgrade()`
+    /// function.
+    ///
+    /// The handle stores a clone of the key. For maps with expensive to clone
+    /// keys, consider storing keys in an `Arc`.
+    pub fn downgrade(&self) -> KeyHandle<Key>
+    where
+        Key: Clone,
+    {
+        KeyHandle {
+            key: self.key().clone(),
+            entry: self.id(),
+        }
+    }
+
+    /// Returns an identifier of this entry that remains valid until the
+    /// entry is removed. See [`EntryId`] for more information.
+    ///
+    /// ```rust
+    /// use lrumap::{LruHashMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = LruHashMap::new(2);
+    /// lru.push(1, 1);
+    /// let id = lru.entry(&1).unwrap().id();
+    ///
+    /// lru.push(1, 11);
+    /// assert!(lru.contains_entry(id));
+    /// lru.take(&1);
+    /// lru.push(2, 2);
+    /// assert!(!lru.contains_entry(id));
+    /// ```
+    #[must_use]
+    pub fn id(&self) -> EntryId {
+        self.cache.cache().entry_id(self.node)
+    }
+
+    /// Returns a cursor for this entry that does not borrow the map. The
+    /// cursor can be converted back into an [`EntryRef`] using the map's
+    /// `resume()` function.
+    pub fn cursor(&self) -> OwnedCursor {
+        OwnedCursor { entry: self.id() }
     }
 
     /// Returns an iterator over the least-recently used keys beginning with the
     /// current entry.
     pub fn iter(&self) -> Iter<'_, Key, Value> {
-        Iter {
-            cache: self.cache.cache(),
-            node: IterState::StartingAt(self.node),
-        }
+        self.cache.cache().iter_starting_at(self.node)
     }
 
     /// Updates this reference to point to the next least recently used key in
@@ -411,7 +1220,7 @@ where
     /// the entry is the last entry in the list.
     #[must_use]
     pub fn move_next(&mut self) -> bool {
-        if let Some(next) = self.cache.cache().get_without_touch(self.node).next {
+        if let Some(next) = self.cache.cache().next(self.node) {
             self.node = next;
             self.accessed = false;
             true
@@ -425,7 +1234,7 @@ where
     /// if the entry is the first entry in the list.
     #[must_use]
     pub fn move_previous(&mut self) -> bool {
-        if let Some(previous) = self.cache.cache().get_without_touch(self.node).previous {
+        if let Some(previous) = self.cache.cache().previous(self.node) {
             self.node = previous;
             self.accessed = false;
             true
@@ -435,7 +1244,7 @@ where
     }
 
     fn remove_with_direction(mut self, move_next: bool) -> ((Key, Value), Option<Self>) {
-        let (removed, next, previous) = self.cache.remove(self.node);
+        let (removed, next, previous) = self.cache.remove_node(self.node);
         let new_self = match (move_next, next, previous) {
             (true, Some(next), _) => {
                 self.node = next;
@@ -497,12 +1306,460 @@ pub enum Removed<Key, Value> {
     Evicted(Key, Value),
 }
 
+/// How a map handles a key that is already present when extending it using
+/// `extend_with()`.
+#[derive(Debug)]
+pub enum DuplicatePolicy<Value> {
+    /// The stored value is replaced and the key is touched. This is the
+    /// behavior of `extend()`.
+    Replace,
+    /// The stored value is kept and the key is not touched. The new value is
+    /// dropped.
+    Ignore,
+    /// The new value is merged into the stored value using the function, and
+    /// the key is touched.
+    Merge(fn(&mut Value, Value)),
+}
+
+// Deriving these traits would require `Value` to implement them, even though
+// function pointers are always `Copy`.
+impl<Value> Clone for DuplicatePolicy<Value> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Value> Copy for DuplicatePolicy<Value> {}
+
+/// A slot reserved in a Least Recently Used map by `reserve_slot()`.
+///
+/// Pushing a new key using the token with `push_with_token()` never evicts
+/// an entry. While the token is outstanding, the map treats the reserved slot
+/// as occupied. The slot is released when the token is pushed with, passed to
+/// `release_slot()`, or dropped.
+///
+/// Each token belongs to the map that reserved it. Pushing with a token on
+/// another map returns an error, and the token's slot is released in the map
+/// that reserved it.
+#[derive(Debug)]
+#[must_use = "the reserved slot is released when the token is dropped"]
+pub struct SlotToken {
+    reservations: Arc<AtomicUsize>,
+}
+
+impl Drop for SlotToken {
+    fn drop(&mut self) {
+        self.reservations.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Statistics about the node storage of a Least Recently Used map, including
+/// a verified length of the chain of vacant slots.
+///
+/// Each entry is stored in a slot of a `Vec`. When an entry is removed, its
+/// slot becomes vacant and is reused by the next inserted entry. For a summary
+/// that can be computed in constant time, see [`Occupancy`]. This struct is
+/// non-exhaustive so that more measurements can be added without breaking
+/// existing code.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct VacancyStats {
+    /// The number of slots containing an entry.
+    pub occupied_slots: usize,
+    /// The number of allocated slots not containing an entry.
+    pub vacant_slots: usize,
+    /// The number of slots in the chain of vacant slots awaiting reuse. This
+    /// should always be equal to `vacant_slots`.
+    pub vacant_chain: usize,
+}
+
+/// A summary of how full a Least Recently Used map is, intended for periodic
+/// reporting and capacity planning.
+///
+/// Entries do not have weights, so occupancy is measured only in entries.
+/// [`VacancyStats`] additionally verifies the chain of vacant slots. This
+/// struct is non-exhaustive so that more measurements can be added without
+/// breaking existing code.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Occupancy {
+    /// The number of entries stored.
+    pub entries: usize,
+    /// The maximum number of entries that can be stored before the least
+    /// recently used entry is evicted.
+    pub capacity: usize,
+    /// The number of allocated slots not containing an entry. These slots are
+    /// reused before any more memory is allocated.
+    pub vacant_slots: usize,
+}
+
+/// A corruption of a cache's internal structure detected by
+/// [`LruPeek::validate()`](crate::LruPeek::validate).
+///
+/// Operations on a corrupted map may panic, as there are no `try_` variants
+/// of the functions that follow the links between entries. Call
+/// [`LruPeek::validate()`](crate::LruPeek::validate) before using a map
+/// whose structure may have been corrupted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CorruptionError {
+    /// Following the links from the most recently used entry visited more
+    /// entries than the cache contains, which indicates a cycle.
+    Cycle,
+    /// The slot at index `slot` does not link back to the entry that links to
+    /// it, is vacant, or does not exist.
+    BrokenLink {
+        /// The index of the slot.
+        slot: usize,
+    },
+    /// The least recently used entry is not the last linked entry.
+    InvalidTail,
+    /// The number of linked entries does not match the cache's length.
+    LengthMismatch {
+        /// The cache's length.
+        length: usize,
+        /// The number of linked entries.
+        linked: usize,
+    },
+}
+
+impl Display for CorruptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cycle => f.write_str("cycle detected"),
+            Self::BrokenLink { slot } => write!(f, "broken link at slot {slot}"),
+            Self::InvalidTail => f.write_str("tail is not the last linked entry"),
+            Self::LengthMismatch { length, linked } => {
+                write!(f, "length is {length}, but {linked} entries are linked")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CorruptionError {}
+
+/// An error returned when a map cannot be configured as requested.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConfigError {
+    /// The capacity is less than 2 or greater than `u32::MAX`.
+    InvalidCapacity(usize),
+    /// The headroom is not less than the map's capacity.
+    InvalidHeadroom {
+        /// The requested headroom.
+        headroom: usize,
+        /// The map's capacity.
+        capacity: usize,
+    },
+    /// Reserving another slot would leave no unreserved slots.
+    NoUnreservedSlots,
+    /// The insertion sampling rate is 0.
+    InvalidSamplingRate,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCapacity(capacity) => write!(
+                f,
+                "capacity must be greater than 1 and at most u32::MAX, but is {capacity}"
+            ),
+            Self::InvalidHeadroom { headroom, capacity } => write!(
+                f,
+                "headroom must be less than capacity, but {headroom} >= {capacity}"
+            ),
+            Self::NoUnreservedSlots => f.write_str("at least one slot must remain unreserved"),
+            Self::InvalidSamplingRate => f.write_str("insertion sampling rate must be at least 1"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Returns an error if `capacity` is not a valid capacity for the maps in
+/// this crate.
+pub fn validate_capacity(capacity: usize) -> Result<(), ConfigError> {
+    if capacity > 1 && u32::try_from(capacity).is_ok() {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidCapacity(capacity))
+    }
+}
+
+/// An iterator over the indices of the slots containing an entry. See
+/// [`VacancyStats`] for more information.
+#[must_use]
+pub struct OccupiedSlots<'a, Key, Value> {
+    slots: Slots<'a, Node<Key, Value>>,
+}
+
+impl<Key, Value> Iterator for OccupiedSlots<'_, Key, Value> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots
+            .find(|(_, node)| node.is_some())
+            .map(|(node, _)| node.as_usize())
+    }
+}
+
+/// An iterator over the keys and values of a Least Recently Used map, in the
+/// order they are stored rather than the order they were touched.
+///
+/// See [`LruPeek::iter_unordered()`](crate::LruPeek::iter_unordered) for more
+/// information.
+#[must_use]
+pub struct Unordered<'a, Key, Value> {
+    slots: Slots<'a, Node<Key, Value>>,
+    remaining: usize,
+}
+
+impl<'a, Key, Value> Iterator for Unordered<'a, Key, Value> {
+    type Item = (&'a Key, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.slots.find_map(|(_, node)| node)?;
+        self.remaining -= 1;
+        Some((node.key(), node.value()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<Key, Value> ExactSizeIterator for Unordered<'_, Key, Value> {}
+
+impl<Key, Value> FusedIterator for Unordered<'_, Key, Value> {}
+
+/// An iterator over the entries of a map in an order that depends only on the
+/// operations performed on the map, from most recently touched to least
+/// recently touched.
+///
+/// Entries that were last touched by the same change are ordered by when they
+/// were touched or inserted. Neither the hasher nor its seed affect the order.
+/// See
+/// [`LruHashMap::iter_deterministic()`](crate::LruHashMap::iter_deterministic)
+/// for more information.
+#[must_use]
+pub struct Deterministic<'a, Key, Value>(Iter<'a, Key, Value>);
+
+impl<'a, Key, Value> Iterator for Deterministic<'a, Key, Value> {
+    type Item = (&'a Key, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<Key, Value> DoubleEndedIterator for Deterministic<'_, Key, Value> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+/// An iterator over the entries closest to being evicted, in order from least
+/// recently touched to most recently touched.
+///
+/// See [`LruPeek::iter_expiring_within()`](crate::LruPeek::iter_expiring_within)
+/// for more information.
+#[must_use]
+pub struct Expiring<'a, Key, Value> {
+    cache: &'a LruCache<Key, Value>,
+    node: Option<NodeId>,
+    staleness: usize,
+    remaining: usize,
+}
+
+impl<'a, Key, Value> Iterator for Expiring<'a, Key, Value> {
+    type Item = (&'a Key, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node_id = self.node?;
+        let node = &self.cache.nodes[node_id];
+        let tail = &self.cache.nodes[self.cache.tail()?];
+        if self.remaining > 0
+            && usize::try_from(node.last_accessed.saturating_sub(tail.last_accessed))
+                .unwrap_or(usize::MAX)
+                <= self.staleness
+        {
+            self.node = self.cache.previous(node_id);
+            self.remaining -= 1;
+            Some((node.key(), node.value()))
+        } else {
+            self.node = None;
+            None
+        }
+    }
+}
+
+impl<Key, Value> FusedIterator for Expiring<'_, Key, Value> {}
+
+/// An iterator over the entries that have been marked dirty, in order from
+/// least recently touched to most recently touched.
+///
+/// See [`LruPeek::iter_dirty()`](crate::LruPeek::iter_dirty) for more
+/// information.
+#[must_use]
+pub struct Dirty<'a, Key, Value> {
+    cache: &'a LruCache<Key, Value>,
+    node: Option<NodeId>,
+    remaining: usize,
+}
+
+impl<'a, Key, Value> Iterator for Dirty<'a, Key, Value> {
+    type Item = (&'a Key, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let node_id = self.node?;
+            let node = &self.cache.nodes[node_id];
+            self.node = self.cache.previous(node_id);
+            self.remaining -= 1;
+            if node.dirty {
+                return Some((node.key(), node.value()));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<Key, Value> FusedIterator for Dirty<'_, Key, Value> {}
+
+/// An iterator over the least recently touched entries of a cache, in order
+/// from least recently touched to most recently touched.
+///
+/// See [`LruPeek::iter_tail()`](crate::LruPeek::iter_tail) for more
+/// information.
+#[must_use]
+pub struct IterTail<'a, Key, Value> {
+    cache: &'a LruCache<Key, Value>,
+    node: Option<NodeId>,
+    remaining: usize,
+}
+
+impl<'a, Key, Value> Iterator for IterTail<'a, Key, Value> {
+    type Item = (&'a Key, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node_id = self.node?;
+        let node = &self.cache.nodes[node_id];
+        self.node = self.cache.previous(node_id);
+        self.remaining -= 1;
+        Some((node.key(), node.value()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<Key, Value> ExactSizeIterator for IterTail<'_, Key, Value> {}
+
+impl<Key, Value> FusedIterator for IterTail<'_, Key, Value> {}
+
+/// An iterator over a cache's keys and their
+/// [staleness](crate::EntryRef::staleness), in order from least recently
+/// touched to most recently touched.
+#[must_use]
+pub struct KeysByStaleness<'a, Key, Value> {
+    cache: &'a LruCache<Key, Value>,
+    node: Option<NodeId>,
+    remaining: usize,
+}
+
+impl<'a, Key, Value> Iterator for KeysByStaleness<'a, Key, Value> {
+    type Item = (usize, &'a Key);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node_id = self.node?;
+        let node = &self.cache.nodes[node_id];
+        self.node = self.cache.previous(node_id);
+        self.remaining -= 1;
+        Some((self.cache.changes_since(node.last_accessed), node.key()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<Key, Value> ExactSizeIterator for KeysByStaleness<'_, Key, Value> {}
+
+impl<Key, Value> FusedIterator for KeysByStaleness<'_, Key, Value> {}
+
 /// A double-ended iterator over a cache's keys and values in order from most
 /// recently touched to least recently touched.
+///
+/// This iterator acts as a cursor: calling `next_back()` moves the cursor
+/// towards the most recently touched entry, which allows entries returned by
+/// `next()` to be returned again. Because the same entries can be returned
+/// from either end, this iterator does not implement `ExactSizeIterator`.
+/// Its `size_hint()` describes the entries `next()` will return, and is exact
+/// whenever the cursor's position in the list is known, such as for an
+/// iterator returned from `iter()`. A fresh iterator is positioned before
+/// the most recently touched entry, so reversing it returns no entries
+/// regardless of its size hint.
+///
+/// Iteration never visits more entries than the cache contains, even if the
+/// links between entries are corrupt. When feature `corruption-reports` is
+/// enabled, `corruption()` returns the error detected if iteration stopped
+/// early because of a corrupt link.
 #[must_use]
 pub struct Iter<'a, Key, Value> {
     cache: &'a LruCache<Key, Value>,
     node: IterState,
+    /// The most entries `next()` can return before reaching the tail.
+    ahead: usize,
+    /// The most entries `next_back()` can return before reaching the head.
+    behind: usize,
+    /// True if `ahead` and `behind` are exact because the position of the
+    /// cursor in the list is known.
+    exact: bool,
+    #[cfg(feature = "corruption-reports")]
+    corruption: Option<CorruptionError>,
+}
+
+impl<Key, Value> Iter<'_, Key, Value> {
+    /// Returns the error detected if this iterator stopped early because the
+    /// links between entries are corrupt.
+    ///
+    /// This cannot happen when using the map types provided by this crate.
+    /// This function is only available when feature `corruption-reports` is
+    /// enabled.
+    #[cfg(feature = "corruption-reports")]
+    #[must_use]
+    pub const fn corruption(&self) -> Option<CorruptionError> {
+        self.corruption
+    }
+
+    /// Records that iteration stopped early because a link leads back to an
+    /// entry that was already visited.
+    #[cfg_attr(
+        not(feature = "corruption-reports"),
+        allow(clippy::unused_self, clippy::needless_pass_by_ref_mut)
+    )]
+    const fn detected_cycle(&mut self) {
+        #[cfg(feature = "corruption-reports")]
+        {
+            self.corruption = Some(CorruptionError::Cycle);
+        }
+    }
 }
 
 enum IterState {
@@ -517,41 +1774,511 @@ impl<'a, Key, Value> Iterator for Iter<'a, Key, Value> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let next_node = match self.node {
-            IterState::BeforeHead => self.cache.head,
+            IterState::BeforeHead => self.cache.head(),
             IterState::StartingAt(node) => Some(node),
-            IterState::Node(node) => self.cache.nodes[node.as_usize()].next,
+            IterState::Node(node) => self.cache.next(node),
             IterState::AfterTail => None,
         };
+        let next_node = next_node.filter(|_| {
+            if self.ahead == 0 {
+                self.detected_cycle();
+                false
+            } else {
+                true
+            }
+        });
         if let Some(node_id) = next_node {
-            let node = &self.cache.nodes[node_id.as_usize()];
+            let node = &self.cache.nodes[node_id];
+            // When moving forwards from a node that was returned, that node
+            // will be returned by `next_back()`.
+            if matches!(self.node, IterState::Node(_)) {
+                self.behind = (self.behind + 1).min(self.cache.len());
+            }
             self.node = IterState::Node(node_id);
+            self.ahead -= 1;
             Some((node.key(), node.value()))
         } else {
             self.node = IterState::AfterTail;
+            self.ahead = 0;
+            self.behind = self.cache.len();
+            self.exact = true;
             None
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.exact {
+            (self.ahead, Some(self.ahead))
+        } else {
+            (0, Some(self.ahead))
+        }
+    }
 }
 impl<'a, Key, Value> DoubleEndedIterator for Iter<'a, Key, Value> {
     fn next_back(&mut self) -> Option<Self::Item> {
         let previous_node = match self.node {
             IterState::BeforeHead => None,
-            IterState::StartingAt(node) | IterState::Node(node) => {
-                self.cache.nodes[node.as_usize()].previous
+            IterState::StartingAt(node) | IterState::Node(node) => self.cache.previous(node),
+            IterState::AfterTail => self.cache.tail(),
+        }
+        .filter(|_| {
+            if self.behind == 0 {
+                self.detected_cycle();
+                false
+            } else {
+                true
             }
-            IterState::AfterTail => self.cache.tail,
-        };
+        });
         if let Some(node_id) = previous_node {
-            let node = &self.cache.nodes[node_id.as_usize()];
+            let node = &self.cache.nodes[node_id];
+            // When moving backwards from a node that was returned, that node
+            // will be returned by `next()` again.
+            if matches!(self.node, IterState::Node(_)) {
+                self.ahead = (self.ahead + 1).min(self.cache.len());
+            }
             self.node = IterState::Node(node_id);
+            self.behind -= 1;
             Some((node.key(), node.value()))
         } else {
             self.node = IterState::BeforeHead;
+            self.ahead = self.cache.len();
+            self.behind = 0;
+            self.exact = true;
             None
         }
     }
 }
 
+// Once `next()` returns `None`, the cursor is after the tail, where `next()`
+// keeps returning `None` until `next_back()` moves the cursor.
+impl<Key, Value> FusedIterator for Iter<'_, Key, Value> {}
+
+/// An iterator over the keys and a projection of the values of a Least
+/// Recently Used map, in order from most recently touched to least recently
+/// touched.
+///
+/// See [`LruPeek::values_projected()`](crate::LruPeek::values_projected) for
+/// more information.
+#[must_use]
+pub struct Projected<'a, Key, Value, Project> {
+    iter: Iter<'a, Key, Value>,
+    project: Project,
+}
+
+impl<'a, Key, Value, Project> Projected<'a, Key, Value, Project> {
+    pub(crate) const fn new(iter: Iter<'a, Key, Value>, project: Project) -> Self {
+        Self { iter, project }
+    }
+}
+
+impl<'a, Key, Value, Project, Projection> Iterator for Projected<'a, Key, Value, Project>
+where
+    Project: FnMut(&'a Value) -> &'a Projection,
+    Projection: ?Sized + 'a,
+{
+    type Item = (&'a Key, &'a Projection);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?;
+        Some((key, (self.project)(value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Projections start at the head, so the bound on the entries ahead is
+        // exact.
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, Key, Value, Project, Projection> ExactSizeIterator for Projected<'a, Key, Value, Project>
+where
+    Project: FnMut(&'a Value) -> &'a Projection,
+    Projection: ?Sized + 'a,
+{
+}
+
+impl<'a, Key, Value, Project, Projection> FusedIterator for Projected<'a, Key, Value, Project>
+where
+    Project: FnMut(&'a Value) -> &'a Projection,
+    Projection: ?Sized + 'a,
+{
+}
+
+/// An iterator that removes the least recently used entries from a map until
+/// it contains no more than a target number of entries.
+///
+/// Entries are removed as the iterator is advanced. If the iterator is dropped
+/// before it is exhausted, the remaining entries are removed and dropped.
+#[must_use]
+pub struct Trim<'a, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    cache: &'a mut Cache,
+    target: usize,
+    evicting: bool,
+    /// A node that is never removed, even when it is the tail.
+    sparing: Option<NodeId>,
+    _phantom: PhantomData<(Key, Value)>,
+}
+
+impl<'a, Cache, Key, Value> Trim<'a, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    /// Returns a trim that reports the removed entries as evictions.
+    pub(crate) const fn evicting(cache: &'a mut Cache, target: usize) -> Self {
+        Self {
+            cache,
+            target,
+            evicting: true,
+            sparing: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns a trim that does not report the removed entries as evictions.
+    pub(crate) const fn removing(cache: &'a mut Cache, target: usize) -> Self {
+        Self {
+            cache,
+            target,
+            evicting: false,
+            sparing: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Cache, Key, Value> Iterator for Trim<'_, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    type Item = (Key, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cache.cache().len() > self.target {
+            let mut tail = self.cache.cache().tail()?;
+            if self.sparing == Some(tail) {
+                tail = self.cache.cache().previous(tail)?;
+            }
+            let dirty = self.cache.cache().get_without_touch(tail).is_dirty();
+            let ((key, value), ..) = self.cache.remove_node(tail);
+            if self.evicting {
+                self.cache.notify_evicted(&key, &value, dirty);
+            }
+            Some((key, value))
+        } else {
+            None
+        }
+    }
+}
+
+impl<Cache, Key, Value> Drop for Trim<'_, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+/// Evicts the least recently used entries of `cache` until it has its
+/// configured headroom available.
+pub fn evict_headroom<Cache, Key, Value>(cache: &mut Cache)
+where
+    Cache: EntryCache<Key, Value>,
+{
+    let target = (cache.cache().capacity() - cache.cache().headroom())
+        .saturating_sub(cache.cache().reserved());
+    Trim::evicting(cache, target).for_each(drop);
+}
+
+/// Evicts the least recently used entries of `cache` other than `node` until
+/// it has its configured headroom available.
+pub fn evict_headroom_sparing<Cache, Key, Value>(cache: &mut Cache, node: NodeId)
+where
+    Cache: EntryCache<Key, Value>,
+{
+    let target = (cache.cache().capacity() - cache.cache().headroom())
+        .saturating_sub(cache.cache().reserved());
+    let mut trim = Trim::evicting(cache, target);
+    trim.sparing = Some(node);
+    trim.for_each(drop);
+}
+
+/// Reserves a slot in `cache`, evicting the least recently used entry if
+/// pushing a new key would evict it. The configured headroom is evicted after
+/// an eviction, as when pushing.
+///
+/// # Panics
+///
+/// Panics if every slot of `cache` is already reserved.
+pub fn reserve_slot<Cache, Key, Value>(cache: &mut Cache) -> (SlotToken, Option<(Key, Value)>)
+where
+    Cache: EntryCache<Key, Value>,
+{
+    assert!(
+        cache.cache().reserved() + 1 < cache.cache().capacity(),
+        "at least one slot must remain unreserved"
+    );
+    let tail = if cache.cache().push_evicts() {
+        cache.cache().tail()
+    } else {
+        None
+    };
+    let evicted = tail.map(|tail| {
+        let dirty = cache.cache().get_without_touch(tail).is_dirty();
+        let (evicted, ..) = cache.remove_node(tail);
+        cache.notify_evicted(&evicted.0, &evicted.1, dirty);
+        evicted
+    });
+    let token = cache.cache_mut().reserve();
+    if evicted.is_some() {
+        evict_headroom(cache);
+    }
+    (token, evicted)
+}
+
+/// Reserves a slot in `cache` like [`reserve_slot()`], returning an error
+/// instead of panicking if every slot is already reserved.
+pub fn try_reserve_slot<Cache, Key, Value>(
+    cache: &mut Cache,
+) -> Result<(SlotToken, Option<(Key, Value)>), ConfigError>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    if cache.cache().reserved() + 1 < cache.cache().capacity() {
+        Ok(reserve_slot(cache))
+    } else {
+        Err(ConfigError::NoUnreservedSlots)
+    }
+}
+
+/// Invokes `operations` with `cache` while grouping its touches into a single
+/// change. The batch ends when `operations` returns or panics.
+pub fn batch<Cache, Key, Value, Output>(
+    cache: &mut Cache,
+    operations: impl FnOnce(&mut Cache) -> Output,
+) -> Output
+where
+    Cache: EntryCache<Key, Value>,
+{
+    struct BatchGuard<'a, Cache, Key, Value>
+    where
+        Cache: EntryCache<Key, Value>,
+    {
+        cache: &'a mut Cache,
+        previous: Batch,
+        _phantom: PhantomData<(Key, Value)>,
+    }
+
+    impl<Cache, Key, Value> Drop for BatchGuard<'_, Cache, Key, Value>
+    where
+        Cache: EntryCache<Key, Value>,
+    {
+        fn drop(&mut self) {
+            self.cache.cache_mut().end_batch(self.previous);
+        }
+    }
+
+    let previous = cache.cache_mut().begin_batch();
+    let guard = BatchGuard {
+        cache,
+        previous,
+        _phantom: PhantomData,
+    };
+    operations(guard.cache)
+}
+
+/// Invokes `operations` with `cache`, returning its result along with a copy
+/// of every entry evicted while it ran. The capture ends when `operations`
+/// returns or panics.
+pub fn with_eviction_capture<Cache, Key, Value, Output>(
+    cache: &mut Cache,
+    operations: impl FnOnce(&mut Cache) -> Output,
+) -> (Output, Vec<(Key, Value)>)
+where
+    Cache: EntryCache<Key, Value>,
+    Key: Clone,
+    Value: Clone,
+{
+    struct CaptureGuard<'a, Cache, Key, Value>
+    where
+        Cache: EntryCache<Key, Value>,
+        Key: Clone,
+        Value: Clone,
+    {
+        cache: &'a mut Cache,
+        start: Option<usize>,
+        /// True until the capture has been ended.
+        active: bool,
+        _phantom: PhantomData<(Key, Value)>,
+    }
+
+    impl<Cache, Key, Value> Drop for CaptureGuard<'_, Cache, Key, Value>
+    where
+        Cache: EntryCache<Key, Value>,
+        Key: Clone,
+        Value: Clone,
+    {
+        fn drop(&mut self) {
+            if self.active {
+                self.cache.cache_mut().end_capture(self.start);
+            }
+        }
+    }
+
+    let start = cache.cache_mut().begin_capture();
+    let mut guard = CaptureGuard {
+        cache,
+        start,
+        active: true,
+        _phantom: PhantomData,
+    };
+    let output = operations(guard.cache);
+    let evicted = guard.cache.cache_mut().end_capture(start);
+    guard.active = false;
+    (output, evicted)
+}
+
+/// Pushes `key` and `value` into `cache` using the slot reserved by `token`,
+/// without evicting any entries. Returns the previous value if `key` was
+/// already present.
+///
+/// If `token` was reserved by another map, `cache` is not modified, `token`
+/// is dropped, and `key` and `value` are returned as an error.
+pub fn push_with_token<Cache, Key, Value>(
+    cache: &mut Cache,
+    token: SlotToken,
+    key: Key,
+    value: Value,
+    push: impl FnOnce(&mut Cache, Key, Value) -> Option<Removed<Key, Value>>,
+) -> Result<Option<Value>, (Key, Value)>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    struct DeferredGuard<'a, Cache, Key, Value>
+    where
+        Cache: EntryCache<Key, Value>,
+    {
+        cache: &'a mut Cache,
+        previous: bool,
+        _phantom: PhantomData<(Key, Value)>,
+    }
+
+    impl<Cache, Key, Value> Drop for DeferredGuard<'_, Cache, Key, Value>
+    where
+        Cache: EntryCache<Key, Value>,
+    {
+        fn drop(&mut self) {
+            self.cache.cache_mut().set_deferred_eviction(self.previous);
+        }
+    }
+
+    if !cache.cache().owns(&token) {
+        return Err((key, value));
+    }
+    drop(token);
+
+    // Eviction is deferred for this push, ensuring it never evicts, even if
+    // the map was filled past its capacity while eviction was deferred. The
+    // previous setting is restored even if the push panics.
+    let previous = cache.cache().deferred_eviction();
+    cache.cache_mut().set_deferred_eviction(true);
+    let guard = DeferredGuard {
+        cache,
+        previous,
+        _phantom: PhantomData,
+    };
+    let removed = push(guard.cache, key, value);
+    drop(guard);
+    match removed {
+        Some(Removed::PreviousValue(previous)) => Ok(Some(previous)),
+        Some(Removed::Evicted(..)) | None => Ok(None),
+    }
+}
+
+/// A cursor over the entries of a Least Recently Used map, from most
+/// recently used to least recently used.
+///
+/// Unlike [`Iter`], the entry last returned by [`Self::advance()`] can be
+/// removed or touched without ending the iteration. Entries touched during
+/// iteration become the most recently used entries and are not visited again.
+#[must_use]
+pub struct CursorIter<'a, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    cache: &'a mut Cache,
+    current: Option<NodeId>,
+    upcoming: Option<NodeId>,
+    remaining: usize,
+    _phantom: PhantomData<(Key, Value)>,
+}
+
+impl<'a, Cache, Key, Value> CursorIter<'a, Cache, Key, Value>
+where
+    Cache: EntryCache<Key, Value>,
+{
+    pub(crate) fn new(cache: &'a mut Cache) -> Self {
+        let upcoming = cache.cache().head();
+        let remaining = cache.cache().len();
+        Self {
+            cache,
+            current: None,
+            upcoming,
+            remaining,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Moves to the next entry, returning its key and value. Returns `None`
+    /// once every entry has been visited.
+    ///
+    /// This function does not touch any keys.
+    pub fn advance(&mut self) -> Option<(&Key, &Value)> {
+        self.current = None;
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.upcoming?;
+        self.remaining -= 1;
+        self.current = Some(node);
+        let cache = self.cache.cache();
+        self.upcoming = cache.next(node);
+        let entry = cache.get_without_touch(node);
+        Some((entry.key(), entry.value()))
+    }
+
+    /// Returns the key and value of the current entry, if any.
+    #[must_use]
+    pub fn current(&self) -> Option<(&Key, &Value)> {
+        self.current.map(|node| {
+            let entry = self.cache.cache().get_without_touch(node);
+            (entry.key(), entry.value())
+        })
+    }
+
+    /// Removes and returns the current entry's key and value. The next call
+    /// to [`Self::advance()`] continues with the entry that followed it.
+    pub fn remove_current(&mut self) -> Option<(Key, Value)> {
+        let node = self.current.take()?;
+        let (removed, ..) = self.cache.remove_node(node);
+        Some(removed)
+    }
+
+    /// Touches the current entry, making it the most recently used key.
+    /// Returns false if there is no current entry.
+    pub fn touch_current(&mut self) -> bool {
+        if let Some(node) = self.current {
+            self.cache.cache_mut().touch(node);
+            true
+        } else {
+            false
+        }
+    }
+}
+
 pub struct IntoIter<Key, Value> {
     cache: LruCache<Key, Value>,
 }
@@ -566,9 +2293,101 @@ impl<Key, Value> Iterator for IntoIter<Key, Value> {
     type Item = (Key, Value);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.cache.len() == 0 {
+            return None;
+        }
         self.cache.head().map(|node| {
             let (removed, ..) = self.cache.remove(node);
             removed
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cache.len(), Some(self.cache.len()))
+    }
+}
+
+impl<Key, Value> DoubleEndedIterator for IntoIter<Key, Value> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.cache.len() == 0 {
+            return None;
+        }
+        self.cache.tail().map(|node| {
+            let (removed, ..) = self.cache.remove(node);
+            removed
+        })
+    }
+}
+
+impl<Key, Value> ExactSizeIterator for IntoIter<Key, Value> {}
+
+impl<Key, Value> FusedIterator for IntoIter<Key, Value> {}
+
+#[test]
+fn corrupt_caches_format_and_validate() {
+    let mut cache = LruCache::new(3);
+    cache.push(1, 1);
+    cache.push(2, 2);
+    cache.push(3, 3);
+    assert_eq!(cache.validate(), Ok(()));
+
+    // Link the tail back to the head.
+    let tail = cache.tail().unwrap();
+    cache.nodes.set_next(tail, cache.head());
+    assert_eq!(format!("{cache:?}").matches("Node").count(), 3);
+    assert_eq!(cache.validate(), Err(CorruptionError::Cycle));
+    let mut iter = cache.iter();
+    assert_eq!(iter.by_ref().count(), 3);
+    #[cfg(feature = "corruption-reports")]
+    assert_eq!(iter.corruption(), Some(CorruptionError::Cycle));
+    assert_eq!(cache.iter_starting_at(tail).count(), 3);
+    #[cfg(feature = "corruption-reports")]
+    assert_eq!(cache.iter().corruption(), None);
+
+    cache.nodes.set_next(tail, None);
+    cache.nodes.set_len(2);
+    assert_eq!(format!("{cache:?}").matches("Node").count(), 2);
+    assert_eq!(cache.validate(), Err(CorruptionError::Cycle));
+
+    cache.nodes.set_len(4);
+    assert_eq!(
+        cache.validate(),
+        Err(CorruptionError::LengthMismatch {
+            length: 4,
+            linked: 3
+        })
+    );
+
+    // A cycle shorter than the length stops at the repeated node.
+    cache.nodes.set_next(tail, cache.head());
+    let formatted = format!("{cache:?}");
+    assert_eq!(formatted.matches("Node {").count(), 3);
+    assert!(formatted.ends_with(&format!("<repeated {:?}>]", cache.head().unwrap())));
+
+    // A link to a vacant slot stops at the missing node.
+    cache.nodes.set_next(tail, None);
+    cache.nodes.set_len(3);
+    let ((removed, _), _, _) = cache.remove(tail);
+    assert_eq!(removed, 1);
+    let middle = cache
+        .head()
+        .and_then(|head| cache.nodes.next(head))
+        .unwrap();
+    cache.nodes.set_next(middle, Some(tail));
+    cache.nodes.set_len(3);
+    let formatted = format!("{cache:?}");
+    assert_eq!(formatted.matches("Node {").count(), 2);
+    assert!(formatted.ends_with(&format!("<missing {tail:?}>]")));
+}
+
+#[test]
+fn sequence_saturates() {
+    let mut cache = LruCache::new(2);
+    cache.push(1, 1);
+    cache.sequence = u64::MAX - 1;
+    cache.push(2, 2);
+    cache.push(3, 3);
+    assert_eq!(cache.sequence(), u64::MAX);
+    assert_eq!(cache.changes_since(0), usize::MAX);
+    assert_eq!(cache.changes_since(u64::MAX), 0);
 }
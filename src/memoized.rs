@@ -0,0 +1,150 @@
+use std::borrow::Borrow;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::{LruHashMap, LruPeek};
+
+/// A function whose most recently used outputs are cached in an
+/// [`LruHashMap`].
+///
+/// Calling [`Self::call()`] returns the cached output for the arguments if
+/// present. Otherwise, the function is invoked and its output is cached,
+/// evicting the output for the least recently used arguments if the cache is
+/// full. Functions with multiple arguments can be memoized by accepting a
+/// tuple.
+///
+/// ```rust
+/// use lrumap::Memoized;
+///
+/// let mut calls = 0;
+/// let mut area = Memoized::new(16, |&(width, height): &(u32, u32)| {
+///     calls += 1;
+///     width * height
+/// });
+///
+/// assert_eq!(*area.call((2, 3)), 6);
+/// assert_eq!(*area.call((2, 3)), 6);
+/// assert_eq!(*area.call((4, 5)), 20);
+/// drop(area);
+/// assert_eq!(calls, 2);
+/// ```
+///
+/// A memoized function can also be given a time-to-live using
+/// [`Self::with_ttl()`], which causes outputs to be recomputed once they
+/// have been cached for longer than the time-to-live.
+#[must_use]
+pub struct Memoized<Args, Output, Function> {
+    function: Function,
+    outputs: LruHashMap<Args, Memo<Output>>,
+    ttl: Option<Duration>,
+}
+
+/// A cached output and when it was computed.
+#[derive(Debug)]
+struct Memo<Output> {
+    output: Output,
+    computed: Instant,
+}
+
+impl<Args, Output, Function> Memoized<Args, Output, Function>
+where
+    Args: Hash + Eq + Clone,
+    Function: FnMut(&Args) -> Output,
+{
+    /// Returns `function` memoized with a cache of up to `capacity` outputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn new(capacity: usize, function: Function) -> Self {
+        Self {
+            function,
+            outputs: LruHashMap::new(capacity),
+            ttl: None,
+        }
+    }
+
+    /// Returns `function` memoized with a cache of up to `capacity` outputs.
+    /// Each output is recomputed once it has been cached for longer than
+    /// `ttl`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn with_ttl(capacity: usize, ttl: Duration, function: Function) -> Self {
+        Self {
+            ttl: Some(ttl),
+            ..Self::new(capacity, function)
+        }
+    }
+
+    /// Returns the output of the function for `args`, invoking the function
+    /// only if the output is not cached or has expired.
+    pub fn call(&mut self, args: Args) -> &Output {
+        self.call_at(args, Instant::now)
+    }
+
+    pub(crate) fn call_at(&mut self, args: Args, now: impl Fn() -> Instant) -> &Output {
+        let cached = self
+            .outputs
+            .get_without_update(&args)
+            .map(|memo| memo.computed);
+        let fresh = match (cached, self.ttl) {
+            (Some(computed), Some(ttl)) => now().saturating_duration_since(computed) <= ttl,
+            (cached, None) => cached.is_some(),
+            (None, Some(_)) => false,
+        };
+
+        let memo = if fresh {
+            self.outputs.get(&args)
+        } else {
+            let output = (self.function)(&args);
+            self.outputs.push(
+                args,
+                Memo {
+                    output,
+                    computed: now(),
+                },
+            );
+            self.outputs.head_key_value().map(|(_, memo)| memo)
+        };
+        &memo.expect("output is cached").output
+    }
+
+    /// Removes the cached output for `args`, returning it if present. The
+    /// next call with `args` invokes the function.
+    pub fn invalidate<QueryArgs>(&mut self, args: &QueryArgs) -> Option<Output>
+    where
+        QueryArgs: Hash + Eq + ?Sized,
+        Args: Borrow<QueryArgs>,
+    {
+        self.outputs.take(args).map(|(_, memo)| memo.output)
+    }
+
+    /// Returns the number of cached outputs, including outputs that have
+    /// expired but have not been recomputed yet.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns true if no outputs are cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+impl<Args, Output, Function> Debug for Memoized<Args, Output, Function>
+where
+    Args: Debug,
+    Output: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Memoized")
+            .field("outputs", &self.outputs)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
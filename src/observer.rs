@@ -0,0 +1,73 @@
+/// Receives notifications about operations performed on a Least Recently Used
+/// map.
+///
+/// Every function has an empty default implementation, allowing implementors
+/// to only handle the notifications they are interested in. The unit type
+/// `()` implements this trait without handling any notifications, and is the
+/// default observer for all map types in this crate. Because observers are
+/// invoked statically, the unit observer has no runtime cost.
+///
+/// ```rust
+/// use lrumap::{CacheObserver, LruHashMap, LruMap};
+///
+/// #[derive(Default, Debug)]
+/// struct Counters {
+///     hits: usize,
+///     misses: usize,
+///     evictions: usize,
+/// }
+///
+/// impl<Key, Value> CacheObserver<Key, Value> for Counters {
+///     fn on_hit(&mut self, _key: &Key) {
+///         self.hits += 1;
+///     }
+///
+///     fn on_miss(&mut self) {
+///         self.misses += 1;
+///     }
+///
+///     fn on_evict(&mut self, _key: &Key, _value: &Value) {
+///         self.evictions += 1;
+///     }
+/// }
+///
+/// let mut lru = LruHashMap::with_observer(2, Counters::default());
+/// lru.extend([(1, 1), (2, 2), (3, 3)]);
+/// assert_eq!(lru.get(&3), Some(&3));
+/// assert_eq!(lru.get(&1), None);
+///
+/// assert_eq!(lru.observer().hits, 1);
+/// assert_eq!(lru.observer().misses, 1);
+/// assert_eq!(lru.observer().evictions, 1);
+/// ```
+pub trait CacheObserver<Key, Value> {
+    /// Invoked when a lookup that touches the key finds `key`.
+    fn on_hit(&mut self, _key: &Key) {}
+
+    /// Invoked when a lookup that touches the key does not find the key.
+    fn on_miss(&mut self) {}
+
+    /// Invoked after `key` has been inserted as a new entry.
+    fn on_insert(&mut self, _key: &Key) {}
+
+    /// Invoked after the value stored for `key` has been replaced.
+    fn on_replace(&mut self, _key: &Key) {}
+
+    /// Invoked when the entry for `key` is evicted to make room for another
+    /// entry.
+    ///
+    /// Entries that were [marked dirty](crate::EntryRef::mark_dirty) are
+    /// reported to [`Self::on_evict_dirty()`] instead.
+    fn on_evict(&mut self, _key: &Key, _value: &Value) {}
+
+    /// Invoked when the entry for `key` is evicted to make room for another
+    /// entry while it is marked dirty. This allows write-back layers to flush
+    /// the entry before it is dropped.
+    ///
+    /// The default implementation invokes [`Self::on_evict()`].
+    fn on_evict_dirty(&mut self, key: &Key, value: &Value) {
+        self.on_evict(key, value);
+    }
+}
+
+impl<Key, Value> CacheObserver<Key, Value> for () {}
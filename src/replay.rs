@@ -0,0 +1,181 @@
+//! Replaying traces of operations against Least Recently Used maps.
+//!
+//! A trace is a sequence of [`TraceOperation`]s, typically recorded from a
+//! production workload. Replaying the same trace against maps with different
+//! capacities, or against different map implementations, shows how each
+//! choice would have performed for that workload.
+//!
+//! ```rust
+//! use lrumap::replay::{replay, TraceOperation};
+//! use lrumap::{LruHashMap, LruMap};
+//!
+//! // A workload that repeatedly cycles through 8 keys.
+//! let trace = (0..1_000_u32).map(|index| TraceOperation::Get(index % 8, index));
+//!
+//! let small = replay(&mut LruHashMap::new(4), trace.clone());
+//! let large = replay(&mut LruHashMap::new(8), trace);
+//! assert_eq!(small.hits, 0);
+//! assert_eq!(large.hits, 992);
+//! assert!(large.hit_ratio() > small.hit_ratio());
+//! ```
+
+use std::hash::Hash;
+
+use crate::{EntryRef, LruMap, Removed};
+
+/// An operation recorded in a trace.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TraceOperation<Key, Value> {
+    /// Looks up the key using [`LruMap::get()`]. If the key is not present,
+    /// the value is pushed, as a cache would after loading it from the
+    /// underlying data source.
+    Get(Key, Value),
+    /// Pushes the key and value using [`LruMap::push()`].
+    Push(Key, Value),
+    /// Removes the key using [`EntryRef::take()`].
+    Remove(Key),
+}
+
+/// The result of applying a [`TraceOperation`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TraceOutcome {
+    /// The key of [`TraceOperation::Get`] was present.
+    Hit,
+    /// The key of [`TraceOperation::Get`] was not present, and its value was
+    /// pushed, evicting `evicted` entries.
+    Miss {
+        /// The number of entries evicted to make room for the value.
+        evicted: usize,
+    },
+    /// The key of [`TraceOperation::Push`] was not present, and the value
+    /// was inserted, evicting `evicted` entries.
+    Inserted {
+        /// The number of entries evicted to make room for the value.
+        evicted: usize,
+    },
+    /// The key of [`TraceOperation::Push`] was present, and its value was
+    /// replaced.
+    Replaced,
+    /// The key of [`TraceOperation::Remove`] was present, and was removed.
+    Removed,
+    /// The key of [`TraceOperation::Remove`] was not present.
+    NotFound,
+}
+
+/// The totals of the outcomes of a replayed trace.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ReplayStats {
+    /// The number of operations applied.
+    pub operations: usize,
+    /// The number of [`TraceOutcome::Hit`]s.
+    pub hits: usize,
+    /// The number of [`TraceOutcome::Miss`]es.
+    pub misses: usize,
+    /// The number of [`TraceOutcome::Inserted`]s.
+    pub insertions: usize,
+    /// The number of [`TraceOutcome::Replaced`]s.
+    pub replacements: usize,
+    /// The number of [`TraceOutcome::Removed`]s.
+    pub removals: usize,
+    /// The total number of entries evicted.
+    pub evictions: usize,
+}
+
+impl ReplayStats {
+    /// Adds `outcome` to these totals.
+    pub const fn record(&mut self, outcome: TraceOutcome) {
+        self.operations += 1;
+        match outcome {
+            TraceOutcome::Hit => self.hits += 1,
+            TraceOutcome::Miss { evicted } => {
+                self.misses += 1;
+                self.evictions += evicted;
+            }
+            TraceOutcome::Inserted { evicted } => {
+                self.insertions += 1;
+                self.evictions += evicted;
+            }
+            TraceOutcome::Replaced => self.replacements += 1,
+            TraceOutcome::Removed => self.removals += 1,
+            TraceOutcome::NotFound => {}
+        }
+    }
+
+    /// Returns the fraction of lookups that were hits, or 0 if no lookups
+    /// were performed.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // Precision loss is acceptable for a ratio.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Applies `operation` to `map`, returning its outcome.
+///
+/// Evictions are counted by comparing the length of the map before and after
+/// inserting, which includes entries evicted to restore the map's headroom.
+pub fn apply<Map, Key, Value>(map: &mut Map, operation: TraceOperation<Key, Value>) -> TraceOutcome
+where
+    Map: LruMap<Key, Value>,
+    Key: Ord + Hash + Eq,
+{
+    match operation {
+        TraceOperation::Get(key, value) => {
+            if map.get(&key).is_some() {
+                TraceOutcome::Hit
+            } else {
+                TraceOutcome::Miss {
+                    evicted: push(map, key, value).unwrap_or(0),
+                }
+            }
+        }
+        TraceOperation::Push(key, value) => push(map, key, value)
+            .map_or(TraceOutcome::Replaced, |evicted| TraceOutcome::Inserted {
+                evicted,
+            }),
+        TraceOperation::Remove(key) => {
+            if map.entry(&key).map(EntryRef::take).is_some() {
+                TraceOutcome::Removed
+            } else {
+                TraceOutcome::NotFound
+            }
+        }
+    }
+}
+
+/// Pushes `key` and `value`, returning the number of evicted entries, or
+/// `None` if the value for an existing key was replaced.
+fn push<Map, Key, Value>(map: &mut Map, key: Key, value: Value) -> Option<usize>
+where
+    Map: LruMap<Key, Value>,
+    Key: Ord + Hash + Eq,
+{
+    let previous_len = map.len();
+    if matches!(map.push(key, value), Some(Removed::PreviousValue(_))) {
+        None
+    } else {
+        Some((previous_len + 1).saturating_sub(map.len()))
+    }
+}
+
+/// Applies each of `trace`'s operations to `map`, returning the totals of
+/// their outcomes.
+pub fn replay<Map, Key, Value>(
+    map: &mut Map,
+    trace: impl IntoIterator<Item = TraceOperation<Key, Value>>,
+) -> ReplayStats
+where
+    Map: LruMap<Key, Value>,
+    Key: Ord + Hash + Eq,
+{
+    let mut stats = ReplayStats::default();
+    for operation in trace {
+        stats.record(apply(map, operation));
+    }
+    stats
+}
@@ -0,0 +1,219 @@
+use std::borrow::Borrow;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::{LruHashMap, LruMap, LruPeek, Removed};
+
+/// A cache of sessions that is bounded by both the number of sessions and how
+/// long each session may go unused.
+///
+/// When inserting a new session into a full cache, the least recently used
+/// session is evicted. Sessions that have not been used for longer than the
+/// idle timeout are expired when they are looked up, or by
+/// [`Self::expire_idle()`]. Because the least recently used session is also
+/// the session that has been idle the longest, expiring idle sessions only
+/// visits the sessions being expired.
+///
+/// The listener is invoked with each session that is evicted or expired,
+/// along with the reason it was removed. Sessions removed with
+/// [`Self::remove()`] are returned to the caller instead.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use lrumap::{SessionCache, SessionEnd};
+///
+/// let mut ended = Vec::new();
+/// let mut sessions = SessionCache::with_listener(
+///     2,
+///     Duration::from_secs(60 * 30),
+///     |id: u64, _session: String, reason| ended.push((id, reason)),
+/// );
+/// sessions.insert(1, String::from("alice"));
+/// sessions.insert(2, String::from("bob"));
+/// assert_eq!(sessions.get(&1).map(|name| name.as_str()), Some("alice"));
+///
+/// // The cache is full, so the least recently used session is evicted.
+/// sessions.insert(3, String::from("carol"));
+/// drop(sessions);
+/// assert_eq!(ended, [(2, SessionEnd::Evicted)]);
+/// ```
+#[must_use]
+pub struct SessionCache<Id, Session, Listener = fn(Id, Session, SessionEnd)> {
+    sessions: LruHashMap<Id, Idle<Session>>,
+    idle_timeout: Duration,
+    listener: Listener,
+}
+
+/// The reason a session was removed from a [`SessionCache`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SessionEnd {
+    /// The session was the least recently used session when a new session
+    /// was inserted into a full cache.
+    Evicted,
+    /// The session was not used for longer than the idle timeout.
+    Expired,
+}
+
+/// A session and when it was last used.
+#[derive(Debug)]
+struct Idle<Session> {
+    session: Session,
+    last_used: Instant,
+}
+
+impl<Id, Session> SessionCache<Id, Session>
+where
+    Id: Hash + Eq + Clone,
+{
+    /// Creates a new cache that holds up to `capacity` sessions, each of
+    /// which expires after being unused for `idle_timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn new(capacity: usize, idle_timeout: Duration) -> Self {
+        Self::with_listener(capacity, idle_timeout, |_, _, _| {})
+    }
+}
+
+impl<Id, Session, Listener> SessionCache<Id, Session, Listener>
+where
+    Id: Hash + Eq + Clone,
+    Listener: FnMut(Id, Session, SessionEnd),
+{
+    /// Creates a new cache that holds up to `capacity` sessions, each of
+    /// which expires after being unused for `idle_timeout`. `listener` is
+    /// invoked with each session that is evicted or expired.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn with_listener(capacity: usize, idle_timeout: Duration, listener: Listener) -> Self {
+        Self {
+            sessions: LruHashMap::new(capacity),
+            idle_timeout,
+            listener,
+        }
+    }
+
+    /// Returns the duration a session may go unused before it expires.
+    #[must_use]
+    pub const fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Returns the number of sessions in this cache, including sessions that
+    /// have been idle for longer than the idle timeout but have not been
+    /// expired yet.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Returns true if this cache contains no sessions.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Returns the session for `id`, if present and not expired.
+    ///
+    /// This function marks the session as used, making it the most recently
+    /// used session. If the session has been idle for longer than the idle
+    /// timeout, it is expired and `None` is returned.
+    pub fn get<QueryId>(&mut self, id: &QueryId) -> Option<&mut Session>
+    where
+        QueryId: Hash + Eq + ?Sized,
+        Id: Borrow<QueryId>,
+    {
+        self.get_at(id, Instant::now())
+    }
+
+    pub(crate) fn get_at<QueryId>(&mut self, id: &QueryId, now: Instant) -> Option<&mut Session>
+    where
+        QueryId: Hash + Eq + ?Sized,
+        Id: Borrow<QueryId>,
+    {
+        let last_used = self.sessions.get_without_update(id)?.last_used;
+        if now.saturating_duration_since(last_used) > self.idle_timeout {
+            let (id, idle) = self.sessions.take(id)?;
+            (self.listener)(id, idle.session, SessionEnd::Expired);
+            return None;
+        }
+
+        let idle = self.sessions.get_mut(id)?;
+        idle.last_used = now;
+        Some(&mut idle.session)
+    }
+
+    /// Inserts `session` for `id`, marking it as used. Returns the session
+    /// previously stored for `id`, if any.
+    ///
+    /// If this cache is full and does not contain `id`, the least recently
+    /// used session is evicted and passed to the listener.
+    pub fn insert(&mut self, id: Id, session: Session) -> Option<Session> {
+        self.insert_at(id, session, Instant::now())
+    }
+
+    pub(crate) fn insert_at(&mut self, id: Id, session: Session, now: Instant) -> Option<Session> {
+        match self.sessions.push(
+            id,
+            Idle {
+                session,
+                last_used: now,
+            },
+        ) {
+            Some(Removed::PreviousValue(previous)) => Some(previous.session),
+            Some(Removed::Evicted(id, evicted)) => {
+                (self.listener)(id, evicted.session, SessionEnd::Evicted);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Removes the session for `id`, returning it if present. The listener is
+    /// not invoked.
+    pub fn remove<QueryId>(&mut self, id: &QueryId) -> Option<Session>
+    where
+        QueryId: Hash + Eq + ?Sized,
+        Id: Borrow<QueryId>,
+    {
+        self.sessions.take(id).map(|(_, idle)| idle.session)
+    }
+
+    /// Expires every session that has been idle for longer than the idle
+    /// timeout, passing each to the listener. Returns the number of sessions
+    /// expired.
+    pub fn expire_idle(&mut self) -> usize {
+        self.expire_idle_at(Instant::now())
+    }
+
+    pub(crate) fn expire_idle_at(&mut self, now: Instant) -> usize {
+        let mut expired = 0;
+        while let Some(tail) = self.sessions.tail() {
+            if now.saturating_duration_since(tail.peek_value().last_used) <= self.idle_timeout {
+                break;
+            }
+            let (id, idle) = tail.take();
+            (self.listener)(id, idle.session, SessionEnd::Expired);
+            expired += 1;
+        }
+        expired
+    }
+}
+
+impl<Id, Session, Listener> Debug for SessionCache<Id, Session, Listener>
+where
+    Id: Debug,
+    Session: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCache")
+            .field("sessions", &self.sessions)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,523 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::sync::PoisonError;
+
+use crate::lru::validate_capacity;
+use crate::sync::{Arc, AtomicUsize, Mutex, MutexGuard, Ordering};
+use crate::{ConfigError, LruHashMap, LruPeek, OwnedCursor, Removed, SmallLruMap};
+
+/// A Least Recently Used map that can be shared between threads.
+///
+/// Internally, this type uses an [`LruHashMap`] protected by a [`Mutex`].
+/// Cloning this type produces another handle to the same map.
+///
+/// Each thread can create a [`FrontLruMap`] using [`Self::front()`], which
+/// keeps a small, unsynchronized map of recently used entries in front of the
+/// shared map. This avoids acquiring the lock for repeated lookups of the same
+/// keys.
+///
+/// ```rust
+/// use lrumap::SharedLruMap;
+///
+/// let shared = SharedLruMap::new(16);
+/// shared.push(1, "one");
+///
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| {
+///         let mut front = shared.front(4, 8);
+///         assert_eq!(front.get(&1), Some(&"one"));
+///     });
+/// });
+/// ```
+///
+/// # Testing with loom
+///
+/// When compiled with `--cfg loom`, this type and [`FrontLruMap`] use
+/// [loom](https://docs.rs/loom)'s synchronization primitives. This allows
+/// crates that embed these types to include them in their own `loom::model`
+/// tests. [`Self::modifications()`] and [`FrontLruMap::pending_touches()`]
+/// can be used to observe the synchronization state deterministically.
+#[derive(Debug)]
+#[must_use]
+pub struct SharedLruMap<Key, Value> {
+    data: Arc<SharedData<Key, Value>>,
+}
+
+#[derive(Debug)]
+struct SharedData<Key, Value> {
+    map: Mutex<LruHashMap<Key, Value>>,
+    /// Incremented each time the map is modified, allowing [`FrontLruMap`]s
+    /// to detect when their entries may no longer match the shared map.
+    version: AtomicUsize,
+}
+
+impl<Key, Value> Clone for SharedLruMap<Key, Value> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<Key, Value> SharedLruMap<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    /// Creates a new map with the maximum `capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: Arc::new(SharedData {
+                map: Mutex::new(LruHashMap::new(capacity)),
+                version: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Creates a new map with the maximum `capacity`, returning an error
+    /// instead of panicking if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn try_new(capacity: usize) -> Result<Self, ConfigError> {
+        validate_capacity(capacity)?;
+        Ok(Self::new(capacity))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LruHashMap<Key, Value>> {
+        self.data.map.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Marks the map as modified, returning the version prior to the
+    /// modification. This must be called while the map is locked.
+    fn modified(&self) -> usize {
+        self.data.version.fetch_add(1, Ordering::AcqRel)
+    }
+
+    /// Returns the number of times this map has been modified. Each
+    /// modification causes [`FrontLruMap`]s to discard their local entries.
+    #[must_use]
+    pub fn modifications(&self) -> usize {
+        self.data.version.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of keys present in this map.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if this map contains no keys.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Returns a copy of the stored value for `key`, if present.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    #[must_use]
+    pub fn get<QueryKey>(&self, key: &QueryKey) -> Option<Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.lock().get(key).cloned()
+    }
+
+    /// Returns a guard containing a copy of the stored value for `key`, if
+    /// present. The lock is only held while copying the value.
+    ///
+    /// Unlike [`Self::get()`], the key is touched when the guard is dropped
+    /// rather than when the value is read. The guard does not borrow this map,
+    /// which allows holding it across `.await` points, and the touch reflects
+    /// when the value was last in use.
+    ///
+    /// ```rust
+    /// use lrumap::SharedLruMap;
+    ///
+    /// let shared = SharedLruMap::new(2);
+    /// shared.push(1, "one");
+    /// shared.push(2, "two");
+    ///
+    /// let one = shared.read(&1).unwrap();
+    /// assert_eq!(*one, "one");
+    /// drop(one);
+    ///
+    /// // Dropping the guard touched 1, so 2 is evicted.
+    /// shared.push(3, "three");
+    /// assert!(shared.get(&2).is_none());
+    /// ```
+    #[must_use]
+    pub fn read<QueryKey>(&self, key: &QueryKey) -> Option<ValueGuard<Key, Value>>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let (key, value, cursor) = self.lock().entry(key).map(|entry| {
+            (
+                entry.key().clone(),
+                entry.peek_value().clone(),
+                entry.cursor(),
+            )
+        })?;
+        Some(ValueGuard {
+            map: self.clone(),
+            key,
+            value,
+            cursor,
+        })
+    }
+
+    /// Inserts `value` for `key` into this map. If a value is already stored
+    /// for this key, [`Removed::PreviousValue`] is returned with the previously
+    /// stored value. If no value is currently stored and the map is full, the
+    /// least recently used entry will be returned in [`Removed::Evicted`].
+    /// Otherwise, `None` will be returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    pub fn push(&self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
+        let mut map = self.lock();
+        self.modified();
+        map.push(key, value)
+    }
+
+    /// Removes `key` from this map, returning the stored key and value if
+    /// present.
+    pub fn take<QueryKey>(&self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let mut map = self.lock();
+        self.modified();
+        map.take(key)
+    }
+
+    /// Returns a new front cache for this map that keeps up to `capacity`
+    /// entries locally. Touches of entries that are found locally are written
+    /// to this map in batches of `batch_size`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn front(&self, capacity: usize, batch_size: usize) -> FrontLruMap<Key, Value> {
+        FrontLruMap {
+            shared: self.clone(),
+            local: SmallLruMap::new(capacity),
+            capacity,
+            version: self.modifications(),
+            pending_touches: Vec::with_capacity(batch_size),
+            batch_size,
+        }
+    }
+}
+
+impl<Key, Value> SharedLruMap<Key, std::sync::Arc<Value>>
+where
+    Key: Hash + Eq + Clone,
+{
+    /// Returns a clone of the [`Arc`](std::sync::Arc) stored for `key`, if
+    /// present. The lock is only held while incrementing the reference count,
+    /// and the returned value remains usable after the entry is evicted.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use lrumap::SharedLruMap;
+    ///
+    /// let shared = SharedLruMap::new(2);
+    /// shared.push(1, Arc::new(String::from("one")));
+    ///
+    /// let one = shared.get_arc(&1).unwrap();
+    /// std::thread::scope(|scope| {
+    ///     scope.spawn(move || assert_eq!(*one, "one"));
+    /// });
+    /// ```
+    #[must_use]
+    pub fn get_arc<QueryKey>(&self, key: &QueryKey) -> Option<std::sync::Arc<Value>>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.lock().get_arc(key)
+    }
+}
+
+/// A copy of a value read from a [`SharedLruMap`]. Returned from
+/// [`SharedLruMap::read()`].
+///
+/// The entry that was read is touched in the shared map when this guard is
+/// dropped. If the entry has been removed by then, dropping the guard has no
+/// effect, even if the key has since been inserted again.
+#[derive(Debug)]
+#[must_use]
+pub struct ValueGuard<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    map: SharedLruMap<Key, Value>,
+    key: Key,
+    value: Value,
+    cursor: OwnedCursor,
+}
+
+impl<Key, Value> ValueGuard<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    /// Returns the key of this value.
+    pub const fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+impl<Key, Value> Deref for ValueGuard<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    type Target = Value;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<Key, Value> Drop for ValueGuard<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.map.lock().resume(self.cursor) {
+            entry.touch();
+        }
+    }
+}
+
+/// A small Least Recently Used map that sits in front of a [`SharedLruMap`].
+///
+/// Lookups that are found in this map do not acquire the shared map's lock.
+/// Instead, the touched key is recorded and written to the shared map once
+/// the configured number of touches is pending, when the shared map is
+/// locked for another operation, or when [`Self::flush()`] is called. Pending
+/// touches are also flushed when this type is dropped.
+///
+/// Lookups that are not found in this map are retrieved from the shared map
+/// and promoted into this map.
+///
+/// Any modification of the shared map, including through other handles,
+/// causes all front caches to discard their local entries on their next
+/// operation. This ensures values returned by a front cache are never older
+/// than the last modification it has observed.
+#[derive(Debug)]
+#[must_use]
+pub struct FrontLruMap<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    shared: SharedLruMap<Key, Value>,
+    local: SmallLruMap<Key, Value>,
+    capacity: usize,
+    version: usize,
+    pending_touches: Vec<Key>,
+    batch_size: usize,
+}
+
+impl<Key, Value> FrontLruMap<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    /// Returns the shared map this front cache is in front of.
+    pub const fn shared(&self) -> &SharedLruMap<Key, Value> {
+        &self.shared
+    }
+
+    /// Returns the stored value for `key`, if present.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    /// If the key is found locally, the touch is recorded and written to the
+    /// shared map later.
+    pub fn get<QueryKey>(&mut self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let current_version = self.shared.modifications();
+        self.synchronize(current_version);
+
+        if let Some(mut entry) = self.local.entry(key) {
+            entry.touch();
+            self.pending_touches.push(entry.key().clone());
+            if self.pending_touches.len() >= self.batch_size {
+                self.flush();
+            }
+        } else {
+            let mut map = self.shared.lock();
+            Self::write_touches(&mut map, &mut self.pending_touches);
+            let (key, value) = map.entry(key).map(|mut entry| {
+                entry.touch();
+                (entry.key().clone(), entry.peek_value().clone())
+            })?;
+            let current_version = self.shared.modifications();
+            drop(map);
+
+            self.synchronize(current_version);
+            self.local.push(key, value);
+        }
+
+        self.local.get_without_update(key)
+    }
+
+    /// Inserts `value` for `key` into the shared map and this front cache. See
+    /// [`SharedLruMap::push()`] for more information.
+    pub fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
+        let mut map = self.shared.lock();
+        Self::write_touches(&mut map, &mut self.pending_touches);
+        let previous_version = self.shared.modified();
+        let removed = map.push(key.clone(), value.clone());
+        drop(map);
+
+        self.synchronize(previous_version);
+        self.version = previous_version.wrapping_add(1);
+        if let Some(Removed::Evicted(evicted, _)) = &removed {
+            self.local.take(evicted);
+        }
+        self.local.push(key, value);
+
+        removed
+    }
+
+    /// Removes `key` from the shared map and this front cache, returning the
+    /// stored key and value if present.
+    pub fn take<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let mut map = self.shared.lock();
+        Self::write_touches(&mut map, &mut self.pending_touches);
+        let previous_version = self.shared.modified();
+        let removed = map.take(key);
+        drop(map);
+
+        self.synchronize(previous_version);
+        self.version = previous_version.wrapping_add(1);
+        self.local.take(key);
+
+        removed
+    }
+
+    /// Returns the number of touches that have not been written to the shared
+    /// map yet.
+    #[must_use]
+    pub const fn pending_touches(&self) -> usize {
+        self.pending_touches.len()
+    }
+
+    /// Writes all pending touches to the shared map.
+    pub fn flush(&mut self) {
+        if !self.pending_touches.is_empty() {
+            let mut map = self.shared.lock();
+            Self::write_touches(&mut map, &mut self.pending_touches);
+        }
+    }
+
+    fn write_touches(map: &mut LruHashMap<Key, Value>, pending_touches: &mut Vec<Key>) {
+        // Touches are written in the order they happened, leaving the most
+        // recently touched key at the head of the shared map.
+        for key in pending_touches.drain(..) {
+            map.get(&key);
+        }
+    }
+
+    /// Discards all local entries if the shared map has been modified since
+    /// they were retrieved.
+    fn synchronize(&mut self, current_version: usize) {
+        if current_version != self.version {
+            self.local = SmallLruMap::new(self.capacity);
+            self.version = current_version;
+        }
+    }
+}
+
+impl<Key, Value> Drop for FrontLruMap<Key, Value>
+where
+    Key: Hash + Eq + Clone,
+    Value: Clone,
+{
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::thread;
+
+    use super::SharedLruMap;
+    use crate::Removed;
+
+    #[test]
+    fn modifications_invalidate_fronts() {
+        loom::model(|| {
+            let shared = SharedLruMap::new(2);
+            shared.push(1, 1);
+
+            let writer = {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    shared.push(1, 2);
+                })
+            };
+
+            let mut front = shared.front(2, 2);
+            let first = front.get(&1).copied();
+            assert!(matches!(first, Some(1 | 2)));
+            writer.join().unwrap();
+
+            // Once the write has completed, the front must not return the
+            // value it cached before the write.
+            assert_eq!(front.get(&1), Some(&2));
+        });
+    }
+
+    #[test]
+    fn pending_touches_and_evictions() {
+        loom::model(|| {
+            let shared = SharedLruMap::new(2);
+            shared.push(1, 1);
+            shared.push(2, 2);
+
+            let reader = {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mut front = shared.front(2, 2);
+                    front.get(&1);
+                    front.get(&1);
+                    front.pending_touches()
+                })
+            };
+
+            let evicted = shared.push(3, 3);
+            let pending = reader.join().unwrap();
+            assert!(pending <= 1);
+            // Key 1 is only protected from eviction if the reader touched it
+            // before the push.
+            assert!(matches!(
+                evicted,
+                Some(Removed::Evicted(1, 1) | Removed::Evicted(2, 2))
+            ));
+            assert_eq!(shared.len(), 2);
+        });
+    }
+}
@@ -0,0 +1,1964 @@
+use std::borrow::Borrow;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::iter::{Peekable, Rev, Take};
+
+use crate::lru::{
+    batch, evict_headroom, evict_headroom_sparing, push_with_token, reserve_slot, retain,
+    retain_incremental, try_reserve_slot, with_eviction_capture, ConfigError, CorruptionError,
+    CursorIter, Dirty, DuplicatePolicy, EntryCache, EntryId, EntryRef, Expiring, IntoIter, Iter,
+    IterTail, KeyHandle, KeysByStaleness, LruCache, NodeId, Occupancy, OccupiedSlots, OwnedCursor,
+    Removed, RetainProgress, SlotToken, Trim, Unordered, VacancyStats,
+};
+use crate::{CacheObserver, LruMap, LruPeek};
+
+/// A Least Recently Used map with fixed capacity that locates keys by scanning
+/// its entries linearly.
+///
+/// This type is intended for very small capacities, where the cost of hashing
+/// or comparing keys in a tree outweighs comparing every key directly.
+///
+/// When inserting a new key and the map is at-capacity, the least recently used
+/// key will be evicted to make room for the new key.
+///
+/// Because no separate index is maintained, each key is only stored once and
+/// `Key` is only required to implement `Eq`. Lookups take time proportional to
+/// the number of entries, so [`LruHashMap`](crate::LruHashMap) or
+/// [`LruBTreeMap`](crate::LruBTreeMap) should be preferred for capacities
+/// larger than a few dozen entries.
+///
+/// The `Observer` is notified of operations performed on this map. See
+/// [`CacheObserver`] for more information.
+#[derive(Debug)]
+#[must_use]
+pub struct SmallLruMap<Key, Value, Observer = ()> {
+    cache: LruCache<Key, Value>,
+    observer: Observer,
+}
+
+impl<Key, Value> SmallLruMap<Key, Value>
+where
+    Key: Eq,
+{
+    /// Creates a new map with the maximum `capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_observer(capacity, ())
+    }
+}
+
+impl<Key, Value, Observer> SmallLruMap<Key, Value, Observer>
+where
+    Key: Eq,
+    Observer: CacheObserver<Key, Value>,
+{
+    /// Creates a new map with the maximum `capacity` that notifies `observer`
+    /// of operations performed on the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is <= 1 or > `u32::MAX`.
+    pub fn with_observer(capacity: usize, observer: Observer) -> Self {
+        assert!(capacity > 1);
+        assert!(capacity <= usize::try_from(u32::MAX).unwrap());
+        Self {
+            cache: LruCache::new(capacity),
+            observer,
+        }
+    }
+
+    /// Returns a reference to this map's observer.
+    pub const fn observer(&self) -> &Observer {
+        &self.observer
+    }
+
+    /// Returns an exclusive reference to this map's observer.
+    pub const fn observer_mut(&mut self) -> &mut Observer {
+        &mut self.observer
+    }
+
+    /// Returns a map containing the result of `map(value)` for each value in
+    /// this map.
+    ///
+    /// The keys keep their order, staleness, and scores, and the new map
+    /// keeps this map's settings and observer. This allows a derived cache to
+    /// be built without losing the recency of the original cache's keys.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut raw = SmallLruMap::new(3);
+    /// raw.extend([(1, "10"), (2, "20"), (3, "30")]);
+    /// raw.get(&1);
+    ///
+    /// let parsed = raw.map_values(|value| value.parse::<u32>().unwrap());
+    /// assert_eq!(
+    ///     parsed.iter().collect::<Vec<_>>(),
+    ///     [(&1, &10), (&3, &30), (&2, &20)]
+    /// );
+    /// ```
+    pub fn map_values<NewValue>(
+        self,
+        map: impl FnMut(Value) -> NewValue,
+    ) -> SmallLruMap<Key, NewValue, Observer> {
+        SmallLruMap {
+            cache: self.cache.map_values(map),
+            observer: self.observer,
+        }
+    }
+
+    /// Returns the stored value for `key`, if present.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    pub fn get<QueryKey>(&mut self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        if let Some(node) = self.cache.find(key) {
+            let node = self.cache.get(node);
+            self.observer.on_hit(node.key());
+            Some(node.value())
+        } else {
+            self.observer.on_miss();
+            None
+        }
+    }
+
+    /// Returns the stored value for `key`, if present.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    pub fn get_mut<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        if let Some(node) = self.cache.find(key) {
+            let node = self.cache.get_mut(node);
+            self.observer.on_hit(node.key());
+            Some(node.value_mut())
+        } else {
+            self.observer.on_miss();
+            None
+        }
+    }
+
+    /// Returns true if `key` is present in this map.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(String::from("a"), 1);
+    ///
+    /// assert!(lru.contains_key("a"));
+    /// assert!(!lru.contains_key("b"));
+    /// ```
+    #[must_use]
+    pub fn contains_key<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.cache.find(key).is_some()
+    }
+
+    /// Returns the stored value for `key`, if present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    pub fn get_without_update<QueryKey>(&self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.cache
+            .find(key)
+            .map(|node| self.cache.get_without_touch(node).value())
+    }
+
+    /// Returns a mutable reference to the stored value for `key`, if present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// *lru.get_mut_without_update(&1).unwrap() += 10;
+    /// assert_eq!(lru.tail().unwrap().peek_value(), &11);
+    /// ```
+    pub fn get_mut_without_update<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.cache.find(key)?;
+        Some(self.cache.get_mut_without_touch(node).value_mut())
+    }
+
+    /// Returns true if `a` was touched more recently than `b`, or `None` if
+    /// either key is not present.
+    ///
+    /// The keys' last accessed sequences are compared. Neither key is more
+    /// recent than the other if they were last touched in the same
+    /// [batch](Self::batch). This function does not touch either key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2)]);
+    /// assert_eq!(lru.is_more_recent_than(&2, &1), Some(true));
+    ///
+    /// lru.get(&1);
+    /// assert_eq!(lru.is_more_recent_than(&2, &1), Some(false));
+    /// assert_eq!(lru.is_more_recent_than(&2, &3), None);
+    /// ```
+    #[must_use]
+    pub fn is_more_recent_than<QueryKey>(&self, a: &QueryKey, b: &QueryKey) -> Option<bool>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let a = self
+            .cache
+            .get_without_touch(self.cache.find(a)?)
+            .last_accessed();
+        let b = self
+            .cache
+            .get_without_touch(self.cache.find(b)?)
+            .last_accessed();
+        Some(a > b)
+    }
+
+    /// Returns an iterator over the keys and values beginning with `key` in
+    /// order from most recently touched to least recently touched, if `key`
+    /// is present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///
+    /// let colder = lru.iter_from(&2).unwrap().map(|(key, _)| *key);
+    /// assert_eq!(colder.collect::<Vec<_>>(), vec![2, 1]);
+    /// assert!(lru.iter_from(&4).is_none());
+    /// ```
+    pub fn iter_from<QueryKey>(&self, key: &QueryKey) -> Option<Iter<'_, Key, Value>>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.cache
+            .find(key)
+            .map(|node| self.cache.iter_starting_at(node))
+    }
+
+    /// Invokes `update` with the value stored for `key`, if present. Returns
+    /// true if `key` was found.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert!(lru.update(&1, |value| *value += 10));
+    /// assert!(!lru.update(&3, |value| *value += 10));
+    /// assert_eq!(lru.head().unwrap().key(), &1);
+    /// assert_eq!(lru.get_without_update(&1), Some(&11));
+    /// ```
+    pub fn update<QueryKey, Update>(&mut self, key: &QueryKey, update: Update) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+        Update: FnOnce(&mut Value),
+    {
+        self.get_mut(key).map(update).is_some()
+    }
+
+    /// Invokes `update` with the value stored for `key`, if present. Returns
+    /// true if `key` was found.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert!(lru.update_without_touch(&1, |value| *value += 10));
+    /// assert_eq!(lru.head().unwrap().key(), &2);
+    /// assert_eq!(lru.get_without_update(&1), Some(&11));
+    /// ```
+    pub fn update_without_touch<QueryKey, Update>(&mut self, key: &QueryKey, update: Update) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+        Update: FnOnce(&mut Value),
+    {
+        if let Some(node) = self.cache.find(key) {
+            update(self.cache.get_mut_without_touch(node).value_mut());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Invokes `modify` with the key and value stored for each of `keys` that
+    /// is present, returning the keys that were not found.
+    ///
+    /// This function touches each key that is found, making the last found
+    /// key the most recently used key. All touches are grouped into a single
+    /// change, as with [`Self::batch()`].
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///
+    /// let missing = lru.modify_many(&[1, 4, 2], |_, value| *value += 10);
+    /// assert_eq!(missing, [&4]);
+    /// assert_eq!(
+    ///     lru.iter().collect::<Vec<_>>(),
+    ///     [(&2, &12), (&1, &11), (&3, &3)]
+    /// );
+    /// ```
+    pub fn modify_many<'keys, QueryKey, Keys, Modify>(
+        &mut self,
+        keys: Keys,
+        mut modify: Modify,
+    ) -> Vec<&'keys QueryKey>
+    where
+        QueryKey: Eq + ?Sized + 'keys,
+        Key: Borrow<QueryKey>,
+        Keys: IntoIterator<Item = &'keys QueryKey>,
+        Modify: FnMut(&Key, &mut Value),
+    {
+        batch(self, |lru| {
+            let mut missing = Vec::new();
+            for key in keys {
+                if let Some(node) = lru.cache.find(key) {
+                    let node = lru.cache.get_mut(node);
+                    lru.observer.on_hit(node.key());
+                    let (key, value) = node.key_and_value_mut();
+                    modify(key, value);
+                } else {
+                    lru.observer.on_miss();
+                    missing.push(key);
+                }
+            }
+            missing
+        })
+    }
+
+    /// Returns an [`EntryRef`] for `key`, if present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache. The [`EntryRef`] can touch the key, depending on which
+    /// functions are used.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    /// lru.push(3, 3);
+    ///
+    /// let mut entry = lru.entry(&2).unwrap();
+    /// assert_eq!(entry.staleness(), 1);
+    /// assert_eq!(entry.value(), &2);
+    /// assert_eq!(entry.staleness(), 0);
+    ///
+    /// assert_eq!(lru.head().unwrap().key(), &2);
+    /// ```
+    pub fn entry<QueryKey>(&mut self, key: &QueryKey) -> Option<EntryRef<'_, Self, Key, Value>>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.cache.find(key).map(|node| EntryRef::new(self, node))
+    }
+
+    /// Touches `key`, moving it to be the second most recently used key
+    /// rather than the head. Returns true if `key` was found.
+    ///
+    /// This is equivalent to calling [`Self::touch_to_depth()`] with a depth
+    /// of 1.
+    pub fn touch_soft<QueryKey>(&mut self, key: &QueryKey) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.touch_to_depth(key, 1)
+    }
+
+    /// Touches `key`, moving it to be `depth` entries behind the most recently
+    /// used key. Returns true if `key` was found. A `depth` of 0 is equivalent
+    /// to touching the key normally.
+    ///
+    /// If the key is already within `depth` entries of the head, its position
+    /// is preserved. This allows accesses such as periodic background scans
+    /// to keep entries from being evicted without hiding which entries were
+    /// most recently used by other accesses. Moving an entry this way takes
+    /// the staleness of the entry in front of it rather than resetting it.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    ///
+    /// assert!(lru.touch_soft(&1));
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [4, 1, 3, 2]);
+    /// assert!(lru.touch_to_depth(&2, 2));
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [4, 1, 2, 3]);
+    /// assert!(!lru.touch_soft(&5));
+    /// ```
+    pub fn touch_to_depth<QueryKey>(&mut self, key: &QueryKey, depth: usize) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        if let Some(node) = self.cache.find(key) {
+            self.cache.touch_to_depth(node, depth);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Marks the entry for `key` as dirty, returning true if `key` was found.
+    /// See [`EntryRef::mark_dirty()`] for more information.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    pub fn mark_dirty<QueryKey>(&mut self, key: &QueryKey) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        if let Some(node) = self.cache.find(key) {
+            self.cache.get_mut_without_touch(node).set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns an [`EntryRef`] for the key referenced by `handle`, if it is
+    /// still present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// let handle = lru.entry(&1).unwrap().downgrade();
+    ///
+    /// lru.push(2, 2);
+    /// assert_eq!(lru.upgrade(&handle).unwrap().peek_value(), &1);
+    /// lru.push(3, 3);
+    /// assert!(lru.upgrade(&handle).is_none());
+    /// ```
+    pub fn upgrade(&mut self, handle: &KeyHandle<Key>) -> Option<EntryRef<'_, Self, Key, Value>> {
+        let node = self
+            .cache
+            .resolve(handle)
+            .or_else(|| self.cache.find(handle.key()))?;
+        Some(EntryRef::new(self, node))
+    }
+
+    /// Returns an [`EntryRef`] for the entry `cursor` was created for, if it
+    /// is still present.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///
+    /// let cursor = lru.entry(&2).unwrap().cursor();
+    ///
+    /// // The map can be modified while the cursor is held.
+    /// lru.push(4, 4);
+    /// assert_eq!(lru.resume(cursor).unwrap().key(), &2);
+    /// lru.push(5, 5);
+    /// assert!(lru.resume(cursor).is_none());
+    /// ```
+    pub fn resume(&mut self, cursor: OwnedCursor) -> Option<EntryRef<'_, Self, Key, Value>> {
+        let node = self.cache.resume(cursor)?;
+        Some(EntryRef::new(self, node))
+    }
+
+    /// Invokes `operations` with this map, grouping all touches it performs
+    /// into a single change. Returns the result of `operations`.
+    ///
+    /// Each touch normally counts as a change to the map, which increases the
+    /// [staleness](EntryRef::staleness) of every other entry. Grouping the
+    /// touches performed by a single logical operation keeps staleness
+    /// proportional to the number of logical operations. Keys touched within
+    /// the batch are still moved to the front of the lru cache in the order
+    /// they are touched. Calls to this function can be nested.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// lru.batch(|lru| {
+    ///     lru.get(&1);
+    ///     lru.get(&2);
+    ///     lru.get(&3);
+    /// });
+    ///
+    /// assert_eq!(lru.entry(&4).unwrap().staleness(), 1);
+    /// assert_eq!(lru.entry(&1).unwrap().staleness(), 0);
+    /// assert_eq!(lru.head().unwrap().key(), &3);
+    /// ```
+    pub fn batch<Operations, Output>(&mut self, operations: Operations) -> Output
+    where
+        Operations: FnOnce(&mut Self) -> Output,
+    {
+        batch(self, operations)
+    }
+
+    /// Invokes `operations` with this map, returning its result along with a
+    /// copy of every entry evicted while it ran, from first to last evicted.
+    ///
+    /// Entries are captured regardless of which function evicted them,
+    /// including entries evicted to restore
+    /// [headroom](Self::set_headroom) that are not returned to the caller.
+    /// Entries that are removed explicitly, such as through
+    /// [`EntryRef::take()`], are not evictions and are not captured. Calls to
+    /// this function can be nested, and the entries captured by an inner call
+    /// are also returned from the outer call.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// let ((), evicted) = lru.with_eviction_capture(|lru| {
+    ///     lru.extend([(1, 1), (2, 2), (3, 3)]);
+    ///     lru.push(4, 4);
+    /// });
+    ///
+    /// assert_eq!(evicted, vec![(1, 1), (2, 2)]);
+    /// ```
+    pub fn with_eviction_capture<Operations, Output>(
+        &mut self,
+        operations: Operations,
+    ) -> (Output, Vec<(Key, Value)>)
+    where
+        Operations: FnOnce(&mut Self) -> Output,
+        Key: Clone,
+        Value: Clone,
+    {
+        with_eviction_capture(self, operations)
+    }
+
+    /// Removes `key` from this map, returning the stored key and value if
+    /// present.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(String::from("a"), 1);
+    ///
+    /// assert_eq!(lru.take("a"), Some((String::from("a"), 1)));
+    /// assert_eq!(lru.take("a"), None);
+    /// assert!(lru.is_empty());
+    /// ```
+    pub fn take<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.cache.find(key)?;
+        let (removed, ..) = self.cache.remove(node);
+        Some(removed)
+    }
+
+    /// Removes `key` from this map, returning the stored value if present.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(String::from("a"), 1);
+    ///
+    /// assert_eq!(lru.remove("a"), Some(1));
+    /// assert_eq!(lru.remove("a"), None);
+    /// assert!(lru.is_empty());
+    /// ```
+    pub fn remove<QueryKey>(&mut self, key: &QueryKey) -> Option<Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.take(key).map(|(_, value)| value)
+    }
+
+    /// Removes `key` from this map, returning the stored key and value if
+    /// present. This is equivalent to [`Self::take()`], and is useful when
+    /// the stored key owns data that the query key does not.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// let key: Arc<str> = Arc::from("a");
+    /// lru.push(key.clone(), 1);
+    ///
+    /// let (removed, value) = lru.remove_entry("a").unwrap();
+    /// assert!(Arc::ptr_eq(&removed, &key));
+    /// assert_eq!(value, 1);
+    /// assert_eq!(lru.remove_entry("a"), None);
+    /// ```
+    pub fn remove_entry<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.take(key)
+    }
+
+    /// Removes the least recently used entry from this map, returning its key
+    /// and value if the map is not empty.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// assert_eq!(lru.pop_least_recent(), Some((2, 2)));
+    /// assert_eq!(lru.pop_least_recent(), Some((3, 3)));
+    /// assert_eq!(lru.pop_least_recent(), Some((1, 1)));
+    /// assert_eq!(lru.pop_least_recent(), None);
+    /// ```
+    pub fn pop_least_recent(&mut self) -> Option<(Key, Value)> {
+        let tail = self.cache.tail()?;
+        let (removed, ..) = self.remove_node(tail);
+        Some(removed)
+    }
+
+    /// Removes the most recently used entry from this map, returning its key
+    /// and value if the map is not empty.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// assert_eq!(lru.pop_most_recent(), Some((1, 1)));
+    /// assert_eq!(lru.pop_most_recent(), Some((3, 3)));
+    /// assert_eq!(lru.pop_most_recent(), Some((2, 2)));
+    /// assert_eq!(lru.pop_most_recent(), None);
+    /// ```
+    pub fn pop_most_recent(&mut self) -> Option<(Key, Value)> {
+        let head = self.cache.head()?;
+        let (removed, ..) = self.remove_node(head);
+        Some(removed)
+    }
+
+    /// Removes every entry from this map. The storage allocated for the
+    /// entries is kept, allowing the map to be refilled without allocating.
+    ///
+    /// The observer is not notified of the removed entries.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.clear();
+    /// assert!(lru.is_empty());
+    /// assert!(lru.get(&1).is_none());
+    ///
+    /// lru.push(4, 4);
+    /// assert_eq!(lru.vacancy_stats().vacant_slots, 2);
+    /// ```
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Inserts `value` for `key` into this map. If a value is already stored
+    /// for this key, [`Removed::PreviousValue`] is returned with the previously
+    /// stored value. If no value is currently stored and the map is full, the
+    /// least recently used entry will be returned in [`Removed::Evicted`].
+    /// Otherwise, `None` will be returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, Removed, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    /// lru.push(3, 3);
+    ///
+    /// // The cache is now full. The next push will evict an entry.
+    /// let removed = lru.push(4, 4);
+    /// assert_eq!(removed, Some(Removed::Evicted(1, 1)));
+    ///
+    /// // This leaves the cache with 4 as the most recent key, and 2 as the
+    /// // least recent key.
+    /// assert_eq!(lru.head().unwrap().key(), &4);
+    /// assert_eq!(lru.tail().unwrap().key(), &2);
+    /// ```
+    pub fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
+        if let Some(node) = self.cache.find(&key) {
+            // Swap the value out.
+            let node = self.cache.get_mut(node);
+            let value = node.replace_value(value);
+            self.observer.on_replace(node.key());
+
+            return Some(Removed::PreviousValue(value));
+        }
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, result) = self.cache.push(key, value);
+        if let Some(Removed::Evicted(key, value)) = &result {
+            self.notify_evicted(key, value, dirty);
+            evict_headroom(self);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        result
+    }
+
+    /// Inserts `value` for `key` into this map if `key` is not already
+    /// present. If the map is full, the least recently used entry will be
+    /// returned in [`Removed::Evicted`]. If `key` is already present, the map
+    /// is not modified and `key` and `value` are returned as an error.
+    ///
+    /// Unlike [`Self::push()`], this function never replaces an existing
+    /// value. This function touches the key, making it the most recently used
+    /// key.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, Removed};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// assert_eq!(lru.insert_new(1, 1), Ok(None));
+    /// assert_eq!(lru.insert_new(1, 11), Err((1, 11)));
+    /// assert_eq!(lru.insert_new(2, 2), Ok(None));
+    /// assert_eq!(lru.insert_new(3, 3), Ok(Some(Removed::Evicted(1, 1))));
+    /// ```
+    pub fn insert_new(
+        &mut self,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Removed<Key, Value>>, (Key, Value)> {
+        if self.cache.find(&key).is_some() {
+            return Err((key, value));
+        }
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, result) = self.cache.push(key, value);
+        if let Some(Removed::Evicted(key, value)) = &result {
+            self.notify_evicted(key, value, dirty);
+            evict_headroom(self);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        Ok(result)
+    }
+
+    /// Inserts `value` for `key` into this map, subject to the rate set with
+    /// [`Self::set_insertion_sampling()`]. If `key` is already present, its
+    /// value is replaced as with [`Self::push()`]. Otherwise, if `key` is not
+    /// admitted, the map is not modified and `key` and `value` are returned
+    /// as an error.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, Removed, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.set_insertion_sampling(2);
+    /// assert_eq!(lru.push_sampled(1, 1), Ok(None));
+    /// assert_eq!(lru.push_sampled(2, 2), Err((2, 2)));
+    /// assert_eq!(lru.push_sampled(1, 11), Ok(Some(Removed::PreviousValue(1))));
+    /// assert_eq!(lru.push_sampled(2, 2), Ok(None));
+    /// ```
+    pub fn push_sampled(
+        &mut self,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Removed<Key, Value>>, (Key, Value)> {
+        if self.cache.find(&key).is_none() && !self.cache.admit() {
+            return Err((key, value));
+        }
+        Ok(self.push(key, value))
+    }
+
+    /// Replaces the value stored for `key` with `new_value`, returning the
+    /// previous value. If `key` is not present, the map is not modified,
+    /// `new_value` is dropped, and `None` is returned.
+    ///
+    /// Unlike [`Self::push()`], this function never inserts a new entry.
+    /// This function touches the key, making it the most recently used key.
+    /// Use [`Self::swap_value()`] to replace the value without touching the
+    /// key.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert_eq!(lru.replace_existing(&1, 11), Some(1));
+    /// assert_eq!(lru.replace_existing(&3, 33), None);
+    /// assert_eq!(lru.head().unwrap().peek_value(), &11);
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    pub fn replace_existing<QueryKey>(&mut self, key: &QueryKey, new_value: Value) -> Option<Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.cache.find(key)?;
+        let node = self.cache.get_mut(node);
+        let value = node.replace_value(new_value);
+        self.observer.on_replace(node.key());
+        Some(value)
+    }
+
+    /// Updates the value stored for `key` using `update` if present, otherwise
+    /// inserts the value returned by `insert`. If inserting a new entry
+    /// requires evicting the least recently used entry, the evicted entry is
+    /// returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// assert_eq!(lru.upsert(1, |count| *count += 1, || 1), None);
+    /// assert_eq!(lru.upsert(1, |count| *count += 1, || 1), None);
+    /// assert_eq!(lru.get_without_update(&1), Some(&2));
+    ///
+    /// lru.push(2, 1);
+    /// assert_eq!(lru.upsert(3, |count| *count += 1, || 1), Some((1, 2)));
+    /// ```
+    pub fn upsert<Update, Insert>(
+        &mut self,
+        key: Key,
+        update: Update,
+        insert: Insert,
+    ) -> Option<(Key, Value)>
+    where
+        Update: FnOnce(&mut Value),
+        Insert: FnOnce() -> Value,
+    {
+        if let Some(node) = self.cache.find(&key) {
+            let node = self.cache.get_mut(node);
+            self.observer.on_hit(node.key());
+            update(node.value_mut());
+
+            return None;
+        }
+
+        self.observer.on_miss();
+        let dirty = self.cache.evictee_is_dirty();
+        let (node, result) = self.cache.push(key, insert());
+
+        let evicted = if let Some(Removed::Evicted(key, value)) = result {
+            self.notify_evicted(&key, &value, dirty);
+            evict_headroom(self);
+            Some((key, value))
+        } else {
+            None
+        };
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        evicted
+    }
+
+    /// Inserts `value` for `key` into this map. If a value is already stored
+    /// for this key, `merge` is called to combine `value` into the stored
+    /// value. If no value is currently stored and the map is full, the least
+    /// recently used entry is evicted and returned.
+    ///
+    /// This function touches the key, making it the most recently used key.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// let append = |stored: &mut Vec<u32>, new: Vec<u32>| stored.extend(new);
+    /// assert_eq!(lru.push_or_merge(1, vec![1], append), None);
+    /// assert_eq!(lru.push_or_merge(1, vec![2], append), None);
+    /// assert_eq!(lru.get_without_update(&1), Some(&vec![1, 2]));
+    ///
+    /// lru.push(2, vec![]);
+    /// assert_eq!(lru.push_or_merge(3, vec![3], append), Some((1, vec![1, 2])));
+    /// ```
+    pub fn push_or_merge<Merge>(
+        &mut self,
+        key: Key,
+        value: Value,
+        merge: Merge,
+    ) -> Option<(Key, Value)>
+    where
+        Merge: FnOnce(&mut Value, Value),
+    {
+        if let Some(node) = self.cache.find(&key) {
+            let node = self.cache.get_mut(node);
+            merge(node.value_mut(), value);
+            self.observer.on_replace(node.key());
+
+            return None;
+        }
+
+        match self.push(key, value) {
+            Some(Removed::Evicted(key, value)) => Some((key, value)),
+            _ => None,
+        }
+    }
+
+    /// Reserves a slot for a new entry, returning a token that allows pushing
+    /// a new key without evicting an entry. If pushing a new key would evict
+    /// an entry, the least recently used entry is evicted now and returned.
+    ///
+    /// This allows flushing an evicted entry before constructing an expensive
+    /// value. Until the token is passed to [`Self::push_with_token()`] or
+    /// [`Self::release_slot()`], or dropped, the reserved slot is treated as
+    /// occupied.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.extend([(1, 1), (2, 2)]);
+    ///
+    /// let (token, evicted) = lru.reserve_slot();
+    /// assert_eq!(evicted, Some((1, 1)));
+    /// assert_eq!(lru.push_with_token(token, 3, 3), Ok(None));
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if reserving a slot would leave no unreserved slots.
+    pub fn reserve_slot(&mut self) -> (SlotToken, Option<(Key, Value)>) {
+        reserve_slot(self)
+    }
+
+    /// Reserves a slot for a later insert, returning an error instead of
+    /// panicking if reserving a slot would leave no unreserved slots. See
+    /// [`Self::reserve_slot()`] for more information.
+    pub fn try_reserve_slot(&mut self) -> Result<(SlotToken, Option<(Key, Value)>), ConfigError> {
+        try_reserve_slot(self)
+    }
+
+    /// Inserts `value` for `key` into the slot reserved by `token`, releasing
+    /// the reservation. If a value is already stored for this key, the value
+    /// is replaced and the previous value is returned.
+    ///
+    /// This function never evicts an entry, and it touches the key, making it
+    /// the most recently used key.
+    ///
+    /// # Errors
+    ///
+    /// If `token` was returned from `reserve_slot()` on another map, this map
+    /// is not modified and `key` and `value` are returned. The token's slot is
+    /// released in the map that reserved it.
+    pub fn push_with_token(
+        &mut self,
+        token: SlotToken,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Value>, (Key, Value)> {
+        push_with_token(self, token, key, value, Self::push)
+    }
+
+    /// Releases the slot reserved by `token` without inserting an entry. This
+    /// is equivalent to dropping `token`.
+    pub fn release_slot(&mut self, token: SlotToken) {
+        debug_assert!(
+            self.cache.owns(&token),
+            "token was not reserved by this map"
+        );
+        drop(token);
+    }
+
+    /// Replaces the value stored for `key` with `new_value` if `predicate`
+    /// returns true for the currently stored value. Returns `Ok(Some(..))`
+    /// with the previous value if it was replaced, or `Err(new_value)` if
+    /// `predicate` returned false.
+    ///
+    /// If `key` is not present, `new_value` is inserted and `Ok(None)` is
+    /// returned. If the map is full, inserting will evict the least recently
+    /// used entry. The evicted entry is reported to this map's observer and
+    /// dropped. [`Self::would_evict()`] can be used to check whether an
+    /// eviction would happen beforehand.
+    ///
+    /// If the value is replaced or inserted, this function touches the key,
+    /// making it the most recently used key. Otherwise, the key's position is
+    /// preserved.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// let newer = |current: &(u32, &str)| current.0 < 2;
+    /// assert_eq!(lru.replace_if(1, (1, "one"), newer), Ok(None));
+    /// assert_eq!(lru.replace_if(1, (2, "two"), newer), Ok(Some((1, "one"))));
+    /// assert_eq!(lru.replace_if(1, (3, "three"), newer), Err((3, "three")));
+    /// assert_eq!(lru.get_without_update(&1), Some(&(2, "two")));
+    /// ```
+    pub fn replace_if<Predicate>(
+        &mut self,
+        key: Key,
+        new_value: Value,
+        predicate: Predicate,
+    ) -> Result<Option<Value>, Value>
+    where
+        Predicate: FnOnce(&Value) -> bool,
+    {
+        if let Some(node_ref) = self.cache.find(&key) {
+            if !predicate(self.cache.get_without_touch(node_ref).value()) {
+                return Err(new_value);
+            }
+
+            let node = self.cache.get_mut(node_ref);
+            let value = node.replace_value(new_value);
+            self.observer.on_replace(node.key());
+
+            return Ok(Some(value));
+        }
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, result) = self.cache.push(key, new_value);
+        if let Some(Removed::Evicted(key, value)) = &result {
+            self.notify_evicted(key, value, dirty);
+            evict_headroom(self);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+
+        Ok(None)
+    }
+
+    /// Replaces the value stored for `key` with `new_value`, returning the
+    /// previous value. If `key` is not present, `new_value` is dropped and
+    /// `None` is returned.
+    ///
+    /// This function does not touch the key, preserving its current position in
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert_eq!(lru.swap_value(&1, 11), Some(1));
+    /// assert_eq!(lru.swap_value(&3, 33), None);
+    /// assert_eq!(lru.tail().unwrap().peek_value(), &11);
+    /// ```
+    pub fn swap_value<QueryKey>(&mut self, key: &QueryKey, new_value: Value) -> Option<Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.cache.find(key)?;
+        let node = self.cache.get_mut_without_touch(node);
+        let value = node.replace_value(new_value);
+        self.observer.on_replace(node.key());
+        Some(value)
+    }
+
+    /// Replaces the value stored for `key` with `new_value` and restarts its
+    /// [age since insertion](EntryRef::age_since_insert), returning the
+    /// previous value. If `key` is not present, `new_value` is dropped and
+    /// `None` is returned.
+    ///
+    /// Like [`Self::swap_value()`], this function does not touch the key. A
+    /// refresh-ahead loop can refresh the entries returned from
+    /// [`LruPeek::iter_expiring_within()`] without making them more recently
+    /// used than the entries that were requested.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// lru.push(2, 2);
+    ///
+    /// assert_eq!(lru.refresh(&1, 11), Some(1));
+    /// assert_eq!(lru.refresh(&3, 33), None);
+    /// let refreshed = lru.tail().unwrap();
+    /// assert_eq!(refreshed.peek_value(), &11);
+    /// assert_eq!(refreshed.age_since_insert(), 0);
+    /// assert_eq!(refreshed.staleness(), 1);
+    /// ```
+    pub fn refresh<QueryKey>(&mut self, key: &QueryKey, new_value: Value) -> Option<Value>
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        let node = self.cache.find(key)?;
+        let sequence = self.cache.sequence();
+        let node = self.cache.get_mut_without_touch(node);
+        let value = node.refresh_value(new_value, sequence);
+        self.observer.on_replace(node.key());
+        Some(value)
+    }
+
+    /// Pushes all items from `iterator` into this map. If there are more
+    /// entries in the iterator than capacity remaining, keys will be evicted as
+    /// needed.
+    ///
+    /// This function is equivalent to a for loop calling [`Self::push()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    ///
+    /// assert_eq!(lru.head().unwrap().key(), &4);
+    /// assert_eq!(lru.tail().unwrap().key(), &2);
+    /// ```
+    pub fn extend<IntoIter: IntoIterator<Item = (Key, Value)>>(&mut self, iterator: IntoIter) {
+        for (key, value) in iterator {
+            self.push(key, value);
+        }
+    }
+
+    /// Pushes all items from `iterator` into this map, handling keys that are
+    /// already present according to `on_duplicate`. New keys are pushed, and
+    /// keys will be evicted as needed.
+    ///
+    /// This allows loading a snapshot into a live map without overwriting
+    /// values that have been updated since the snapshot was taken.
+    ///
+    /// ```rust
+    /// use lrumap::{DuplicatePolicy, SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.push(1, 10);
+    ///
+    /// lru.extend_with([(1, 1), (2, 2)], DuplicatePolicy::Ignore);
+    /// assert_eq!(lru.get_without_update(&1), Some(&10));
+    ///
+    /// lru.extend_with([(1, 5), (3, 3)], DuplicatePolicy::Merge(|stored, new| *stored += new));
+    /// assert_eq!(lru.get_without_update(&1), Some(&15));
+    /// assert_eq!(lru.len(), 3);
+    /// ```
+    pub fn extend_with<IntoIter: IntoIterator<Item = (Key, Value)>>(
+        &mut self,
+        iterator: IntoIter,
+        on_duplicate: DuplicatePolicy<Value>,
+    ) {
+        for (key, value) in iterator {
+            match on_duplicate {
+                DuplicatePolicy::Merge(merge) => {
+                    self.push_or_merge(key, value, merge);
+                }
+                DuplicatePolicy::Ignore if self.cache.find(&key).is_some() => {}
+                DuplicatePolicy::Replace | DuplicatePolicy::Ignore => {
+                    self.push(key, value);
+                }
+            }
+        }
+    }
+
+    /// Pushes items from `iterator` into this map until pushing the next item
+    /// would require evicting an entry. Returns the remaining items, including
+    /// the item that was not pushed.
+    ///
+    /// Keys that are already present are always pushed, because replacing a
+    /// value never requires an eviction. When evictions are deferred, new keys
+    /// are only pushed while the map is below its capacity.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.push(1, 1);
+    /// let mut remaining = lru.extend_until_full([(2, 2), (1, 11), (3, 3), (4, 4)]);
+    ///
+    /// assert_eq!(remaining.next(), Some((4, 4)));
+    /// assert_eq!(lru.len(), 3);
+    /// assert_eq!(lru.get_without_update(&1), Some(&11));
+    /// ```
+    pub fn extend_until_full<IntoIter: IntoIterator<Item = (Key, Value)>>(
+        &mut self,
+        iterator: IntoIter,
+    ) -> Peekable<IntoIter::IntoIter> {
+        let mut iterator = iterator.into_iter().peekable();
+        while let Some((key, value)) =
+            iterator.next_if(|(key, _)| !self.cache.is_full() || self.cache.find(key).is_some())
+        {
+            self.push(key, value);
+        }
+        iterator
+    }
+
+    /// Inserts a previously evicted entry back into this map as the least
+    /// recently used entry. This is useful when an eviction turns out to be
+    /// premature, such as when persisting an evicted entry fails.
+    ///
+    /// If `removed` is [`Removed::Evicted`] and its key is not currently
+    /// present, the entry is inserted at the least recently used end of this
+    /// map. If the map is full, the current least recently used entry is
+    /// evicted to make room and returned in `Ok(Some(..))`. The configured
+    /// [headroom](Self::set_headroom) is then evicted, as when pushing, from
+    /// the entries more recently used than the reinserted entry.
+    ///
+    /// [`Removed::PreviousValue`] does not contain a key and cannot be
+    /// reinserted. It is returned in `Err` along with evicted entries whose
+    /// key has been pushed again since they were evicted.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// let removed = lru.push(4, 4).unwrap();
+    ///
+    /// // Reinserting evicts the current tail.
+    /// assert_eq!(lru.reinsert(removed), Ok(Some((2, 2))));
+    /// assert_eq!(lru.tail().unwrap().key(), &1);
+    /// ```
+    pub fn reinsert(
+        &mut self,
+        removed: Removed<Key, Value>,
+    ) -> Result<Option<(Key, Value)>, Removed<Key, Value>> {
+        let (key, value) = match removed {
+            Removed::Evicted(key, value) if self.cache.find(&key).is_none() => (key, value),
+            other => return Err(other),
+        };
+
+        let dirty = self.cache.evictee_is_dirty();
+
+        let (node, evicted) = self.cache.push_back(key, value);
+        if let Some((evicted_key, evicted_value)) = &evicted {
+            self.notify_evicted(evicted_key, evicted_value, dirty);
+        }
+        self.observer
+            .on_insert(self.cache.get_without_touch(node).key());
+        if evicted.is_some() {
+            evict_headroom_sparing(self, node);
+        }
+
+        Ok(evicted)
+    }
+
+    /// Returns the most recently used key, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    #[must_use]
+    pub fn head_key(&self) -> Option<&Key> {
+        self.head_key_value().map(|(key, _)| key)
+    }
+
+    /// Returns the least recently used key, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// assert_eq!(lru.head_key(), Some(&3));
+    /// assert_eq!(lru.tail_key(), Some(&1));
+    /// assert_eq!(lru.tail_key_value(), Some((&1, &1)));
+    /// ```
+    #[must_use]
+    pub fn tail_key(&self) -> Option<&Key> {
+        self.tail_key_value().map(|(key, _)| key)
+    }
+
+    /// Returns the most recently used key and value, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    #[must_use]
+    pub fn head_key_value(&self) -> Option<(&Key, &Value)> {
+        self.cache.head().map(|node| {
+            let node = self.cache.get_without_touch(node);
+            (node.key(), node.value())
+        })
+    }
+
+    /// Returns the least recently used key and value, if any.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    #[must_use]
+    pub fn tail_key_value(&self) -> Option<(&Key, &Value)> {
+        self.cache.tail().map(|node| {
+            let node = self.cache.get_without_touch(node);
+            (node.key(), node.value())
+        })
+    }
+
+    /// Returns the entry that will be evicted when the next new key is pushed,
+    /// or `None` if there is room for another key.
+    ///
+    /// This function does not touch any keys, preserving the current order of
+    /// the lru cache.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// assert_eq!(lru.peek_evictee(), None);
+    /// lru.push(2, 2);
+    /// assert_eq!(lru.peek_evictee(), Some((&1, &1)));
+    /// ```
+    #[must_use]
+    pub fn peek_evictee(&self) -> Option<(&Key, &Value)> {
+        self.cache
+            .peek_evictee()
+            .map(|node| (node.key(), node.value()))
+    }
+
+    /// Returns true if pushing `key` into this map would cause an entry to be
+    /// evicted. Pushing a key that is already present never evicts an entry.
+    /// The entry that would be evicted can be inspected using
+    /// [`Self::peek_evictee()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.push(1, 1);
+    /// assert!(!lru.would_evict(&2));
+    /// lru.push(2, 2);
+    /// assert!(!lru.would_evict(&1));
+    /// assert!(lru.would_evict(&3));
+    /// ```
+    #[must_use]
+    pub fn would_evict<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Eq + ?Sized,
+        Key: Borrow<QueryKey>,
+    {
+        self.cache.push_evicts() && self.cache.find(key).is_none()
+    }
+
+    /// Enables or disables deferred eviction.
+    ///
+    /// While evictions are deferred, pushing a new key into a full map will
+    /// grow the map beyond its capacity instead of evicting the least recently
+    /// used entry. The entries beyond capacity remain accessible until
+    /// [`Self::reclaim()`] is called, which evicts the least recently used
+    /// entries until the map is back within its capacity.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(2);
+    /// lru.set_deferred_eviction(true);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// assert_eq!(lru.len(), 4);
+    ///
+    /// let evicted = lru.reclaim().collect::<Vec<_>>();
+    /// assert_eq!(evicted, vec![(1, 1), (2, 2)]);
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    pub const fn set_deferred_eviction(&mut self, deferred: bool) {
+        self.cache.set_deferred_eviction(deferred);
+    }
+
+    /// Returns true if evictions are currently being deferred. See
+    /// [`Self::set_deferred_eviction()`] for more information.
+    #[must_use]
+    pub const fn deferred_eviction(&self) -> bool {
+        self.cache.deferred_eviction()
+    }
+
+    /// Sets the rate at which [`Self::push_sampled()`] admits new keys. One
+    /// in every `one_in` new keys is inserted, and the others are counted in
+    /// [`Self::rejected_insertions()`] and returned to the caller. Keys that
+    /// are already present are always updated.
+    ///
+    /// Sampling protects the map from insert storms, such as a scan over many
+    /// keys that will never be requested again, which would otherwise evict
+    /// every entry. Keys that are pushed repeatedly are eventually admitted.
+    /// The rate can be changed at any time, and a rate of 1, the default,
+    /// admits every new key.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(4);
+    /// lru.set_insertion_sampling(3);
+    /// for key in 0..9 {
+    ///     let _ = lru.push_sampled(key, key);
+    /// }
+    /// assert_eq!(lru.len(), 3);
+    /// assert_eq!(lru.rejected_insertions(), 6);
+    ///
+    /// lru.set_insertion_sampling(1);
+    /// assert_eq!(lru.push_sampled(9, 9), Ok(None));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `one_in` is 0.
+    pub const fn set_insertion_sampling(&mut self, one_in: usize) {
+        self.cache.set_insertion_sampling(one_in);
+    }
+
+    /// Sets the rate at which [`Self::push_sampled()`] admits new keys,
+    /// returning an error instead of panicking if `one_in` is 0. See
+    /// [`Self::set_insertion_sampling()`] for more information.
+    ///
+    /// ```rust
+    /// use lrumap::{ConfigError, LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::<u32, u32>::new(4);
+    /// assert_eq!(lru.try_set_insertion_sampling(2), Ok(()));
+    /// assert_eq!(
+    ///     lru.try_set_insertion_sampling(0),
+    ///     Err(ConfigError::InvalidSamplingRate)
+    /// );
+    /// assert_eq!(lru.insertion_sampling(), 2);
+    /// ```
+    pub const fn try_set_insertion_sampling(&mut self, one_in: usize) -> Result<(), ConfigError> {
+        self.cache.try_set_insertion_sampling(one_in)
+    }
+
+    /// Returns the rate at which [`Self::push_sampled()`] admits new keys. See
+    /// [`Self::set_insertion_sampling()`] for more information.
+    #[must_use]
+    pub const fn insertion_sampling(&self) -> usize {
+        self.cache.insertion_sampling()
+    }
+
+    /// Returns the number of new keys that [`Self::push_sampled()`] has not
+    /// inserted.
+    #[must_use]
+    pub const fn rejected_insertions(&self) -> u64 {
+        self.cache.rejected_insertions()
+    }
+
+    /// Sets the number of entries to keep available when evicting.
+    ///
+    /// When pushing a new key into a full map, the least recently used entry
+    /// is evicted and returned as usual. Additional entries are then evicted
+    /// until the map contains no more than `capacity - headroom` entries.
+    /// The evicted entries are reported to the observer and dropped. This
+    /// allows evictions to happen in batches rather than on every push into
+    /// a full map.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek, Removed};
+    ///
+    /// let mut lru = SmallLruMap::new(4);
+    /// lru.set_headroom(2);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// assert_eq!(lru.len(), 4);
+    ///
+    /// assert_eq!(lru.push(5, 5), Some(Removed::Evicted(1, 1)));
+    /// assert_eq!(lru.len(), 2);
+    /// assert_eq!(lru.push(6, 6), None);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `headroom` is not less than this map's capacity.
+    pub const fn set_headroom(&mut self, headroom: usize) {
+        self.cache.set_headroom(headroom);
+    }
+
+    /// Sets the number of entries to evict in addition to the least recently
+    /// used entry, returning an error instead of panicking if `headroom` is
+    /// not less than this map's capacity. See [`Self::set_headroom()`] for
+    /// more information.
+    ///
+    /// ```rust
+    /// use lrumap::{ConfigError, LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::<u32, u32>::new(4);
+    /// assert_eq!(lru.try_set_headroom(3), Ok(()));
+    /// assert_eq!(
+    ///     lru.try_set_headroom(4),
+    ///     Err(ConfigError::InvalidHeadroom {
+    ///         headroom: 4,
+    ///         capacity: 4
+    ///     })
+    /// );
+    /// assert_eq!(lru.headroom(), 3);
+    /// ```
+    pub const fn try_set_headroom(&mut self, headroom: usize) -> Result<(), ConfigError> {
+        self.cache.try_set_headroom(headroom)
+    }
+
+    /// Returns the number of entries kept available when evicting. See
+    /// [`Self::set_headroom()`] for more information.
+    #[must_use]
+    pub const fn headroom(&self) -> usize {
+        self.cache.headroom()
+    }
+
+    /// Returns an iterator that evicts the least recently used entries until
+    /// this map is within its capacity. This is only needed when evictions
+    /// have been deferred using [`Self::set_deferred_eviction()`].
+    ///
+    /// Entries are evicted as the iterator is advanced. If the iterator is
+    /// dropped before being exhausted, the remaining entries that exceed the
+    /// capacity will be evicted and dropped.
+    pub const fn reclaim(&mut self) -> Trim<'_, Self, Key, Value> {
+        let capacity = self.cache.capacity();
+        Trim::evicting(self, capacity)
+    }
+
+    /// Returns an iterator that removes the least recently used entries until
+    /// this map contains no more than `len` entries, keeping only the `len`
+    /// most recently used entries.
+    ///
+    /// Entries are removed as the iterator is advanced. If the iterator is
+    /// dropped before being exhausted, the remaining entries beyond `len` will
+    /// be removed and dropped. Removed entries are not reported to the
+    /// observer as evictions.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    /// lru.get(&1);
+    ///
+    /// let removed = lru.truncate(2).collect::<Vec<_>>();
+    /// assert_eq!(removed, vec![(2, 2), (3, 3)]);
+    /// assert_eq!(lru.len(), 2);
+    /// ```
+    pub const fn truncate(&mut self, len: usize) -> Trim<'_, Self, Key, Value> {
+        Trim::removing(self, len)
+    }
+
+    /// Returns a cursor over the entries of this map, from most recently used
+    /// to least recently used. The current entry can be removed or touched
+    /// without ending the iteration.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek};
+    ///
+    /// let mut lru = SmallLruMap::new(4);
+    /// lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    ///
+    /// let mut cursor = lru.cursor_iter();
+    /// while let Some((key, _)) = cursor.advance() {
+    ///     if key % 2 == 0 {
+    ///         cursor.remove_current();
+    ///     } else if *key == 1 {
+    ///         cursor.touch_current();
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(lru.iter().collect::<Vec<_>>(), [(&1, &1), (&3, &3)]);
+    /// ```
+    pub fn cursor_iter(&mut self) -> CursorIter<'_, Self, Key, Value> {
+        CursorIter::new(self)
+    }
+
+    /// Removes each entry that `keep` returns false for, visiting every entry
+    /// once from most recently used to least recently used. `keep` can modify
+    /// the values of the entries it keeps. The order of the remaining entries
+    /// is unchanged.
+    ///
+    /// Removed entries are not reported to the observer as evictions. To
+    /// spread the work across multiple calls, use
+    /// [`Self::retain_incremental()`].
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, LruPeek, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(8);
+    /// lru.extend((0..8).map(|key| (key, key)));
+    ///
+    /// lru.retain(|key, _| key % 2 == 0);
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [6, 4, 2, 0]);
+    /// assert!(lru.get(&3).is_none());
+    /// ```
+    pub fn retain<Keep>(&mut self, keep: Keep)
+    where
+        Keep: FnMut(&Key, &mut Value) -> bool,
+    {
+        retain(self, keep);
+    }
+
+    /// Visits up to `budget` entries, removing each entry that `keep` returns
+    /// false for. Returns the progress to pass to the next call, allowing a
+    /// sweep over a large map to be spread across multiple calls.
+    ///
+    /// Entries are visited in the order they are stored rather than by how
+    /// recently they were used. The map can be modified between calls.
+    /// Touching or removing entries does not change where the sweep resumes,
+    /// so each entry is visited at most once and the sweep always finishes.
+    /// Entries inserted between calls may not be visited by the current
+    /// sweep.
+    ///
+    /// Removed entries are not reported to the observer as evictions.
+    ///
+    /// ```rust
+    /// use lrumap::{SmallLruMap, LruMap, LruPeek, RetainProgress};
+    ///
+    /// let mut lru = SmallLruMap::new(8);
+    /// lru.extend((0..8).map(|key| (key, key)));
+    ///
+    /// let mut progress = RetainProgress::default();
+    /// while !progress.is_finished() {
+    ///     progress = lru.retain_incremental(progress, 3, |key, _| key % 2 == 0);
+    /// }
+    /// assert_eq!(lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [6, 4, 2, 0]);
+    /// ```
+    pub fn retain_incremental<Keep>(
+        &mut self,
+        progress: RetainProgress,
+        budget: usize,
+        keep: Keep,
+    ) -> RetainProgress
+    where
+        Keep: FnMut(&Key, &mut Value) -> bool,
+    {
+        retain_incremental(self, progress, budget, keep)
+    }
+
+    /// Converts this map into an iterator of its entries in order from least
+    /// recently touched to most recently touched.
+    ///
+    /// ```rust
+    /// use lrumap::{LruMap, SmallLruMap};
+    ///
+    /// let mut lru = SmallLruMap::new(3);
+    /// lru.extend([(1, 1), (2, 2), (3, 3)]);
+    /// lru.get(&1);
+    ///
+    /// let entries = lru.into_lru_iter().collect::<Vec<_>>();
+    /// assert_eq!(entries, vec![(2, 2), (3, 3), (1, 1)]);
+    /// ```
+    pub fn into_lru_iter(self) -> Rev<IntoIter<Key, Value>> {
+        IntoIter::from(self.cache).rev()
+    }
+}
+
+impl<Key, Value, Observer> LruPeek<Key, Value> for SmallLruMap<Key, Value, Observer>
+where
+    Key: Eq,
+    Observer: CacheObserver<Key, Value>,
+{
+    fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    fn is_full(&self) -> bool {
+        self.cache.is_full()
+    }
+
+    fn remaining_capacity(&self) -> usize {
+        self.cache.remaining_capacity()
+    }
+
+    fn tail_staleness(&self) -> Option<usize> {
+        self.cache.tail_staleness()
+    }
+
+    fn peek_head(&self) -> Option<(&Key, &Value)> {
+        self.head_key_value()
+    }
+
+    fn head_key(&self) -> Option<&Key> {
+        self.head_key()
+    }
+
+    fn head_key_value(&self) -> Option<(&Key, &Value)> {
+        self.head_key_value()
+    }
+
+    fn tail_key(&self) -> Option<&Key> {
+        self.tail_key()
+    }
+
+    fn tail_key_value(&self) -> Option<(&Key, &Value)> {
+        self.tail_key_value()
+    }
+
+    fn iter(&self) -> Iter<'_, Key, Value> {
+        self.cache.iter()
+    }
+
+    fn get_without_update<QueryKey>(&self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.get_without_update(key)
+    }
+
+    fn contains_key<QueryKey>(&self, key: &QueryKey) -> bool
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.contains_key(key)
+    }
+
+    fn is_more_recent_than<QueryKey>(&self, a: &QueryKey, b: &QueryKey) -> Option<bool>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.is_more_recent_than(a, b)
+    }
+
+    fn iter_from<QueryKey>(&self, key: &QueryKey) -> Option<Iter<'_, Key, Value>>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.iter_from(key)
+    }
+
+    fn staleness_histogram(&self, bucket_bounds: &[usize]) -> Vec<usize> {
+        self.cache.staleness_histogram(bucket_bounds)
+    }
+
+    fn partition_by_staleness(
+        &self,
+        threshold: usize,
+    ) -> (Take<Iter<'_, Key, Value>>, Iter<'_, Key, Value>) {
+        self.cache.partition_by_staleness(threshold)
+    }
+
+    fn iter_expiring_within(&self, staleness: usize) -> Expiring<'_, Key, Value> {
+        self.cache.iter_expiring_within(staleness)
+    }
+
+    fn iter_tail(&self, n: usize) -> IterTail<'_, Key, Value> {
+        self.cache.iter_tail(n)
+    }
+
+    fn keys_by_staleness(&self) -> KeysByStaleness<'_, Key, Value> {
+        self.cache.keys_by_staleness()
+    }
+
+    fn iter_dirty(&self) -> Dirty<'_, Key, Value> {
+        self.cache.iter_dirty()
+    }
+
+    fn vacancy_stats(&self) -> VacancyStats {
+        self.cache.vacancy_stats()
+    }
+
+    fn occupied_slots(&self) -> OccupiedSlots<'_, Key, Value> {
+        self.cache.occupied_slots()
+    }
+
+    fn iter_unordered(&self) -> Unordered<'_, Key, Value> {
+        self.cache.iter_unordered()
+    }
+
+    #[cfg(feature = "access-scores")]
+    fn top_k_by_access(&self, k: usize) -> Vec<(&Key, f32)> {
+        self.cache.top_k_by_access(k)
+    }
+
+    fn occupancy(&self) -> Occupancy {
+        self.cache.occupancy()
+    }
+
+    fn contains_entry(&self, id: EntryId) -> bool {
+        self.cache.contains_entry(id)
+    }
+
+    fn validate(&self) -> Result<(), CorruptionError> {
+        self.cache.validate()
+    }
+}
+
+impl<Key, Value, Observer> LruMap<Key, Value> for SmallLruMap<Key, Value, Observer>
+where
+    Key: Eq,
+    Observer: CacheObserver<Key, Value> + Default,
+{
+    type Config = Observer;
+
+    fn with_config(capacity: usize, observer: Self::Config) -> Self {
+        Self::with_observer(capacity, observer)
+    }
+
+    fn head(&mut self) -> Option<EntryRef<'_, Self, Key, Value>> {
+        self.cache.head().map(|node| EntryRef::new(self, node))
+    }
+
+    fn tail(&mut self) -> Option<EntryRef<'_, Self, Key, Value>> {
+        self.cache.tail().map(|node| EntryRef::new(self, node))
+    }
+
+    fn get<QueryKey>(&mut self, key: &QueryKey) -> Option<&Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.get(key)
+    }
+
+    fn get_mut<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.get_mut(key)
+    }
+
+    fn get_mut_without_update<QueryKey>(&mut self, key: &QueryKey) -> Option<&mut Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.get_mut_without_update(key)
+    }
+
+    fn entry<QueryKey>(&mut self, key: &QueryKey) -> Option<EntryRef<'_, Self, Key, Value>>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.entry(key)
+    }
+
+    fn take<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.take(key)
+    }
+
+    fn remove<QueryKey>(&mut self, key: &QueryKey) -> Option<Value>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.remove(key)
+    }
+
+    fn remove_entry<QueryKey>(&mut self, key: &QueryKey) -> Option<(Key, Value)>
+    where
+        QueryKey: Ord + Hash + Eq + ?Sized,
+        Key: Borrow<QueryKey> + Ord + Hash + Eq,
+    {
+        self.remove_entry(key)
+    }
+
+    fn pop_least_recent(&mut self) -> Option<(Key, Value)> {
+        self.pop_least_recent()
+    }
+
+    fn pop_most_recent(&mut self) -> Option<(Key, Value)> {
+        self.pop_most_recent()
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+
+    fn push(&mut self, key: Key, value: Value) -> Option<Removed<Key, Value>> {
+        self.push(key, value)
+    }
+
+    fn extend<IntoIter: IntoIterator<Item = (Key, Value)>>(&mut self, iterator: IntoIter) {
+        self.extend(iterator);
+    }
+}
+
+impl<Key, Value, Observer> EntryCache<Key, Value> for SmallLruMap<Key, Value, Observer>
+where
+    Key: Eq,
+    Observer: CacheObserver<Key, Value>,
+{
+    fn cache(&self) -> &LruCache<Key, Value> {
+        &self.cache
+    }
+
+    fn cache_mut(&mut self) -> &mut LruCache<Key, Value> {
+        &mut self.cache
+    }
+
+    fn remove_node(&mut self, node: NodeId) -> ((Key, Value), Option<NodeId>, Option<NodeId>) {
+        self.cache.remove(node)
+    }
+
+    fn notify_evicted(&mut self, key: &Key, value: &Value, dirty: bool) {
+        self.cache.capture_evicted(key, value);
+        if dirty {
+            self.observer.on_evict_dirty(key, value);
+        } else {
+            self.observer.on_evict(key, value);
+        }
+    }
+}
+
+impl<Key, Value, Observer> IntoIterator for SmallLruMap<Key, Value, Observer>
+where
+    Key: Eq,
+{
+    type IntoIter = IntoIter<Key, Value>;
+    type Item = (Key, Value);
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::from(self.cache)
+    }
+}
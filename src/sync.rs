@@ -0,0 +1,17 @@
+//! Synchronization primitives used by the concurrent map types.
+//!
+//! When compiled with `--cfg loom`, [loom](https://docs.rs/loom)'s
+//! primitives are used instead of the standard library's. This allows the
+//! concurrent map types to be verified within `loom::model`, both by this
+//! crate's tests and by crates embedding these types.
+
+#[cfg(loom)]
+pub use loom::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+#[cfg(not(loom))]
+pub use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard,
+};
@@ -0,0 +1,384 @@
+//! Model-based testing of Least Recently Used maps.
+//!
+//! This module is only available when the `testing` feature is enabled. It
+//! applies sequences of [`Operation`]s to both a real map and a simple
+//! reference implementation, [`Model`], and panics as soon as the two
+//! disagree. This allows new map implementations to be checked against the
+//! same behavior as the maps in this crate.
+//!
+//! [`check_slab_operations()`] similarly checks a
+//! [`LinkedSlab`](crate::linked_slab::LinkedSlab) against a `VecDeque`, for
+//! policies built directly on the list.
+//!
+//! ```rust
+//! use lrumap::testing::{check_operations, random_operations};
+//! use lrumap::LruHashMap;
+//!
+//! for seed in 0..4 {
+//!     check_operations::<LruHashMap<u32, u32>, _, _>(4, random_operations(seed, 100, 8));
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::linked_slab::{LinkedSlab, NodeId};
+use crate::{EntryRef, LruMap, Removed};
+
+/// An operation that can be applied to a map and a [`Model`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Operation<Key, Value> {
+    /// Pushes the key and value using [`LruMap::push()`].
+    Push(Key, Value),
+    /// Looks up the key using [`LruMap::get()`], touching it.
+    Get(Key),
+    /// Looks up the key using
+    /// [`LruPeek::get_without_update()`](crate::LruPeek::get_without_update),
+    /// which does not touch it.
+    Peek(Key),
+    /// Touches the key using [`EntryRef::touch()`](crate::EntryRef::touch).
+    Touch(Key),
+    /// Removes the key using [`EntryRef::take()`](crate::EntryRef::take).
+    Remove(Key),
+    /// Removes the least recently used entry using [`LruMap::tail()`].
+    RemoveTail,
+}
+
+/// The result of applying an [`Operation`].
+#[derive(Debug, Eq, PartialEq)]
+pub enum Outcome<Key, Value> {
+    /// The result of [`Operation::Push`].
+    Pushed(Option<Removed<Key, Value>>),
+    /// The value found by [`Operation::Get`] or [`Operation::Peek`].
+    Found(Option<Value>),
+    /// Whether the key of [`Operation::Touch`] was present.
+    Touched(bool),
+    /// The entry removed by [`Operation::Remove`] or
+    /// [`Operation::RemoveTail`].
+    Removed(Option<(Key, Value)>),
+}
+
+/// A reference Least Recently Used map that stores its entries in a
+/// [`VecDeque`], from most recently used to least recently used.
+///
+/// Every operation is a linear scan, which keeps the implementation obviously
+/// correct at the expense of performance.
+#[derive(Debug, Clone)]
+pub struct Model<Key, Value> {
+    capacity: usize,
+    entries: VecDeque<(Key, Value)>,
+}
+
+impl<Key, Value> Model<Key, Value>
+where
+    Key: Eq,
+    Value: Clone,
+{
+    /// Returns a new model with the maximum `capacity`.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns an iterator over the entries from most recently used to least
+    /// recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> + '_ {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    fn touch(&mut self, key: &Key) -> Option<&Value> {
+        let index = self.entries.iter().position(|(stored, _)| stored == key)?;
+        let entry = self.entries.remove(index)?;
+        self.entries.push_front(entry);
+        self.entries.front().map(|(_, value)| value)
+    }
+
+    /// Applies `operation` to this model, returning the expected outcome.
+    pub fn apply(&mut self, operation: Operation<Key, Value>) -> Outcome<Key, Value> {
+        match operation {
+            Operation::Push(key, value) => {
+                let previous = self
+                    .entries
+                    .iter()
+                    .position(|(stored, _)| stored == &key)
+                    .and_then(|index| self.entries.remove(index));
+                let removed = if let Some((_, previous)) = previous {
+                    Some(Removed::PreviousValue(previous))
+                } else if self.entries.len() >= self.capacity {
+                    self.entries
+                        .pop_back()
+                        .map(|(key, value)| Removed::Evicted(key, value))
+                } else {
+                    None
+                };
+                self.entries.push_front((key, value));
+                Outcome::Pushed(removed)
+            }
+            Operation::Get(key) => Outcome::Found(self.touch(&key).cloned()),
+            Operation::Peek(key) => Outcome::Found(
+                self.entries
+                    .iter()
+                    .find(|(stored, _)| stored == &key)
+                    .map(|(_, value)| value.clone()),
+            ),
+            Operation::Touch(key) => Outcome::Touched(self.touch(&key).is_some()),
+            Operation::Remove(key) => Outcome::Removed(
+                self.entries
+                    .iter()
+                    .position(|(stored, _)| stored == &key)
+                    .and_then(|index| self.entries.remove(index)),
+            ),
+            Operation::RemoveTail => Outcome::Removed(self.entries.pop_back()),
+        }
+    }
+}
+
+/// Applies `operation` to `map`, returning its outcome.
+pub fn apply<Map, Key, Value>(
+    map: &mut Map,
+    operation: Operation<Key, Value>,
+) -> Outcome<Key, Value>
+where
+    Map: LruMap<Key, Value>,
+    Key: Ord + Hash + Eq,
+    Value: Clone,
+{
+    match operation {
+        Operation::Push(key, value) => Outcome::Pushed(map.push(key, value)),
+        Operation::Get(key) => Outcome::Found(map.get(&key).cloned()),
+        Operation::Peek(key) => Outcome::Found(map.get_without_update(&key).cloned()),
+        Operation::Touch(key) => {
+            Outcome::Touched(map.entry(&key).map(|mut entry| entry.touch()).is_some())
+        }
+        Operation::Remove(key) => Outcome::Removed(map.entry(&key).map(EntryRef::take)),
+        Operation::RemoveTail => Outcome::Removed(map.tail().map(EntryRef::take)),
+    }
+}
+
+/// Applies each of `operations` to a new `Map` and a new [`Model`] with the
+/// same `capacity`.
+///
+/// # Panics
+///
+/// Panics if the map's outcome of an operation differs from the model's, if
+/// the order of the map's entries differs from the model's after an
+/// operation, or if the map fails [`LruPeek::validate()`](crate::LruPeek::validate).
+pub fn check_operations<Map, Key, Value>(
+    capacity: usize,
+    operations: impl IntoIterator<Item = Operation<Key, Value>>,
+) where
+    Map: LruMap<Key, Value>,
+    Key: Ord + Hash + Eq + Clone + Debug,
+    Value: Clone + Eq + Debug,
+{
+    let mut map = Map::new(capacity);
+    let mut model = Model::new(capacity);
+    for (step, operation) in operations.into_iter().enumerate() {
+        let expected = model.apply(operation.clone());
+        let actual = apply(&mut map, operation.clone());
+        assert_eq!(actual, expected, "step {step}: {operation:?}");
+        assert!(
+            map.iter().eq(model.iter()),
+            "step {step}: {operation:?}: order differs, expected {:?}, found {:?}",
+            model.iter().collect::<Vec<_>>(),
+            map.iter().collect::<Vec<_>>(),
+        );
+        assert_eq!(map.validate(), Ok(()), "step {step}: {operation:?}");
+    }
+}
+
+/// Returns `count` pseudo-random operations with keys in `0..key_space`.
+///
+/// The same `seed` always produces the same operations, allowing failures to
+/// be reproduced. Using a `key_space` a little larger than the capacity of
+/// the map being tested produces a mix of hits, misses, and evictions.
+///
+/// # Panics
+///
+/// Panics if `key_space` is 0.
+#[must_use]
+pub fn random_operations(seed: u64, count: usize, key_space: u32) -> Vec<Operation<u32, u32>> {
+    assert!(key_space > 0, "key_space must be greater than 0");
+    // xorshift64* requires a non-zero state, which one seed would not produce.
+    let mut state = match seed ^ 0x9e37_79b9_7f4a_7c15 {
+        0 => 0x9e37_79b9_7f4a_7c15,
+        state => state,
+    };
+    let mut random = move || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as u32
+    };
+
+    (0..count)
+        .map(|_| {
+            let key = random() % key_space;
+            match random() % 8 {
+                0..=2 => Operation::Push(key, random()),
+                3 | 4 => Operation::Get(key),
+                5 => Operation::Peek(key),
+                6 => Operation::Touch(key),
+                _ if random() % 4 == 0 => Operation::RemoveTail,
+                _ => Operation::Remove(key),
+            }
+        })
+        .collect()
+}
+
+/// An operation that can be applied to a
+/// [`LinkedSlab`](crate::linked_slab::LinkedSlab) by
+/// [`check_slab_operations()`].
+///
+/// Positions are taken modulo the length of the list, so every operation is
+/// valid for any non-empty list. Operations that require nodes are skipped
+/// when the list is too short.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SlabOperation {
+    /// Inserts a new value before the head.
+    PushFront,
+    /// Inserts a new value after the tail.
+    PushBack,
+    /// Moves the node at the position to the front of the list.
+    MoveToFront(usize),
+    /// Moves the node at the first position to directly after the node at
+    /// the second position, with positions counted after removing the moved
+    /// node.
+    MoveAfter(usize, usize),
+    /// Removes the node at the position.
+    Remove(usize),
+    /// Removes every node.
+    Clear,
+}
+
+/// Decodes `bytes` into a sequence of [`SlabOperation`]s. Every input
+/// produces a valid sequence, which allows arbitrary data from a fuzzer to be
+/// checked with [`check_slab_operations()`].
+#[must_use]
+pub fn slab_operations_from_bytes(bytes: &[u8]) -> Vec<SlabOperation> {
+    let mut bytes = bytes.iter().map(|&byte| usize::from(byte));
+    let mut operations = Vec::new();
+    while let Some(kind) = bytes.next() {
+        let mut position = || bytes.next().unwrap_or_default();
+        operations.push(match kind % 8 {
+            0 | 1 => SlabOperation::PushFront,
+            2 => SlabOperation::PushBack,
+            3 => SlabOperation::MoveToFront(position()),
+            4 => {
+                let node = position();
+                SlabOperation::MoveAfter(node, position())
+            }
+            5 | 6 => SlabOperation::Remove(position()),
+            _ => SlabOperation::Clear,
+        });
+    }
+    operations
+}
+
+/// Applies each of `operations` to a new
+/// [`LinkedSlab`](crate::linked_slab::LinkedSlab) and to a `VecDeque`
+/// containing the same values in the same order.
+///
+/// # Panics
+///
+/// Panics if the list's links, length, vacant slots, or the values returned
+/// from an operation differ from the model after any operation.
+pub fn check_slab_operations(operations: impl IntoIterator<Item = SlabOperation>) {
+    let mut slab = LinkedSlab::with_capacity(0);
+    let mut model = VecDeque::<(NodeId, usize)>::new();
+    for (step, operation) in operations.into_iter().enumerate() {
+        match operation {
+            SlabOperation::PushFront => model.push_front((slab.push_front(step), step)),
+            SlabOperation::PushBack => model.push_back((slab.push_back(step), step)),
+            SlabOperation::MoveToFront(index) if !model.is_empty() => {
+                let index = index % model.len();
+                let entry = model.remove(index).expect("index in bounds");
+                assert_eq!(slab.move_to_front(entry.0), index != 0, "step {step}");
+                model.push_front(entry);
+            }
+            SlabOperation::MoveAfter(index, anchor) if model.len() > 1 => {
+                let entry = model.remove(index % model.len()).expect("index in bounds");
+                let anchor = anchor % model.len();
+                slab.move_after(entry.0, model[anchor].0);
+                model.insert(anchor + 1, entry);
+            }
+            SlabOperation::Remove(index) if !model.is_empty() => {
+                let index = index % model.len();
+                let (node, value) = model.remove(index).expect("index in bounds");
+                let next = model.get(index).map(|&(node, _)| node);
+                let previous = index.checked_sub(1).map(|index| model[index].0);
+                let generation = slab.generation(node);
+                assert_eq!(slab.remove(node), (value, next, previous), "step {step}");
+                check_vacated(&slab, node, generation, step);
+            }
+            SlabOperation::Clear => {
+                let generations = std::mem::take(&mut model)
+                    .into_iter()
+                    .map(|(node, _)| (node, slab.generation(node)))
+                    .collect::<Vec<_>>();
+                slab.clear();
+                for (node, generation) in generations {
+                    check_vacated(&slab, node, generation, step);
+                }
+            }
+            _ => {}
+        }
+
+        check_slab(&slab, &model, step);
+    }
+}
+
+fn check_vacated(slab: &LinkedSlab<usize>, node: NodeId, generation: u32, step: usize) {
+    assert!(
+        slab.get(node).is_none(),
+        "step {step}: {node:?} is occupied"
+    );
+    assert_eq!(
+        slab.generation(node),
+        generation.wrapping_add(1),
+        "step {step}: {node:?} kept its generation"
+    );
+}
+
+fn check_slab(slab: &LinkedSlab<usize>, model: &VecDeque<(NodeId, usize)>, step: usize) {
+    assert_eq!(slab.len(), model.len(), "step {step}: length differs");
+    let mut previous = None;
+    let mut current = slab.head();
+    for &(node, value) in model {
+        assert_eq!(current, Some(node), "step {step}: link differs");
+        assert_eq!(
+            slab.previous(node),
+            previous,
+            "step {step}: back link differs"
+        );
+        assert_eq!(slab.get(node), Some(&value), "step {step}: value differs");
+        previous = current;
+        current = slab.next(node);
+    }
+    assert_eq!(current, None, "step {step}: list continues past the model");
+    assert_eq!(slab.tail(), previous, "step {step}: tail differs");
+
+    let mut vacant = 0;
+    let mut current = slab.vacant();
+    while let Some(node) = current {
+        assert!(
+            slab.get(node).is_none(),
+            "step {step}: {node:?} is occupied"
+        );
+        vacant += 1;
+        assert!(
+            vacant <= slab.slot_count(),
+            "step {step}: vacant chain cycles"
+        );
+        current = slab.next(node);
+    }
+    assert_eq!(
+        vacant,
+        slab.slot_count() - slab.len(),
+        "step {step}: vacant slots are missing from the chain"
+    );
+}
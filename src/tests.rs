@@ -1,6 +1,15 @@
+use std::collections::hash_map::RandomState;
 use std::fmt::Debug;
+#[cfg(feature = "testing")]
+use std::hash::{BuildHasherDefault, Hasher};
+use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{LruBTreeMap, LruHashMap, LruMap, Removed};
+use crate::{
+    CacheObserver, ConfigError, DuplicatePolicy, EntryRef, IntoLru, KeyPair, LruArcMap,
+    LruArcValueMap, LruBTreeMap, LruHashMap, LruMap, LruPeek, Removed, RetainProgress,
+    SharedLruMap, SmallLruMap, VacancyStats,
+};
 
 fn basic_tests<Map>()
 where
@@ -34,6 +43,23 @@ where
     // at the start of the function.
     assert_eq!(lru.get(&5), Some(&5));
     assert_eq!(lru.head().unwrap().key(), &5);
+    // Mutably getting an entry also updates its access.
+    *lru.get_mut(&2).unwrap() += 100;
+    assert_eq!(lru.head().unwrap().peek_value(), &122);
+    assert_eq!(lru.get_mut(&4), None);
+    // Unless it is a non-touching lookup.
+    *lru.get_mut_without_update(&5).unwrap() += 100;
+    assert_eq!(lru.tail().unwrap().peek_value(), &105);
+    assert_eq!(lru.get_mut_without_update(&4), None);
+    // Neither does checking for a key.
+    assert!(lru.contains_key(&2));
+    assert!(!lru.contains_key(&4));
+    assert_eq!(lru.tail().unwrap().key(), &5);
+    // Comparing recency does not touch either key.
+    assert_eq!(lru.is_more_recent_than(&2, &5), Some(true));
+    assert_eq!(lru.is_more_recent_than(&5, &2), Some(false));
+    assert_eq!(lru.is_more_recent_than(&2, &4), None);
+    assert_eq!(lru.tail().unwrap().key(), &5);
     println!("Final State: {lru:?}");
 }
 
@@ -46,6 +72,58 @@ fn hash_basics() {
 fn btree_basics() {
     basic_tests::<LruBTreeMap<_, _>>();
 }
+
+#[test]
+fn small_basics() {
+    basic_tests::<SmallLruMap<_, _>>();
+}
+
+fn unsized_query_tests<StringMap, BytesMap>()
+where
+    StringMap: LruMap<String, u32>,
+    BytesMap: LruMap<Vec<u8>, u32>,
+{
+    let mut lru = StringMap::new(2);
+    lru.extend([(String::from("a"), 1), (String::from("b"), 2)]);
+    assert_eq!(lru.get("a"), Some(&1));
+    assert_eq!(lru.get_without_update("b"), Some(&2));
+    lru.entry("b").unwrap().touch();
+    assert_eq!(lru.iter_from("b").unwrap().count(), 2);
+    assert_eq!(lru.take("a"), Some((String::from("a"), 1)));
+    assert_eq!(lru.take("a"), None);
+    assert!(lru.get("a").is_none());
+    lru.push(String::from("c"), 3);
+    assert_eq!(lru.remove("c"), Some(3));
+    assert_eq!(lru.remove("c"), None);
+    lru.push(String::from("d"), 4);
+    assert_eq!(lru.remove_entry("d"), Some((String::from("d"), 4)));
+    assert_eq!(lru.remove_entry("d"), None);
+
+    let mut lru = BytesMap::new(2);
+    lru.extend([(b"a".to_vec(), 1), (b"b".to_vec(), 2)]);
+    assert_eq!(lru.get(&b"a"[..]), Some(&1));
+    assert_eq!(
+        lru.entry(&b"b"[..]).map(EntryRef::take),
+        Some((b"b".to_vec(), 2))
+    );
+    assert_eq!(lru.take(&b"a"[..]), Some((b"a".to_vec(), 1)));
+    assert!(lru.is_empty());
+}
+
+#[test]
+fn hash_unsized_queries() {
+    unsized_query_tests::<LruHashMap<_, _>, LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_unsized_queries() {
+    unsized_query_tests::<LruBTreeMap<_, _>, LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_unsized_queries() {
+    unsized_query_tests::<SmallLruMap<_, _>, SmallLruMap<_, _>>();
+}
 fn larger_tests<Map>()
 where
     Map: LruMap<u32, u32> + Debug,
@@ -95,6 +173,11 @@ fn btree_larger() {
     larger_tests::<LruBTreeMap<_, _>>();
 }
 
+#[test]
+fn small_larger() {
+    larger_tests::<SmallLruMap<_, _>>();
+}
+
 #[allow(clippy::cognitive_complexity)]
 fn enumeration_tests<Map>()
 where
@@ -155,6 +238,11 @@ fn btree_enumeration() {
     enumeration_tests::<LruBTreeMap<_, _>>();
 }
 
+#[test]
+fn small_enumeration() {
+    enumeration_tests::<SmallLruMap<_, _>>();
+}
+
 #[allow(clippy::cognitive_complexity)]
 fn iteration_tests<Map>()
 where
@@ -200,6 +288,11 @@ fn btree_iteration() {
     iteration_tests::<LruBTreeMap<_, _>>();
 }
 
+#[test]
+fn small_iteration() {
+    iteration_tests::<SmallLruMap<_, _>>();
+}
+
 fn entry_removal_tests<Map>()
 where
     Map: LruMap<u32, u32> + Debug,
@@ -240,6 +333,27 @@ where
     assert_eq!(key, 2);
     assert!(lru.head().is_none());
     assert!(lru.tail().is_none());
+
+    // Popping removes entries from the tail, and removes them from the index.
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    lru.get(&1);
+    assert_eq!(lru.pop_least_recent(), Some((2, 2)));
+    assert!(lru.get(&2).is_none());
+    assert_eq!(lru.pop_least_recent(), Some((3, 3)));
+    assert_eq!(lru.pop_least_recent(), Some((1, 1)));
+    assert_eq!(lru.pop_least_recent(), None);
+    assert!(lru.is_empty());
+    lru.push(2, 2);
+    assert_eq!(lru.get(&2), Some(&2));
+
+    // Popping from the head removes the most recently used entries first.
+    lru.extend([(1, 1), (3, 3)]);
+    lru.get(&2);
+    assert_eq!(lru.pop_most_recent(), Some((2, 2)));
+    assert!(lru.get_without_update(&2).is_none());
+    assert_eq!(lru.pop_most_recent(), Some((3, 3)));
+    assert_eq!(lru.pop_most_recent(), Some((1, 1)));
+    assert_eq!(lru.pop_most_recent(), None);
 }
 
 #[test]
@@ -251,3 +365,2137 @@ fn hash_entry_removal() {
 fn btree_entry_removal() {
     entry_removal_tests::<LruBTreeMap<_, _>>();
 }
+
+#[test]
+fn small_entry_removal() {
+    entry_removal_tests::<SmallLruMap<_, _>>();
+}
+
+fn insertion_age_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let mut lru = Map::new(3);
+    lru.push(1, 1);
+    lru.push(2, 2);
+    // Touching 1 resets its staleness, but not the age since insertion.
+    assert_eq!(lru.get(&1), Some(&1));
+    assert_eq!(lru.entry(&1).unwrap().staleness(), 0);
+    assert_eq!(lru.entry(&1).unwrap().age_since_insert(), 2);
+    assert_eq!(lru.entry(&2).unwrap().age_since_insert(), 1);
+    // Replacing a value is not an insertion.
+    assert_eq!(lru.push(2, 22), Some(Removed::PreviousValue(2)));
+    assert_eq!(lru.entry(&2).unwrap().staleness(), 0);
+    assert_eq!(lru.entry(&2).unwrap().age_since_insert(), 2);
+    // Reusing a vacant node should reset both counters.
+    let (key, _value) = lru.head().unwrap().take();
+    assert_eq!(key, 2);
+    lru.push(4, 4);
+    assert_eq!(lru.entry(&4).unwrap().staleness(), 0);
+    assert_eq!(lru.entry(&4).unwrap().age_since_insert(), 0);
+    // The last accessed sequence is unaffected by other changes.
+    let sequence = lru.entry(&1).unwrap().last_accessed_sequence();
+    assert_eq!(lru.push(3, 3), None);
+    assert_eq!(lru.entry(&1).unwrap().last_accessed_sequence(), sequence);
+    assert!(lru.entry(&3).unwrap().last_accessed_sequence() > sequence);
+}
+
+#[test]
+fn hash_insertion_age() {
+    insertion_age_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_insertion_age() {
+    insertion_age_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_insertion_age() {
+    insertion_age_tests::<SmallLruMap<_, _>>();
+}
+
+#[test]
+fn refresh_restarts_age() {
+    let mut lru = LruBTreeMap::new(3);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    let staleness = lru.entry(&1).unwrap().staleness();
+    assert_eq!(lru.entry(&1).unwrap().age_since_insert(), 2);
+
+    // Refreshing restarts the age without changing the recency.
+    assert_eq!(lru.refresh(&1, 11), Some(1));
+    assert_eq!(lru.entry(&1).unwrap().age_since_insert(), 0);
+    assert_eq!(lru.entry(&1).unwrap().staleness(), staleness);
+    assert_eq!(lru.iter_expiring_within(0).next(), Some((&1, &11)));
+    assert_eq!(lru.push(4, 4), Some(Removed::Evicted(1, 11)));
+    assert_eq!(lru.refresh(&1, 1), None);
+}
+
+#[test]
+fn deferred_eviction() {
+    let mut lru = LruHashMap::new(2);
+    lru.set_deferred_eviction(true);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    assert_eq!(lru.len(), 3);
+    // Touching a key beyond capacity keeps it from being reclaimed.
+    assert_eq!(lru.get(&1), Some(&1));
+    assert_eq!(lru.reclaim().collect::<Vec<_>>(), vec![(2, 2)]);
+    assert_eq!(lru.len(), 2);
+
+    // Once evictions are no longer deferred, the vacant node beyond capacity
+    // must not be used. Pushing should evict instead.
+    lru.set_deferred_eviction(false);
+    assert_eq!(lru.push(4, 4), Some(Removed::Evicted(3, 3)));
+    assert_eq!(lru.len(), 2);
+
+    // Dropping the reclaim iterator still evicts the entries.
+    let mut lru = LruBTreeMap::new(2);
+    lru.set_deferred_eviction(true);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    drop(lru.reclaim());
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![4, 3]
+    );
+}
+
+#[test]
+fn extend_until_full() {
+    let mut lru = LruHashMap::new(2);
+    let mut remaining = lru.extend_until_full([(1, 1), (2, 2), (3, 3), (2, 22)]);
+    assert_eq!(remaining.peek(), Some(&(3, 3)));
+    assert_eq!(remaining.nth(1), Some((2, 22)));
+    assert_eq!(lru.iter().collect::<Vec<_>>(), vec![(&2, &2), (&1, &1)]);
+
+    // Deferring evictions does not allow growing beyond capacity.
+    let mut lru = SmallLruMap::new(2);
+    lru.set_deferred_eviction(true);
+    assert_eq!(
+        lru.extend_until_full([(1, 1), (2, 2), (3, 3)])
+            .collect::<Vec<_>>(),
+        vec![(3, 3)]
+    );
+    assert_eq!(lru.len(), 2);
+}
+
+#[test]
+fn extend_with() {
+    let keys = |lru: &LruBTreeMap<u32, u32>| lru.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+    let mut lru = LruBTreeMap::new(3);
+    lru.extend([(1, 10), (2, 20)]);
+
+    // Ignoring duplicates does not touch the existing keys.
+    lru.extend_with([(1, 1), (3, 3)], DuplicatePolicy::Ignore);
+    assert_eq!(keys(&lru), vec![3, 2, 1]);
+    assert_eq!(lru.get_without_update(&1), Some(&10));
+
+    // Merging touches the existing keys, and new keys still evict.
+    lru.extend_with(
+        [(1, 5), (4, 4)],
+        DuplicatePolicy::Merge(|stored, new| *stored += new),
+    );
+    assert_eq!(keys(&lru), vec![4, 1, 3]);
+    assert_eq!(lru.get_without_update(&1), Some(&15));
+
+    lru.extend_with([(3, 30)], DuplicatePolicy::Replace);
+    assert_eq!(keys(&lru), vec![3, 4, 1]);
+    assert_eq!(lru.get_without_update(&3), Some(&30));
+
+    let mut lru = LruHashMap::with_observer(2, RecordingObserver::default());
+    lru.push(1, 1);
+    lru.extend_with(
+        [(1, 1), (2, 2)],
+        DuplicatePolicy::Merge(|stored, new| *stored += new),
+    );
+    assert_eq!(lru.get_without_update(&1), Some(&2));
+    assert_eq!(
+        lru.observer().0,
+        [Event::Insert(1), Event::Replace(1), Event::Insert(2)]
+    );
+}
+
+#[test]
+fn push_or_merge() {
+    let add = |stored: &mut u32, new: u32| *stored += new;
+    let mut lru = SmallLruMap::with_observer(2, RecordingObserver::default());
+    assert_eq!(lru.push_or_merge(1, 1, add), None);
+    assert_eq!(lru.push_or_merge(2, 2, add), None);
+    assert_eq!(lru.push_or_merge(1, 10, add), None);
+    assert_eq!(lru.get_without_update(&1), Some(&11));
+    // Merging touched 1, so 2 is evicted.
+    assert_eq!(lru.push_or_merge(3, 3, add), Some((2, 2)));
+    assert_eq!(
+        lru.observer().0,
+        [
+            Event::Insert(1),
+            Event::Insert(2),
+            Event::Replace(1),
+            Event::Evict(2, 2),
+            Event::Insert(3)
+        ]
+    );
+}
+
+#[test]
+fn slot_reservations() {
+    let mut lru = LruHashMap::with_observer(3, RecordingObserver::default());
+    lru.extend([(1, 1), (2, 2)]);
+
+    // A slot is available, so nothing is evicted.
+    let (first, evicted) = lru.reserve_slot();
+    assert_eq!(evicted, None);
+    // The reserved slot is treated as occupied.
+    assert!(lru.would_evict(&3));
+    let (second, evicted) = lru.reserve_slot();
+    assert_eq!(evicted, Some((1, 1)));
+    assert_eq!(lru.push(3, 3), Some(Removed::Evicted(2, 2)));
+
+    assert_eq!(lru.push_with_token(first, 4, 4), Ok(None));
+    assert_eq!(lru.push_with_token(second, 4, 44), Ok(Some(4)));
+    assert_eq!(lru.len(), 2);
+    assert!(!lru.would_evict(&5));
+    assert_eq!(
+        lru.observer().0,
+        [
+            Event::Insert(1),
+            Event::Insert(2),
+            Event::Evict(1, 1),
+            Event::Evict(2, 2),
+            Event::Insert(3),
+            Event::Insert(4),
+            Event::Replace(4)
+        ]
+    );
+
+    let mut lru = SmallLruMap::new(2);
+    lru.extend([(1, 1), (2, 2)]);
+    let (token, evicted) = lru.reserve_slot();
+    assert_eq!(evicted, Some((1, 1)));
+    lru.release_slot(token);
+    assert_eq!(lru.push(3, 3), None);
+}
+
+#[test]
+fn slot_tokens_belong_to_their_map() {
+    let mut first = LruHashMap::new(3);
+    let mut second = LruHashMap::new(2);
+    second.extend([(1, 1), (2, 2)]);
+
+    // A token from another map is rejected rather than used to push without
+    // evicting, and its slot is released in the map that reserved it.
+    let (token, _) = first.reserve_slot();
+    assert_eq!(first.remaining_capacity(), 2);
+    assert_eq!(second.push_with_token(token, 3, 3), Err((3, 3)));
+    assert_eq!(first.remaining_capacity(), 3);
+    assert_eq!(second.len(), 2);
+    assert!(second.contains_key(&1));
+
+    // Dropping a token releases its slot.
+    let (token, _) = first.reserve_slot();
+    assert_eq!(first.remaining_capacity(), 2);
+    drop(token);
+    assert_eq!(first.remaining_capacity(), 3);
+
+    // Tokens remain valid when the values are mapped.
+    let (token, _) = first.reserve_slot();
+    let mut first = first.map_values(|value: u32| u64::from(value));
+    assert_eq!(first.remaining_capacity(), 2);
+    assert_eq!(first.push_with_token(token, 1, 1), Ok(None));
+    assert_eq!(first.remaining_capacity(), 2);
+}
+
+#[test]
+#[should_panic = "at least one slot must remain unreserved"]
+fn reserving_every_slot() {
+    let mut lru = LruBTreeMap::<u32, u32>::new(2);
+    let _token = lru.reserve_slot();
+    let _token = lru.reserve_slot();
+}
+
+#[test]
+fn try_reserve_slot() {
+    let mut lru = SmallLruMap::<u32, u32>::new(3);
+    let (first, _) = lru.try_reserve_slot().unwrap();
+    assert_eq!(lru.remaining_capacity(), 2);
+    let (second, _) = lru.try_reserve_slot().unwrap();
+    assert_eq!(lru.remaining_capacity(), 1);
+    assert!(!lru.is_full());
+    let error = lru.try_reserve_slot().unwrap_err();
+    assert_eq!(error, ConfigError::NoUnreservedSlots);
+    assert_eq!(
+        error.to_string(),
+        "at least one slot must remain unreserved"
+    );
+    lru.push(1, 1);
+    assert!(lru.is_full());
+    assert_eq!(lru.remaining_capacity(), 0);
+    lru.release_slot(first);
+    lru.release_slot(second);
+    assert_eq!(lru.remaining_capacity(), 2);
+    assert_eq!(lru.capacity(), 3);
+}
+
+#[test]
+fn try_set_insertion_sampling() {
+    let mut lru = LruHashMap::<u32, u32>::new(3);
+    lru.set_insertion_sampling(2);
+    let error = lru.try_set_insertion_sampling(0).unwrap_err();
+    assert_eq!(error, ConfigError::InvalidSamplingRate);
+    assert_eq!(
+        error.to_string(),
+        "insertion sampling rate must be at least 1"
+    );
+    assert_eq!(lru.insertion_sampling(), 2);
+    assert_eq!(lru.try_set_insertion_sampling(1), Ok(()));
+    assert_eq!(lru.push_sampled(1, 1), Ok(None));
+}
+
+fn config_error_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    for capacity in [0, 1] {
+        assert_eq!(
+            Map::try_new(capacity).unwrap_err(),
+            ConfigError::InvalidCapacity(capacity)
+        );
+    }
+    if let Some(capacity) = usize::try_from(u32::MAX)
+        .ok()
+        .and_then(|max| max.checked_add(1))
+    {
+        assert_eq!(
+            Map::try_with_config(capacity, Map::Config::default()).unwrap_err(),
+            ConfigError::InvalidCapacity(capacity)
+        );
+    }
+    assert!(Map::try_new(2).is_ok());
+}
+
+#[test]
+fn hash_config_errors() {
+    config_error_tests::<LruHashMap<_, _>>();
+    assert_eq!(
+        SharedLruMap::<u32, u32>::try_new(1)
+            .unwrap_err()
+            .to_string(),
+        "capacity must be greater than 1 and at most u32::MAX, but is 1"
+    );
+    assert!(SharedLruMap::<u32, u32>::try_new(2).is_ok());
+}
+
+#[test]
+fn btree_config_errors() {
+    config_error_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_config_errors() {
+    config_error_tests::<SmallLruMap<_, _>>();
+}
+
+#[test]
+fn reinsert() {
+    let mut lru = LruHashMap::new(3);
+    // Reinserting into an empty map.
+    assert_eq!(lru.reinsert(Removed::Evicted(1, 1)), Ok(None));
+    assert_eq!(lru.reinsert(Removed::Evicted(2, 2)), Ok(None));
+    assert_eq!(lru.push(3, 3), None);
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![3, 1, 2]
+    );
+    // Staleness must still be ordered from head to tail.
+    assert!(lru.entry(&2).unwrap().staleness() >= lru.entry(&1).unwrap().staleness());
+    // Values without keys and keys that are already present are rejected.
+    assert_eq!(
+        lru.reinsert(Removed::PreviousValue(4)),
+        Err(Removed::PreviousValue(4))
+    );
+    assert_eq!(
+        lru.reinsert(Removed::Evicted(3, 33)),
+        Err(Removed::Evicted(3, 33))
+    );
+    assert_eq!(lru.get(&3), Some(&3));
+
+    let mut lru = LruBTreeMap::new(2);
+    lru.extend([(1, 1), (2, 2)]);
+    let removed = lru.push(3, 3).unwrap();
+    assert_eq!(lru.reinsert(removed), Ok(Some((2, 2))));
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![3, 1]
+    );
+    assert_eq!(lru.get(&2), None);
+
+    // Evicting to make room also evicts the headroom, but never the
+    // reinserted entry.
+    let mut lru = SmallLruMap::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    lru.set_headroom(2);
+    assert_eq!(lru.reinsert(Removed::Evicted(5, 5)), Ok(Some((1, 1))));
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![4, 5]
+    );
+    // Reinserting without evicting leaves the headroom unchanged.
+    assert_eq!(lru.reinsert(Removed::Evicted(1, 1)), Ok(None));
+    assert_eq!(lru.len(), 3);
+
+    let mut lru = LruHashMap::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    let removed = lru.push(5, 5).unwrap();
+    lru.set_headroom(1);
+    assert_eq!(lru.reinsert(removed), Ok(Some((2, 2))));
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![5, 4, 1]
+    );
+    assert_eq!(lru.get(&1), Some(&1));
+
+    let mut lru = LruBTreeMap::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    let removed = lru.push(5, 5).unwrap();
+    lru.set_headroom(1);
+    assert_eq!(lru.reinsert(removed), Ok(Some((2, 2))));
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![5, 4, 1]
+    );
+    assert_eq!(lru.get(&1), Some(&1));
+}
+
+#[derive(Default, Debug)]
+struct RecordingObserver(Vec<Event>);
+
+#[derive(Debug, Eq, PartialEq)]
+enum Event {
+    Hit(u32),
+    Miss,
+    Insert(u32),
+    Replace(u32),
+    Evict(u32, u32),
+    EvictDirty(u32, u32),
+}
+
+impl CacheObserver<u32, u32> for RecordingObserver {
+    fn on_hit(&mut self, key: &u32) {
+        self.0.push(Event::Hit(*key));
+    }
+
+    fn on_miss(&mut self) {
+        self.0.push(Event::Miss);
+    }
+
+    fn on_insert(&mut self, key: &u32) {
+        self.0.push(Event::Insert(*key));
+    }
+
+    fn on_replace(&mut self, key: &u32) {
+        self.0.push(Event::Replace(*key));
+    }
+
+    fn on_evict(&mut self, key: &u32, value: &u32) {
+        self.0.push(Event::Evict(*key, *value));
+    }
+
+    fn on_evict_dirty(&mut self, key: &u32, value: &u32) {
+        self.0.push(Event::EvictDirty(*key, *value));
+    }
+}
+
+#[test]
+fn insert_new_and_replace_existing() {
+    let mut lru = LruHashMap::with_observer(3, RecordingObserver::default());
+    lru.set_headroom(1);
+    assert_eq!(lru.replace_existing(&1, 1), None);
+    assert!(lru.is_empty());
+    assert_eq!(lru.insert_new(1, 1), Ok(None));
+    assert_eq!(lru.insert_new(2, 2), Ok(None));
+    assert_eq!(lru.insert_new(3, 3), Ok(None));
+    assert_eq!(lru.insert_new(2, 22), Err((2, 22)));
+    assert_eq!(lru.replace_existing(&1, 11), Some(1));
+    // 1 was touched, so 2 and 3 are evicted to restore the headroom.
+    assert_eq!(lru.insert_new(4, 4), Ok(Some(Removed::Evicted(2, 2))));
+    assert_eq!(lru.iter().collect::<Vec<_>>(), vec![(&4, &4), (&1, &11)]);
+    assert_eq!(
+        lru.observer().0,
+        &[
+            Event::Insert(1),
+            Event::Insert(2),
+            Event::Insert(3),
+            Event::Replace(1),
+            Event::Evict(2, 2),
+            Event::Evict(3, 3),
+            Event::Insert(4),
+        ]
+    );
+}
+
+fn observer_tests<Map>(mut lru: Map, events: fn(&Map) -> &[Event])
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    lru.push(1, 1);
+    lru.push(2, 2);
+    lru.push(2, 22);
+    lru.push(3, 3);
+    assert_eq!(lru.get(&2), Some(&22));
+    assert_eq!(lru.get(&1), None);
+    // Non-touching lookups are not reported.
+    assert_eq!(lru.get_without_update(&3), Some(&3));
+    assert_eq!(
+        events(&lru),
+        &[
+            Event::Insert(1),
+            Event::Insert(2),
+            Event::Replace(2),
+            Event::Evict(1, 1),
+            Event::Insert(3),
+            Event::Hit(2),
+            Event::Miss,
+        ]
+    );
+}
+
+#[test]
+fn hash_observer() {
+    observer_tests(
+        LruHashMap::with_observer(2, RecordingObserver::default()),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn btree_observer() {
+    observer_tests(
+        LruBTreeMap::with_observer(2, RecordingObserver::default()),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn small_observer() {
+    observer_tests(
+        SmallLruMap::with_observer(2, RecordingObserver::default()),
+        |lru| &lru.observer().0,
+    );
+}
+
+fn extend_replaces_tests<Map>(mut lru: Map, events: fn(&Map) -> &[Event])
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    lru.extend([(1, 1), (2, 2), (1, 11), (3, 3)]);
+    assert_eq!(summarize(&lru), (3, Some(3), vec![3, 1, 2]));
+    assert_eq!(lru.get_without_update(&1), Some(&11));
+    assert_eq!(lru.validate(), Ok(()));
+    assert_eq!(
+        events(&lru),
+        &[
+            Event::Insert(1),
+            Event::Insert(2),
+            Event::Replace(1),
+            Event::Insert(3),
+        ]
+    );
+}
+
+#[test]
+fn hash_extend_replaces() {
+    extend_replaces_tests(
+        LruHashMap::with_observer(3, RecordingObserver::default()),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn btree_extend_replaces() {
+    extend_replaces_tests(
+        LruBTreeMap::with_observer(3, RecordingObserver::default()),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn small_extend_replaces() {
+    extend_replaces_tests(
+        SmallLruMap::with_observer(3, RecordingObserver::default()),
+        |lru| &lru.observer().0,
+    );
+}
+
+fn dirty_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let mut lru = Map::new(3);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    let dirty = |lru: &Map| lru.iter_dirty().map(|(key, _)| *key).collect::<Vec<_>>();
+    assert_eq!(dirty(&lru), vec![]);
+
+    let mut entry = lru.entry(&2).unwrap();
+    assert!(!entry.is_dirty());
+    entry.mark_dirty();
+    assert!(entry.is_dirty());
+    let mut entry = lru.tail().unwrap();
+    entry.mark_dirty();
+    assert_eq!(dirty(&lru), vec![1, 2]);
+    // Marking entries dirty does not touch them.
+    assert_eq!(summarize(&lru), (3, Some(3), vec![3, 2, 1]));
+
+    lru.head().unwrap().mark_dirty();
+    lru.entry(&2).unwrap().mark_clean();
+    assert_eq!(dirty(&lru), vec![1, 3]);
+
+    // Evicted entries' slots are reused without the flag.
+    lru.push(4, 4);
+    assert_eq!(dirty(&lru), vec![3]);
+    assert!(!lru.entry(&4).unwrap().is_dirty());
+}
+
+#[test]
+fn hash_dirty() {
+    dirty_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_dirty() {
+    dirty_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_dirty() {
+    dirty_tests::<SmallLruMap<_, _>>();
+}
+
+#[test]
+fn dirty_evictions() {
+    fn evictions(events: &[Event]) -> Vec<&Event> {
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::Evict(..) | Event::EvictDirty(..)))
+            .collect()
+    }
+
+    let mut lru = LruHashMap::with_observer(2, RecordingObserver::default());
+    lru.extend([(1, 1), (2, 2)]);
+    assert!(lru.mark_dirty(&1));
+    lru.push(3, 3);
+    lru.push(4, 4);
+    assert_eq!(
+        evictions(&lru.observer().0),
+        [&Event::EvictDirty(1, 1), &Event::Evict(2, 2)]
+    );
+
+    let mut lru = LruBTreeMap::with_observer(3, RecordingObserver::default());
+    lru.set_deferred_eviction(true);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4), (5, 5)]);
+    assert!(lru.mark_dirty(&2));
+    lru.reclaim().for_each(drop);
+    assert_eq!(
+        evictions(&lru.observer().0),
+        [&Event::Evict(1, 1), &Event::EvictDirty(2, 2)]
+    );
+
+    let mut lru = SmallLruMap::with_observer(2, RecordingObserver::default());
+    lru.extend([(1, 1), (2, 2)]);
+    assert!(lru.mark_dirty(&1));
+    let (token, evicted) = lru.reserve_slot();
+    assert_eq!(evicted, Some((1, 1)));
+    drop(token);
+    assert_eq!(evictions(&lru.observer().0), [&Event::EvictDirty(1, 1)]);
+}
+
+#[test]
+fn batched_touches() {
+    let mut lru = LruHashMap::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    let sequence = lru.entry(&4).unwrap().last_accessed_sequence();
+    let key = lru.batch(|lru| {
+        lru.get(&1);
+        lru.batch(|lru| {
+            lru.get(&2);
+        });
+        lru.get(&3);
+        *lru.head().unwrap().key()
+    });
+    assert_eq!(key, 3);
+    for key in [1, 2, 3] {
+        assert_eq!(
+            lru.entry(&key).unwrap().last_accessed_sequence(),
+            sequence + 1
+        );
+    }
+    // Keys touched in the same batch are equally recent.
+    assert_eq!(lru.is_more_recent_than(&1, &3), Some(false));
+    assert_eq!(lru.is_more_recent_than(&3, &1), Some(false));
+    assert_eq!(lru.is_more_recent_than(&3, &4), Some(true));
+    // Touches after the batch are separate changes again.
+    lru.get(&4);
+    lru.get(&1);
+    assert_eq!(
+        lru.entry(&1).unwrap().last_accessed_sequence(),
+        sequence + 3
+    );
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![1, 4, 3, 2]
+    );
+    assert_eq!(lru.validate(), Ok(()));
+}
+
+#[test]
+fn panicking_batch_ends() {
+    let mut lru = LruBTreeMap::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        lru.batch(|lru| {
+            lru.get(&1);
+            panic!("failed while batching");
+        });
+    }));
+    assert!(result.is_err());
+
+    // Touches after the panic are separate changes.
+    let sequence = lru.entry(&1).unwrap().last_accessed_sequence();
+    lru.get(&2);
+    lru.get(&3);
+    assert_eq!(
+        lru.entry(&3).unwrap().last_accessed_sequence(),
+        sequence + 2
+    );
+}
+
+#[test]
+fn panicking_eviction_capture_ends() {
+    let tracked = Rc::new(2);
+    let mut lru = SmallLruMap::new(2);
+    lru.extend([(1, Rc::new(1)), (2, tracked.clone())]);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        lru.with_eviction_capture(|lru| {
+            lru.push(3, Rc::new(3));
+            panic!("failed while capturing");
+        });
+    }));
+    assert!(result.is_err());
+
+    // Evictions after the panic are not copied into the abandoned capture.
+    lru.push(4, Rc::new(4));
+    assert_eq!(Rc::strong_count(&tracked), 1);
+    let ((), evicted) = lru.with_eviction_capture(|lru| {
+        lru.push(5, Rc::new(5));
+    });
+    assert_eq!(evicted, vec![(3, Rc::new(3))]);
+}
+
+#[test]
+fn panicking_token_push_restores_eviction() {
+    /// Panics when a particular key is inserted.
+    #[derive(Debug)]
+    struct PanicOnInsert(u32);
+
+    impl CacheObserver<u32, u32> for PanicOnInsert {
+        fn on_insert(&mut self, key: &u32) {
+            assert_ne!(*key, self.0, "failed while inserting");
+        }
+    }
+
+    let mut lru = LruBTreeMap::with_observer(3, PanicOnInsert(3));
+    lru.extend([(1, 1), (2, 2)]);
+    let (token, _) = lru.reserve_slot();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = lru.push_with_token(token, 3, 3);
+    }));
+    assert!(result.is_err());
+
+    // Eviction is no longer deferred, so the map stays within its capacity.
+    assert!(!lru.deferred_eviction());
+    assert_eq!(lru.push(4, 4), Some(Removed::Evicted(1, 1)));
+    assert_eq!(lru.len(), 3);
+}
+
+#[test]
+fn modify_many() {
+    let mut lru = LruBTreeMap::new(4);
+    lru.extend(["a", "b", "c", "d"].map(|key| (String::from(key), 0)));
+    let sequence = lru.entry("d").unwrap().last_accessed_sequence();
+
+    let queries = [String::from("c"), String::from("x"), String::from("a")];
+    let mut visited = Vec::new();
+    let missing = lru.modify_many(queries.iter().map(String::as_str), |key, value| {
+        visited.push(key.clone());
+        *value += 1;
+    });
+    assert_eq!(missing, ["x"]);
+    assert_eq!(visited, ["c", "a"]);
+    // Both touches are a single change.
+    for key in ["a", "c"] {
+        assert_eq!(
+            lru.entry(key).unwrap().last_accessed_sequence(),
+            sequence + 1
+        );
+    }
+    assert_eq!(
+        lru.iter()
+            .map(|(key, value)| (key.as_str(), *value))
+            .collect::<Vec<_>>(),
+        [("a", 1), ("c", 1), ("d", 0), ("b", 0)]
+    );
+    assert!(lru
+        .modify_many::<str, _, _>([], |_, _| unreachable!())
+        .is_empty());
+}
+
+#[test]
+fn eviction_capture() {
+    let mut lru = LruHashMap::new(4);
+    lru.set_headroom(1);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    let (inner, outer) = lru.with_eviction_capture(|lru| {
+        // Restoring the headroom evicts 2 without returning it.
+        assert_eq!(lru.push(5, 5), Some(Removed::Evicted(1, 1)));
+        let ((), inner) = lru.with_eviction_capture(|lru| {
+            assert_eq!(lru.take(&3), Some((3, 3)));
+            lru.extend([(6, 6), (7, 7), (8, 8)]);
+        });
+        lru.extend([(9, 9), (10, 10)]);
+        inner
+    });
+    assert_eq!(inner, vec![(4, 4), (5, 5)]);
+    assert_eq!(outer, vec![(1, 1), (2, 2), (4, 4), (5, 5), (6, 6), (7, 7)]);
+
+    // Evictions outside of a capture are not recorded.
+    lru.extend([(11, 11), (12, 12)]);
+    let ((), evicted) = lru.with_eviction_capture(|_| {});
+    assert!(evicted.is_empty());
+}
+
+fn headroom_tests<Map>(mut lru: Map, events: fn(&Map) -> &[Event])
+where
+    Map: LruMap<u32, u32>,
+{
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    assert_eq!(lru.push(5, 5), Some(Removed::Evicted(1, 1)));
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![5, 4, 3]
+    );
+    assert_eq!(lru.push(6, 6), None);
+    assert_eq!(
+        &events(&lru)[4..],
+        &[
+            Event::Evict(1, 1),
+            Event::Evict(2, 2),
+            Event::Insert(5),
+            Event::Insert(6),
+        ]
+    );
+}
+
+#[test]
+fn hash_headroom() {
+    let mut lru = LruHashMap::with_observer(4, RecordingObserver::default());
+    lru.set_headroom(1);
+    assert_eq!(lru.headroom(), 1);
+    headroom_tests(lru, |lru| &lru.observer().0);
+}
+
+#[test]
+fn btree_headroom() {
+    let mut lru = LruBTreeMap::with_observer(4, RecordingObserver::default());
+    lru.set_headroom(1);
+    headroom_tests(lru, |lru| &lru.observer().0);
+}
+
+#[test]
+fn small_headroom() {
+    let mut lru = SmallLruMap::with_observer(4, RecordingObserver::default());
+    lru.set_headroom(1);
+    headroom_tests(lru, |lru| &lru.observer().0);
+}
+
+#[test]
+#[should_panic = "headroom must be less than capacity"]
+fn headroom_too_large() {
+    LruHashMap::<u32, u32>::new(2).set_headroom(2);
+}
+
+fn truncate_tests<Map>(
+    mut lru: Map,
+    truncate: fn(&mut Map, usize) -> Vec<(u32, u32)>,
+    events: fn(&Map) -> &[Event],
+) where
+    Map: LruMap<u32, u32>,
+{
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    assert_eq!(truncate(&mut lru, 5), vec![]);
+    assert_eq!(truncate(&mut lru, 3), vec![(1, 1)]);
+    assert_eq!(truncate(&mut lru, 0), vec![(2, 2), (3, 3), (4, 4)]);
+    assert!(lru.is_empty());
+    // Truncating is not reported as evicting.
+    assert!(!events(&lru)
+        .iter()
+        .any(|event| matches!(event, Event::Evict(..))));
+}
+
+#[test]
+fn hash_truncate() {
+    truncate_tests(
+        LruHashMap::with_observer(4, RecordingObserver::default()),
+        |lru, len| lru.truncate(len).collect(),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn btree_truncate() {
+    truncate_tests(
+        LruBTreeMap::with_observer(4, RecordingObserver::default()),
+        |lru, len| lru.truncate(len).collect(),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn small_truncate() {
+    truncate_tests(
+        SmallLruMap::with_observer(4, RecordingObserver::default()),
+        |lru, len| lru.truncate(len).collect(),
+        |lru| &lru.observer().0,
+    );
+}
+
+#[test]
+fn with_config() {
+    fn build<Map: LruMap<u32, u32>>(config: Map::Config) -> Map {
+        let mut lru = Map::with_config(2, config);
+        lru.extend([(1, 1), (2, 2), (3, 3)]);
+        lru
+    }
+
+    let lru: LruHashMap<u32, u32, RandomState, RecordingObserver> =
+        build((RandomState::new(), RecordingObserver::default()));
+    assert_eq!(lru.observer().0.len(), 4);
+    let lru: LruBTreeMap<u32, u32, RecordingObserver> = build(RecordingObserver::default());
+    assert_eq!(lru.observer().0.len(), 4);
+    let lru: SmallLruMap<u32, u32> = build(());
+    assert_eq!(lru.len(), 2);
+}
+
+#[test]
+fn take_by_key() {
+    let mut lru = LruHashMap::new(3);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    assert_eq!(lru.take(&2), Some((2, 2)));
+    assert_eq!(lru.iter().collect::<Vec<_>>(), &[(&3, &3), (&1, &1)]);
+
+    let mut lru = LruBTreeMap::new(3);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    assert_eq!(lru.take(&3), Some((3, 3)));
+    assert_eq!(lru.take(&1), Some((1, 1)));
+    assert_eq!(lru.iter().collect::<Vec<_>>(), &[(&2, &2)]);
+    // The vacant nodes should be reused before evicting.
+    assert_eq!(lru.push(4, 4), None);
+    assert_eq!(lru.push(5, 5), None);
+    assert_eq!(lru.push(6, 6), Some(Removed::Evicted(2, 2)));
+}
+
+#[test]
+fn key_handles() {
+    let mut lru = LruHashMap::new(2);
+    lru.extend([(1, 1), (2, 2)]);
+    let handle = lru.entry(&1).unwrap().downgrade();
+    assert_eq!(handle.key(), &1);
+
+    // The slot is reused for a different key.
+    assert_eq!(lru.take(&1), Some((1, 1)));
+    lru.push(3, 3);
+    assert!(lru.upgrade(&handle).is_none());
+
+    // The key is stored in a different slot.
+    assert_eq!(lru.push(1, 11), Some(Removed::Evicted(2, 2)));
+    assert_eq!(lru.upgrade(&handle).unwrap().peek_value(), &11);
+    // Upgrading does not touch the key.
+    assert_eq!(lru.peek_head(), Some((&1, &11)));
+    lru.get(&3);
+    assert_eq!(lru.upgrade(&handle).unwrap().key(), &1);
+    assert_eq!(lru.peek_head(), Some((&3, &3)));
+}
+
+fn entry_id_tests<Map>()
+where
+    Map: LruMap<u32, u32>,
+{
+    let mut lru = Map::new(2);
+    lru.push(1, 1);
+    let one = lru.entry(&1).unwrap().id();
+    assert_eq!(lru.entry(&1).unwrap().id(), one);
+    lru.push(2, 2);
+    let two = lru.entry(&2).unwrap().id();
+    assert_ne!(one, two);
+
+    // Touching and replacing values do not change an entry's identity.
+    lru.get(&1);
+    lru.push(1, 11);
+    assert!(lru.contains_entry(one));
+
+    // Evicted entries are detected even though their slot is reused.
+    lru.push(3, 3);
+    assert!(!lru.contains_entry(two));
+    assert_ne!(lru.entry(&3).unwrap().id(), two);
+
+    // Emptying the map and reusing the same slot is also detected.
+    let three = lru.entry(&3).unwrap().id();
+    lru.take(&1);
+    lru.take(&3);
+    lru.push(4, 4);
+    assert!(!lru.contains_entry(three));
+    assert!(!lru.contains_entry(one));
+}
+
+#[test]
+fn hash_entry_ids() {
+    entry_id_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_entry_ids() {
+    entry_id_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_entry_ids() {
+    entry_id_tests::<SmallLruMap<_, _>>();
+}
+
+#[test]
+fn owned_cursors() {
+    let mut lru = LruBTreeMap::new(3);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+
+    // Remove every even entry, releasing the map between each step.
+    let mut cursor = lru.tail().map(|entry| entry.cursor());
+    while let Some(position) = cursor {
+        let mut entry = lru.resume(position).unwrap();
+        let next = if entry.key() % 2 == 0 {
+            entry.remove_moving_previous()
+        } else {
+            entry.move_previous().then_some(entry)
+        };
+        cursor = next.map(|entry| entry.cursor());
+    }
+    assert_eq!(lru.iter().collect::<Vec<_>>(), vec![(&3, &3), (&1, &1)]);
+
+    // A slot reused by a new entry does not resume the old cursor.
+    let cursor = lru.entry(&1).unwrap().cursor();
+    assert_eq!(lru.take(&1), Some((1, 1)));
+    lru.push(4, 4);
+    assert!(lru.resume(cursor).is_none());
+
+    // Emptying the map allows a new entry to be inserted into the same slot
+    // during the same change, which the cursor must still detect.
+    let mut lru = SmallLruMap::new(2);
+    lru.push(1, 1);
+    let cursor = lru.entry(&1).unwrap().cursor();
+    let handle = lru.entry(&1).unwrap().downgrade();
+    lru.take(&1);
+    lru.push(2, 2);
+    assert_eq!(lru.entry(&2).unwrap().age_since_insert(), 0);
+    assert!(lru.resume(cursor).is_none());
+    assert!(lru.upgrade(&handle).is_none());
+}
+
+fn collect_lru_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let lru: Map = [(1, 1), (2, 2), (1, 11), (3, 3)].collect_lru(2);
+    assert_eq!(summarize(&lru), (2, Some(3), vec![3, 1]));
+    assert_eq!(lru.get_without_update(&1), Some(&11));
+}
+
+#[test]
+fn hash_collect_lru() {
+    collect_lru_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_collect_lru() {
+    collect_lru_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_collect_lru() {
+    collect_lru_tests::<SmallLruMap<_, _>>();
+}
+
+fn summarize<Peek>(lru: &Peek) -> (usize, Option<u32>, Vec<u32>)
+where
+    Peek: LruPeek<u32, u32>,
+{
+    assert_eq!(lru.head_key(), lru.head_key_value().map(|(key, _)| key));
+    assert_eq!(
+        lru.tail_key(),
+        lru.keys_by_staleness().next().map(|(_, key)| key)
+    );
+    assert_eq!(lru.tail_key(), lru.tail_key_value().map(|(key, _)| key));
+    assert_eq!(
+        lru.tail_staleness(),
+        lru.keys_by_staleness()
+            .next()
+            .map(|(staleness, _)| staleness)
+    );
+    (
+        lru.len(),
+        lru.peek_head().map(|(key, _)| *key),
+        lru.iter().map(|(key, _)| *key).collect(),
+    )
+}
+
+fn peek_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let mut lru = Map::new(3);
+    assert_eq!(summarize(&lru), (0, None, vec![]));
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    assert_eq!(lru.get_without_update(&1), Some(&1));
+    // Peeking never touches keys.
+    assert_eq!(summarize(&lru), (3, Some(3), vec![3, 2, 1]));
+    assert_eq!(lru.tail_staleness(), Some(2));
+    assert_eq!(lru.staleness_histogram(&[]), vec![3]);
+    assert_eq!(lru.staleness_histogram(&[1]), vec![2, 1]);
+    assert_eq!(lru.staleness_histogram(&[0, 0, 5]), vec![1, 0, 2, 0]);
+    let partition = |lru: &Map, threshold| -> (Vec<u32>, Vec<u32>) {
+        let (fresh, stale) = lru.partition_by_staleness(threshold);
+        (
+            fresh.map(|(key, _)| *key).collect(),
+            stale.map(|(key, _)| *key).collect(),
+        )
+    };
+    assert_eq!(partition(&lru, 0), (vec![3], vec![2, 1]));
+    assert_eq!(partition(&lru, 2), (vec![3, 2, 1], vec![]));
+    assert_eq!(partition(&Map::new(2), 0), (vec![], vec![]));
+    let (_, mut stale) = lru.partition_by_staleness(0);
+    assert_eq!(stale.size_hint(), (2, Some(2)));
+    // The stale entries continue from the fresh entries.
+    assert_eq!(stale.next_back(), Some((&3, &3)));
+
+    let keys = |iter: &mut dyn Iterator<Item = (&u32, &u32)>| {
+        iter.map(|(key, _)| *key).collect::<Vec<_>>()
+    };
+    assert_eq!(keys(&mut lru.iter_head(2)), vec![3, 2]);
+    assert_eq!(keys(&mut lru.iter_head(5)), vec![3, 2, 1]);
+    assert_eq!(keys(&mut lru.iter_tail(2)), vec![1, 2]);
+    assert_eq!(lru.iter_tail(5).len(), 3);
+    assert_eq!(keys(&mut Map::new(2).iter_tail(1)), vec![]);
+
+    let expiring = |lru: &Map, staleness| {
+        lru.iter_expiring_within(staleness)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(expiring(&lru, 0), vec![1]);
+    assert_eq!(expiring(&lru, 1), vec![1, 2]);
+    assert_eq!(expiring(&lru, usize::MAX), vec![1, 2, 3]);
+    assert_eq!(expiring(&Map::new(2), usize::MAX), vec![]);
+    assert_eq!(
+        lru.keys_by_staleness().collect::<Vec<_>>(),
+        vec![(2, &1), (1, &2), (0, &3)]
+    );
+    assert_eq!(lru.keys_by_staleness().len(), 3);
+    assert_eq!(
+        lru.iter_from(&2)
+            .map(|iter| iter.map(|(key, _)| *key).collect()),
+        Some(vec![2, 1])
+    );
+    assert!(lru.iter_from(&4).is_none());
+    assert_eq!(lru.validate(), Ok(()));
+}
+
+#[test]
+fn hash_peek() {
+    peek_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_peek() {
+    peek_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_peek() {
+    peek_tests::<SmallLruMap<_, _>>();
+}
+
+fn exact_size_tests<Map: LruMap<u32, u32> + Debug>() {
+    let mut lru = Map::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+
+    // The position of a fresh iterator is known, so its size hint is exact,
+    // allowing collections to preallocate.
+    let mut iter = lru.iter();
+    assert_eq!(iter.size_hint(), (3, Some(3)));
+    assert_eq!(iter.next(), Some((&3, &3)));
+    assert_eq!(iter.size_hint(), (2, Some(2)));
+    assert_eq!(iter.next(), Some((&2, &2)));
+    // Moving the cursor back returns an entry that `next()` returns again.
+    assert_eq!(iter.next_back(), Some((&3, &3)));
+    assert_eq!(iter.size_hint(), (2, Some(2)));
+    assert_eq!(iter.next(), Some((&2, &2)));
+    assert_eq!(iter.next(), Some((&1, &1)));
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+    // The iterator is fused.
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+    assert_eq!(iter.next_back(), Some((&1, &1)));
+    assert_eq!(iter.next_back(), Some((&2, &2)));
+    assert_eq!(iter.size_hint(), (1, Some(1)));
+
+    // Starting at an entry leaves its position unknown until an end is
+    // reached.
+    let entry = lru.entry(&2).unwrap();
+    let mut iter = entry.iter();
+    assert_eq!(iter.size_hint(), (0, Some(3)));
+    assert_eq!(iter.by_ref().count(), 2);
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+
+    assert_eq!(lru.iter().rev().count(), 0);
+
+    let projected = lru.values_projected(|value| value);
+    assert_eq!(projected.len(), 3);
+
+    let into_iter = lru.into_iter();
+    assert_eq!(into_iter.len(), 3);
+}
+
+#[test]
+fn hash_exact_size() {
+    exact_size_tests::<LruHashMap<u32, u32>>();
+}
+
+#[test]
+fn btree_exact_size() {
+    exact_size_tests::<LruBTreeMap<u32, u32>>();
+}
+
+#[test]
+fn small_exact_size() {
+    exact_size_tests::<SmallLruMap<u32, u32>>();
+}
+
+fn into_lru_iter_tests<Map: LruMap<u32, u32> + Debug>() {
+    let mut lru = Map::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    lru.get(&2);
+
+    let mut iter = lru.into_iter();
+    assert_eq!(iter.next_back(), Some((1, 1)));
+    assert_eq!(iter.next(), Some((2, 2)));
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.next_back(), Some((3, 3)));
+    assert_eq!(iter.next_back(), Some((4, 4)));
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn hash_into_lru_iter() {
+    into_lru_iter_tests::<LruHashMap<u32, u32>>();
+}
+
+#[test]
+fn btree_into_lru_iter() {
+    into_lru_iter_tests::<LruBTreeMap<u32, u32>>();
+}
+
+#[test]
+fn small_into_lru_iter() {
+    into_lru_iter_tests::<SmallLruMap<u32, u32>>();
+}
+
+#[test]
+fn arc_keys() {
+    // Keys that don't implement Clone can be used when wrapped in an Arc.
+    #[derive(Hash, Eq, PartialEq, Debug)]
+    struct Key(u32);
+
+    let mut lru = LruArcMap::new(2);
+    lru.push(Arc::new(Key(1)), 1);
+    lru.push(Arc::new(Key(2)), 2);
+    assert_eq!(lru.get(&Key(1)), Some(&1));
+    assert_eq!(
+        lru.push(Arc::new(Key(3)), 3),
+        Some(Removed::Evicted(Arc::new(Key(2)), 2))
+    );
+    assert_eq!(lru.take(&Key(1)), Some((Arc::new(Key(1)), 1)));
+    assert_eq!(lru.len(), 1);
+}
+
+#[test]
+fn shared_front_caches() {
+    let shared = SharedLruMap::new(4);
+    shared.push(1, 1);
+    shared.push(2, 2);
+    shared.push(3, 3);
+
+    let mut front = shared.front(2, 2);
+    // Misses are promoted from the shared map.
+    assert_eq!(front.get(&1), Some(&1));
+    assert_eq!(front.get(&4), None);
+    // Local hits are batched. The first touch is pending.
+    assert_eq!(front.get(&1), Some(&1));
+    shared.push(4, 4);
+    assert_eq!(shared.take(&4), Some((4, 4)));
+    // Modifying the shared map discards the local entries, but the pending
+    // touch is written once the shared map is accessed through the front.
+    assert_eq!(front.get(&2), Some(&2));
+    assert_eq!(shared.push(5, 5), None);
+    assert_eq!(shared.push(6, 6), Some(Removed::Evicted(3, 3)));
+
+    // Pushing through the front keeps its local entries current.
+    assert_eq!(front.push(7, 7), Some(Removed::Evicted(1, 1)));
+    assert_eq!(front.get(&2), Some(&2));
+    assert_eq!(front.get(&7), Some(&7));
+    // Dropping the front flushes the pending touch of 7, leaving 2 as the
+    // least recently used key after two more evictions.
+    drop(front);
+    assert_eq!(shared.push(8, 8), Some(Removed::Evicted(5, 5)));
+    assert_eq!(shared.push(9, 9), Some(Removed::Evicted(6, 6)));
+    assert_eq!(shared.push(10, 10), Some(Removed::Evicted(2, 2)));
+    assert_eq!(shared.len(), 4);
+}
+
+#[test]
+fn shared_read_guards() {
+    let shared = SharedLruMap::new(2);
+    shared.push(1, 1);
+    shared.push(2, 2);
+
+    let guard = shared.read(&1).unwrap();
+    assert_eq!((guard.key(), *guard), (&1, 1));
+    assert!(shared.read(&3).is_none());
+    // The lock is not held by the guard, and the touch is deferred.
+    assert_eq!(shared.push(2, 22), Some(Removed::PreviousValue(2)));
+    assert_eq!(shared.push(3, 3), Some(Removed::Evicted(1, 1)));
+    // Dropping a guard for a removed key has no effect.
+    drop(guard);
+    assert_eq!(shared.len(), 2);
+
+    let guard = shared.read(&2).unwrap();
+    std::thread::scope(|scope| {
+        scope.spawn(move || assert_eq!(*guard, 22));
+    });
+    // The guard was dropped by the other thread, touching 2.
+    assert_eq!(shared.push(4, 4), Some(Removed::Evicted(3, 3)));
+
+    // Dropping a guard for an entry that was replaced by a new entry with the
+    // same key does not touch the new entry.
+    let guard = shared.read(&2).unwrap();
+    assert_eq!(shared.take(&2), Some((2, 22)));
+    assert_eq!(shared.push(2, 2), None);
+    assert_eq!(shared.push(5, 5), Some(Removed::Evicted(4, 4)));
+    drop(guard);
+    assert_eq!(shared.push(6, 6), Some(Removed::Evicted(2, 2)));
+}
+
+#[test]
+fn shared_front_caches_threaded() {
+    let shared = SharedLruMap::new(128);
+    std::thread::scope(|scope| {
+        for thread in 0..4_u32 {
+            let shared = &shared;
+            scope.spawn(move || {
+                let mut front = shared.front(4, 8);
+                for key in 0..32 {
+                    front.push(thread * 32 + key, key);
+                    assert_eq!(front.get(&(thread * 32 + key)), Some(&key));
+                }
+            });
+        }
+    });
+    assert_eq!(shared.len(), 128);
+}
+
+fn composite_key_tests<Map>()
+where
+    Map: LruMap<(String, u32), u32> + Debug,
+{
+    let mut lru = Map::new(3);
+    lru.extend([
+        (("a".to_string(), 1), 1),
+        (("a".to_string(), 2), 2),
+        (("b".to_string(), 1), 3),
+    ]);
+
+    let a = "a".to_string();
+    let b = "b".to_string();
+    assert_eq!(lru.get(&(&a, &2_u32) as &dyn KeyPair<_, _>), Some(&2));
+    assert_eq!(lru.get(&(&b, &1_u32) as &dyn KeyPair<_, _>), Some(&3));
+    assert_eq!(lru.get(&(&b, &2_u32) as &dyn KeyPair<_, _>), None);
+    assert_eq!(
+        lru.get_without_update(&(&a, &1_u32) as &dyn KeyPair<_, _>),
+        Some(&1)
+    );
+    assert_eq!(lru.head().unwrap().key(), &("b".to_string(), 1));
+}
+
+#[test]
+fn hash_composite_key() {
+    composite_key_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_composite_key() {
+    composite_key_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_composite_key() {
+    composite_key_tests::<SmallLruMap<_, _>>();
+}
+
+fn vacancy_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let stats = |occupied_slots, vacant_slots| VacancyStats {
+        occupied_slots,
+        vacant_slots,
+        vacant_chain: vacant_slots,
+    };
+    let mut lru = Map::new(4);
+    assert_eq!(lru.vacancy_stats(), stats(0, 0));
+    assert_eq!(lru.capacity(), 4);
+    assert_eq!(lru.remaining_capacity(), 4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    assert_eq!(lru.vacancy_stats(), stats(4, 0));
+    assert!(lru.is_full());
+    assert_eq!(lru.remaining_capacity(), 0);
+
+    // Remove the entries in slots 1 and 3.
+    assert_eq!(lru.entry(&2).unwrap().take(), (2, 2));
+    assert_eq!(lru.entry(&4).unwrap().take(), (4, 4));
+    assert_eq!(lru.vacancy_stats(), stats(2, 2));
+    assert_eq!(lru.occupied_slots().collect::<Vec<_>>(), vec![0, 2]);
+
+    // Vacant slots are reused before the map grows.
+    lru.push(5, 5);
+    assert_eq!(lru.vacancy_stats(), stats(3, 1));
+    assert_eq!(lru.occupied_slots().collect::<Vec<_>>(), vec![0, 2, 3]);
+    // Entries are visited in storage order, skipping the vacant slot.
+    let unordered = lru.iter_unordered();
+    assert_eq!(unordered.len(), 3);
+    assert_eq!(
+        unordered.collect::<Vec<_>>(),
+        vec![(&1, &1), (&3, &3), (&5, &5)]
+    );
+
+    let occupancy = lru.occupancy();
+    assert_eq!(occupancy.entries, 3);
+    assert_eq!(occupancy.capacity, 4);
+    assert_eq!(occupancy.vacant_slots, 1);
+}
+
+#[test]
+fn hash_vacancy() {
+    vacancy_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_vacancy() {
+    vacancy_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_vacancy() {
+    vacancy_tests::<SmallLruMap<_, _>>();
+}
+
+#[cfg(feature = "access-scores")]
+fn top_k_by_access_tests<Map>()
+where
+    Map: LruMap<u32, u32>,
+{
+    let mut lru = Map::new(4);
+    assert!(lru.top_k_by_access(2).is_empty());
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    for _ in 0..4 {
+        lru.get(&3);
+        lru.get(&1);
+    }
+    lru.get(&3);
+
+    let keys = |top: Vec<(&u32, f32)>| top.into_iter().map(|(key, _)| *key).collect::<Vec<_>>();
+    assert!(lru.top_k_by_access(0).is_empty());
+    assert_eq!(keys(lru.top_k_by_access(2)), [3, 1]);
+    assert_eq!(lru.top_k_by_access(10).len(), 4);
+    let top = lru.top_k_by_access(4);
+    assert!(top.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    let highest = top[0].1;
+    assert!((lru.entry(&3).unwrap().score() - highest).abs() < 1e-6);
+
+    // Looking up the scores does not touch any entries.
+    assert_eq!(lru.head().unwrap().key(), &3);
+    assert_eq!(lru.tail().unwrap().key(), &2);
+}
+
+#[cfg(feature = "access-scores")]
+#[test]
+fn hash_top_k_by_access() {
+    top_k_by_access_tests::<LruHashMap<_, _>>();
+}
+
+#[cfg(feature = "access-scores")]
+#[test]
+fn btree_top_k_by_access() {
+    top_k_by_access_tests::<LruBTreeMap<_, _>>();
+}
+
+#[cfg(feature = "access-scores")]
+#[test]
+fn small_top_k_by_access() {
+    top_k_by_access_tests::<SmallLruMap<_, _>>();
+}
+
+#[test]
+fn cursor_iter() {
+    let mut lru = SmallLruMap::new(5);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4), (5, 5)]);
+
+    let mut cursor = lru.cursor_iter();
+    assert_eq!(cursor.current(), None);
+    assert!(!cursor.touch_current());
+    let mut visited = Vec::new();
+    while let Some((&key, _)) = cursor.advance() {
+        visited.push(key);
+        match key {
+            5 | 2 => {
+                assert_eq!(cursor.remove_current(), Some((key, key)));
+                // The current entry can only be removed once.
+                assert_eq!(cursor.remove_current(), None);
+            }
+            3 => assert!(cursor.touch_current()),
+            _ => {}
+        }
+    }
+    assert_eq!(visited, [5, 4, 3, 2, 1]);
+    assert_eq!(cursor.current(), None);
+
+    assert_eq!(
+        lru.iter().collect::<Vec<_>>(),
+        [(&3, &3), (&4, &4), (&1, &1)]
+    );
+}
+
+#[test]
+fn retain() {
+    let mut sessions = LruBTreeMap::new(4);
+    sessions.extend([("a", 3_u32), ("b", 0), ("c", 1), ("d", 0)]);
+    sessions.get("b");
+
+    let mut visited = Vec::new();
+    sessions.retain(|key, remaining| {
+        visited.push(*key);
+        *remaining = remaining.saturating_sub(1);
+        *remaining > 0
+    });
+    assert_eq!(visited, ["b", "d", "c", "a"]);
+    assert_eq!(sessions.iter().collect::<Vec<_>>(), [(&"a", &2)]);
+    assert_eq!(sessions.validate(), Ok(()));
+    for key in ["b", "c", "d"] {
+        assert!(!sessions.contains_key(key));
+    }
+
+    // The removed keys can be pushed again.
+    sessions.push("b", 1);
+    assert_eq!(sessions.get("b"), Some(&1));
+    assert_eq!(sessions.len(), 2);
+}
+
+#[test]
+fn incremental_retain() {
+    let keys = |lru: &LruHashMap<u32, u32>| lru.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+    let mut lru = LruHashMap::new(8);
+    lru.extend((0..8).map(|key| (key, key)));
+    let mut visited = Vec::new();
+    let mut keep = |key: &u32, value: &mut u32| {
+        visited.push(*key);
+        *value += 10;
+        key & 1 == 0
+    };
+
+    // Entries are visited in the order of their slots, which matches the
+    // order they were inserted in.
+    let progress = lru.retain_incremental(RetainProgress::default(), 3, &mut keep);
+    assert!(!progress.is_finished());
+    assert_eq!(keys(&lru), [7, 6, 5, 4, 3, 2, 0]);
+    // The map can be modified between calls. Touching the entry the sweep
+    // resumes from does not restart the sweep, and the pushed entry reuses a
+    // slot the sweep has already passed.
+    lru.get(&3);
+    lru.push(8, 8);
+    let progress = lru.retain_incremental(progress, 0, &mut keep);
+    let progress = lru.retain_incremental(progress, 2, &mut keep);
+    assert_eq!(keys(&lru), [8, 7, 6, 5, 4, 2, 0]);
+    // Removing the entry the sweep resumes from does not restart it either.
+    lru.take(&5);
+    let progress = lru.retain_incremental(progress, usize::MAX, &mut keep);
+    assert!(progress.is_finished());
+    let progress = lru.retain_incremental(progress, usize::MAX, &mut keep);
+    assert!(progress.is_finished());
+
+    assert_eq!(visited, [0, 1, 2, 3, 4, 6, 7]);
+    assert_eq!(keys(&lru), [8, 6, 4, 2, 0]);
+    assert_eq!(lru.get_without_update(&6), Some(&16));
+    assert_eq!(lru.get_without_update(&8), Some(&8));
+}
+
+#[test]
+fn soft_touches() {
+    let keys = |lru: &LruBTreeMap<u32, u32>| lru.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+    let mut lru = LruBTreeMap::new(5);
+    lru.extend((1..=5).map(|key| (key, key)));
+    let staleness = lru.entry(&4).unwrap().staleness();
+
+    assert!(lru.touch_soft(&1));
+    assert_eq!(keys(&lru), [5, 1, 4, 3, 2]);
+    // Soft touches do not count as a change, and the moved entry takes the
+    // staleness of the entry in front of it.
+    assert_eq!(lru.entry(&4).unwrap().staleness(), staleness);
+    assert_eq!(lru.entry(&1).unwrap().staleness(), 0);
+    assert_eq!(lru.validate(), Ok(()));
+
+    // Entries already within the depth are not moved.
+    assert!(lru.touch_to_depth(&4, 3));
+    assert_eq!(keys(&lru), [5, 1, 4, 3, 2]);
+    assert!(lru.touch_to_depth(&2, 100));
+    assert_eq!(keys(&lru), [5, 1, 4, 3, 2]);
+
+    assert!(lru.touch_to_depth(&2, 2));
+    assert_eq!(keys(&lru), [5, 1, 2, 4, 3]);
+    assert!(lru.touch_to_depth(&3, 0));
+    assert_eq!(keys(&lru), [3, 5, 1, 2, 4]);
+    assert!(lru.entry(&4).unwrap().staleness() >= lru.entry(&2).unwrap().staleness());
+    assert_eq!(lru.validate(), Ok(()));
+    assert!(!lru.touch_soft(&6));
+}
+
+fn clear_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let mut lru = Map::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    lru.take(&2);
+    let id = lru.entry(&3).unwrap().id();
+
+    lru.clear();
+    assert!(lru.is_empty());
+    assert_eq!(lru.validate(), Ok(()));
+    assert!(lru.iter().next().is_none());
+    assert!(!lru.contains_entry(id));
+    let stats = lru.vacancy_stats();
+    assert_eq!(
+        (stats.occupied_slots, stats.vacant_slots, stats.vacant_chain),
+        (0, 4, 4)
+    );
+
+    // The slots are reused, and entries that reuse a slot are not mistaken
+    // for the entries stored before clearing.
+    lru.extend([(5, 5), (6, 6), (7, 7), (8, 8)]);
+    assert_eq!(lru.vacancy_stats().vacant_slots, 0);
+    assert!(!lru.contains_entry(id));
+    assert_eq!(lru.get(&3), None);
+    assert_eq!(lru.push(9, 9), Some(Removed::Evicted(5, 5)));
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        [9, 8, 7, 6]
+    );
+    assert_eq!(lru.validate(), Ok(()));
+}
+
+#[test]
+fn hash_clear() {
+    clear_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_clear() {
+    clear_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_clear() {
+    clear_tests::<SmallLruMap<_, _>>();
+}
+
+fn diff_tests<Map>()
+where
+    Map: LruMap<u32, u32> + Debug,
+{
+    let map = |keys: &[u32]| {
+        let mut map = Map::new(8);
+        // Pushed from least to most recently used.
+        map.extend(keys.iter().rev().map(|&key| (key, key)));
+        map
+    };
+    let check = |from: &[u32], to: &[u32], added: &[u32], removed: &[u32], reordered: &[u32]| {
+        let mut from = map(from);
+        let to = map(to);
+        let diff = from.diff(&to);
+        assert_eq!(diff.added, added);
+        assert_eq!(diff.removed, removed);
+        assert_eq!(diff.reordered, reordered);
+
+        // Applying the diff produces the same keys in the same order.
+        for key in &diff.removed {
+            assert!(from.take(key).is_some());
+        }
+        let keys = to.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        for key in keys.iter().rev() {
+            if diff.added.contains(key) {
+                from.push(*key, *key);
+            } else if diff.reordered.contains(key) {
+                from.get(key);
+            }
+        }
+        assert!(from.diff(&to).is_empty(), "{from:?} != {to:?}");
+    };
+
+    check(&[1, 2, 3], &[1, 2, 3], &[], &[], &[]);
+    check(&[], &[1, 2], &[1, 2], &[], &[]);
+    check(&[1, 2], &[], &[], &[1, 2], &[]);
+    check(&[1, 2, 3, 4], &[2, 3], &[], &[1, 4], &[]);
+    check(&[1, 2, 3], &[3, 1, 2], &[], &[], &[3]);
+    check(&[1, 2, 3], &[2, 1, 3], &[], &[], &[2]);
+    check(&[1, 2, 3], &[3, 2, 1], &[], &[], &[3, 2]);
+    check(&[1, 2], &[1, 5, 2], &[5], &[], &[1]);
+    check(&[1, 2, 3, 4], &[6, 4, 1, 5, 3], &[6, 5], &[2], &[4, 1]);
+}
+
+#[test]
+fn hash_diff() {
+    diff_tests::<LruHashMap<_, _>>();
+}
+
+#[test]
+fn btree_diff() {
+    diff_tests::<LruBTreeMap<_, _>>();
+}
+
+#[test]
+fn small_diff() {
+    diff_tests::<SmallLruMap<_, _>>();
+}
+
+#[test]
+fn map_values() {
+    let mut raw = LruHashMap::new(4);
+    raw.extend([(1, "1"), (2, "2"), (3, "3"), (4, "4")]);
+    raw.take(&2);
+    raw.get(&1);
+    raw.set_headroom(1);
+    let staleness = raw.entry(&3).unwrap().staleness();
+
+    let mut parsed = raw.map_values(|value| value.parse::<u32>().unwrap());
+    assert_eq!(parsed.validate(), Ok(()));
+    assert_eq!(parsed.headroom(), 1);
+    assert_eq!(parsed.entry(&3).unwrap().staleness(), staleness);
+    assert_eq!(
+        parsed.values_projected(|value| value).collect::<Vec<_>>(),
+        [(&1, &1), (&4, &4), (&3, &3)]
+    );
+
+    // The vacant slot is reused, and the map continues evicting with the same
+    // headroom.
+    parsed.push(5, 5);
+    assert_eq!(parsed.vacancy_stats().vacant_slots, 0);
+    assert_eq!(parsed.push(6, 6), Some(Removed::Evicted(3, 3)));
+    assert!(!parsed.contains_key(&4));
+    assert_eq!(parsed.get(&1), Some(&1));
+    assert_eq!(parsed.len(), 3);
+}
+
+#[test]
+fn insertion_sampling() {
+    let mut lru = LruHashMap::new(4);
+    assert_eq!(lru.insertion_sampling(), 1);
+    lru.extend([(1, 1), (2, 2)]);
+
+    // A scan of new keys only admits one in four, leaving most of the
+    // existing entries in place.
+    lru.set_insertion_sampling(4);
+    let rejected = (10..18)
+        .filter(|&key| lru.push_sampled(key, key).is_err())
+        .count();
+    assert_eq!(rejected, 6);
+    assert_eq!(lru.rejected_insertions(), 6);
+    assert_eq!(
+        lru.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        [14, 10, 2, 1]
+    );
+
+    // Existing keys are always updated and are not counted.
+    assert_eq!(lru.push_sampled(1, 11), Ok(Some(Removed::PreviousValue(1))));
+    assert_eq!(lru.rejected_insertions(), 6);
+
+    // Changing the rate restarts sampling with the next new key.
+    lru.set_insertion_sampling(2);
+    assert_eq!(lru.push_sampled(20, 20), Ok(Some(Removed::Evicted(2, 2))));
+    assert_eq!(lru.push_sampled(21, 21), Err((21, 21)));
+    assert_eq!(lru.rejected_insertions(), 7);
+}
+
+#[cfg(feature = "access-scores")]
+#[test]
+fn access_scores() {
+    let score = |lru: &mut LruHashMap<u32, u32>, key: u32| lru.entry(&key).unwrap().score();
+    let mut lru = LruHashMap::new(4);
+    lru.extend([(1, 1), (2, 2)]);
+    assert!((score(&mut lru, 1) - 0.5_f32.powf(0.25)).abs() < 1e-6);
+    assert!((score(&mut lru, 2) - 1.).abs() < 1e-6);
+
+    // Touching the head still counts as an access.
+    for _ in 0..3 {
+        lru.get(&1);
+    }
+    let hot = score(&mut lru, 1);
+    assert!((hot - (0.5_f32.sqrt() + 3.)).abs() < 1e-5);
+    assert!(hot > score(&mut lru, 2) + 2.);
+
+    // The score halves after as many changes as the capacity.
+    lru.extend([(3, 3), (4, 4)]);
+    lru.get(&3);
+    lru.get(&4);
+    assert!((score(&mut lru, 1) - hot / 2.).abs() < 1e-5);
+}
+
+#[cfg(feature = "access-scores")]
+#[test]
+fn stale_in_place_touch_score() {
+    let mut lru = LruHashMap::new(4);
+    lru.extend([(1, 1), (2, 2), (3, 3), (4, 4)]);
+    for _ in 0..1000 {
+        lru.get(&1);
+        lru.get(&2);
+    }
+
+    // Key 4 is already within the depth, so it is touched without moving,
+    // long after it was last accessed.
+    assert!(lru.touch_to_depth(&4, 4));
+    let stale = lru.entry(&4).unwrap().score();
+    assert!(stale.is_finite());
+    assert!((stale - 1.).abs() < 1e-6);
+
+    lru.get(&3);
+    assert!(lru.entry(&4).unwrap().score().is_finite());
+    let top = lru.top_k_by_access(1);
+    assert!(matches!(top[..], [(&1 | &2, _)]));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn model_based() {
+    use crate::testing::{check_operations, random_operations};
+
+    for seed in 0..64 {
+        let operations = random_operations(seed, 500, 6);
+        check_operations::<LruHashMap<u32, u32>, _, _>(4, operations.clone());
+        check_operations::<LruBTreeMap<u32, u32>, _, _>(4, operations.clone());
+        check_operations::<SmallLruMap<u32, u32>, _, _>(4, operations.clone());
+        check_operations::<LruHashMap<u32, u32, BuildHasherDefault<Colliding>>, _, _>(
+            4, operations,
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn random_operations_from_every_seed() {
+    use crate::testing::random_operations;
+
+    // This seed would produce a zero state, which xorshift never leaves.
+    let operations = random_operations(0x9e37_79b9_7f4a_7c15, 64, 6);
+    assert!(operations
+        .iter()
+        .any(|operation| operation != &operations[0]));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn slab_model_based() {
+    use crate::testing::{check_slab_operations, slab_operations_from_bytes, SlabOperation};
+
+    check_slab_operations([
+        SlabOperation::PushBack,
+        SlabOperation::PushFront,
+        SlabOperation::PushBack,
+        SlabOperation::MoveAfter(0, 1),
+        SlabOperation::MoveToFront(2),
+        SlabOperation::Remove(1),
+        SlabOperation::PushFront,
+        SlabOperation::Clear,
+        SlabOperation::PushBack,
+        SlabOperation::Remove(0),
+    ]);
+
+    let mut state = 0x2545_f491_u32;
+    for _ in 0..64 {
+        let bytes = (0..256)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state.to_le_bytes()[0]
+            })
+            .collect::<Vec<_>>();
+        check_slab_operations(slab_operations_from_bytes(&bytes));
+    }
+}
+
+/// A hasher that only produces two distinct hashes, ensuring most keys
+/// collide.
+#[cfg(feature = "testing")]
+#[derive(Default)]
+struct Colliding(u64);
+
+#[cfg(feature = "testing")]
+impl Hasher for Colliding {
+    fn finish(&self) -> u64 {
+        self.0 % 2
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.wrapping_add(u64::from(*byte));
+        }
+    }
+}
+
+#[test]
+fn deterministic_iteration() {
+    let snapshot = || {
+        let mut lru = LruHashMap::with_random_state(4);
+        lru.extend((0..6).map(|key| (key, key)));
+        lru.batch(|lru| {
+            lru.get(&3);
+            lru.get(&2);
+        });
+        lru.take(&4);
+        lru.push(6, 6);
+        lru.iter_deterministic()
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>()
+    };
+
+    // Each map is seeded differently.
+    for _ in 0..16 {
+        assert_eq!(snapshot(), [6, 2, 3, 5]);
+    }
+}
+
+#[test]
+#[cfg(feature = "interning")]
+fn interned_string_keys() {
+    let mut lru = LruArcMap::<str, u32>::new(2);
+    assert_eq!(lru.push_str("a", 1), None);
+    assert_eq!(lru.push_str("b", 2), None);
+    let a = Arc::clone(lru.tail_key().unwrap());
+    // One reference is held by the entry, one here, and one by the index
+    // unless it only stores hashes.
+    let references = if cfg!(feature = "hashed-index") { 2 } else { 3 };
+    assert_eq!(Arc::strong_count(&a), references);
+
+    assert_eq!(lru.push_str("a", 3), Some(Removed::PreviousValue(1)));
+    assert!(Arc::ptr_eq(&a, lru.head_key().unwrap()));
+    assert_eq!(Arc::strong_count(&a), references);
+
+    assert_eq!(
+        lru.push_str("c", 4),
+        Some(Removed::Evicted(Arc::from("b"), 2))
+    );
+    assert_eq!(lru.take("a"), Some((a, 3)));
+}
+
+#[test]
+fn arc_values() {
+    let mut lru = LruArcValueMap::new(2);
+    lru.push(1, Arc::new(1));
+    lru.push(2, Arc::new(2));
+
+    let one = lru.get_arc(&1).unwrap();
+    assert_eq!(Arc::strong_count(&one), 2);
+    // get_arc() touched 1, so 2 is evicted.
+    assert_eq!(
+        lru.push(3, Arc::new(3)),
+        Some(Removed::Evicted(2, Arc::new(2)))
+    );
+    assert_eq!(lru.take(&1), Some((1, one.clone())));
+    assert_eq!(Arc::strong_count(&one), 1);
+
+    let shared = SharedLruMap::new(2);
+    shared.push(1, Arc::new(String::from("one")));
+    let one = shared.get_arc(&1).unwrap();
+    assert!(shared.get_arc(&2).is_none());
+    assert!(Arc::ptr_eq(&one, &shared.get(&1).unwrap()));
+    shared.push(1, Arc::new(String::from("uno")));
+    assert_eq!(*one, "one");
+}
+
+fn replay_tests<Map>(mut lru: Map)
+where
+    Map: LruMap<u32, u32>,
+{
+    use crate::replay::{apply, replay, ReplayStats, TraceOperation, TraceOutcome};
+
+    assert_eq!(
+        apply(&mut lru, TraceOperation::Get(1, 1)),
+        TraceOutcome::Miss { evicted: 0 }
+    );
+    assert_eq!(
+        apply(&mut lru, TraceOperation::Get(1, 2)),
+        TraceOutcome::Hit
+    );
+    assert_eq!(lru.get(&1), Some(&1));
+
+    let stats = replay(
+        &mut lru,
+        [
+            TraceOperation::Push(2, 2),
+            TraceOperation::Push(2, 22),
+            TraceOperation::Get(3, 3),
+            TraceOperation::Get(4, 4),
+            TraceOperation::Push(5, 5),
+            TraceOperation::Remove(1),
+            TraceOperation::Remove(5),
+            TraceOperation::Get(3, 33),
+        ],
+    );
+    assert_eq!(
+        stats,
+        ReplayStats {
+            operations: 8,
+            hits: 1,
+            misses: 2,
+            insertions: 2,
+            replacements: 1,
+            removals: 1,
+            evictions: 2,
+        }
+    );
+    assert!((stats.hit_ratio() - 1. / 3.).abs() < f64::EPSILON);
+    assert!(ReplayStats::default().hit_ratio().abs() < f64::EPSILON);
+}
+
+#[test]
+fn hash_replay() {
+    replay_tests(LruHashMap::new(3));
+}
+
+#[test]
+fn btree_replay() {
+    replay_tests(LruBTreeMap::new(3));
+}
+
+#[test]
+fn small_replay() {
+    replay_tests(SmallLruMap::new(3));
+}
+
+#[test]
+fn replay_counts_headroom_evictions() {
+    use crate::replay::{apply, TraceOperation, TraceOutcome};
+
+    let mut lru = LruHashMap::new(3);
+    lru.set_headroom(1);
+    lru.extend([(1, 1), (2, 2), (3, 3)]);
+    assert_eq!(
+        apply(&mut lru, TraceOperation::Push(4, 4)),
+        TraceOutcome::Inserted { evicted: 2 }
+    );
+}
+
+#[test]
+fn session_cache() {
+    use std::time::{Duration, Instant};
+
+    use crate::{SessionCache, SessionEnd};
+
+    let start = Instant::now();
+    let at = |seconds| start + Duration::from_secs(seconds);
+    let mut ended = Vec::new();
+    let mut sessions =
+        SessionCache::with_listener(3, Duration::from_secs(10), |id, session, end| {
+            ended.push((id, session, end));
+        });
+
+    assert_eq!(sessions.insert_at(1, 1, at(0)), None);
+    assert_eq!(sessions.insert_at(2, 2, at(1)), None);
+    assert_eq!(sessions.insert_at(3, 3, at(2)), None);
+    assert_eq!(sessions.insert_at(1, 11, at(3)), Some(1));
+    // 2 is the least recently used session, and is evicted.
+    assert_eq!(sessions.insert_at(4, 4, at(4)), None);
+    // Using a session resets its idle time.
+    *sessions.get_at(&3, at(12)).unwrap() += 30;
+    // 1 was last used 10 seconds ago, which is not longer than the timeout.
+    assert_eq!(sessions.get_at(&1, at(13)), Some(&mut 11));
+    assert_eq!(sessions.get_at(&4, at(15)), None);
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.expire_idle_at(at(22)), 0);
+    assert_eq!(sessions.remove(&1), Some(11));
+    assert_eq!(sessions.expire_idle_at(at(23)), 1);
+    assert!(sessions.is_empty());
+    drop(sessions);
+
+    assert_eq!(
+        ended,
+        [
+            (2, 2, SessionEnd::Evicted),
+            (4, 4, SessionEnd::Expired),
+            (3, 33, SessionEnd::Expired),
+        ]
+    );
+}
+
+#[test]
+fn memoized() {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    use crate::Memoized;
+
+    let calls = Cell::new(0);
+    let mut double = Memoized::new(2, |value: &u32| {
+        calls.set(calls.get() + 1);
+        value * 2
+    });
+    assert_eq!(*double.call(1), 2);
+    assert_eq!(*double.call(2), 4);
+    assert_eq!(*double.call(1), 2);
+    assert_eq!(calls.get(), 2);
+    // 2 is the least recently used output, and is evicted.
+    assert_eq!(*double.call(3), 6);
+    assert_eq!(*double.call(2), 4);
+    assert_eq!(calls.get(), 4);
+    assert_eq!(double.invalidate(&2), Some(4));
+    assert_eq!(double.invalidate(&2), None);
+    assert_eq!(double.len(), 1);
+
+    let start = Instant::now();
+    let at = |seconds| move || start + Duration::from_secs(seconds);
+    calls.set(0);
+    let mut double = Memoized::with_ttl(2, Duration::from_secs(10), |value: &u32| {
+        calls.set(calls.get() + 1);
+        value * 2
+    });
+    assert_eq!(*double.call_at(1, at(0)), 2);
+    // Hits do not extend the time-to-live.
+    assert_eq!(*double.call_at(1, at(10)), 2);
+    assert_eq!(calls.get(), 1);
+    assert_eq!(*double.call_at(1, at(11)), 2);
+    assert_eq!(calls.get(), 2);
+    assert_eq!(double.len(), 1);
+}